@@ -0,0 +1,53 @@
+//! GraphBuilder — construct a flowchart [`Graph`] in code instead of Mermaid text.
+//!
+//! The result is the same AST the parser produces, so [`crate::render_graph`]
+//! draws it exactly like the equivalent DSL.
+
+use crate::types::{
+    Direction, EdgeType, Graph, NodeShape, edge_new, graph_new, node_bare, node_new,
+};
+
+/// Chainable builder for a flowchart [`Graph`]:
+/// `GraphBuilder::new(Direction::TD).node("A", "Start", NodeShape::Rectangle)
+/// .edge("A", "B", EdgeType::Arrow, Some("go")).build()`.
+#[derive(Debug, Clone)]
+pub struct GraphBuilder {
+    graph: Graph,
+}
+
+impl GraphBuilder {
+    pub fn new(direction: Direction) -> Self {
+        let mut graph = graph_new();
+        graph.direction = direction;
+        GraphBuilder { graph }
+    }
+
+    /// Add a node, or replace the label and shape of one already added
+    /// (including one created implicitly by [`GraphBuilder::edge`]).
+    pub fn node(mut self, id: &str, label: &str, shape: NodeShape) -> Self {
+        let node = node_new(id.to_string(), label.to_string(), shape);
+        match self.graph.nodes.iter_mut().find(|n| n.id == id) {
+            Some(existing) => *existing = node,
+            None => self.graph.nodes.push(node),
+        }
+        self
+    }
+
+    /// Add an edge. Endpoints not added yet become bare rectangle nodes, as
+    /// with `A --> B` in the DSL.
+    pub fn edge(mut self, from: &str, to: &str, edge_type: EdgeType, label: Option<&str>) -> Self {
+        for id in [from, to] {
+            if !self.graph.nodes.iter().any(|n| n.id == id) {
+                self.graph.nodes.push(node_bare(id.to_string()));
+            }
+        }
+        let mut edge = edge_new(from.to_string(), to.to_string(), edge_type);
+        edge.label = label.unwrap_or_default().to_string();
+        self.graph.edges.push(edge);
+        self
+    }
+
+    pub fn build(self) -> Graph {
+        self.graph
+    }
+}
@@ -0,0 +1,142 @@
+//! Canvas — a rendered diagram as an editable grid of cells.
+//!
+//! [`render_to_canvas`] lays out and paints a diagram exactly as
+//! [`crate::render_dsl_with_config`] would, then hands back the cells so
+//! callers can overlay their own text and boxes (annotations, watermarks)
+//! before turning it into a string. Coordinates are those of the output:
+//! BT/RL flips and the frame are already applied.
+
+use crate::{
+    CharSet, LineEnding, RenderConfig, RenderError, canvas, cdraw_box, cwrite_str,
+    inline_trivial_dsl, ir_lines, layout_dsl,
+};
+
+/// A grid of terminal cells, one string per cell (empty for the second
+/// column of a wide character). Drawing past the right or bottom edge
+/// grows the grid; nothing is drawn at negative coordinates.
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    inner: canvas::Canvas,
+    line_ending: LineEnding,
+    trailing_newline: bool,
+    trim_trailing: bool,
+}
+
+impl Canvas {
+    /// A blank canvas drawing boxes with `charset`, stringified with
+    /// [`crate::config_default`]'s line options.
+    pub fn new(width: usize, height: usize, charset: CharSet) -> Self {
+        let defaults = crate::config_default();
+        Canvas {
+            inner: canvas::canvas_new(width as i32, height as i32, charset),
+            line_ending: defaults.line_ending,
+            trailing_newline: defaults.trailing_newline,
+            trim_trailing: defaults.trim_trailing,
+        }
+    }
+
+    /// Width of the grid, in cells.
+    pub fn width(&self) -> usize {
+        self.inner.width as usize
+    }
+
+    /// Height of the grid, in rows.
+    pub fn height(&self) -> usize {
+        self.inner.height as usize
+    }
+
+    /// Text of the cell at `(col, row)`; `None` outside the canvas.
+    pub fn cell(&self, col: usize, row: usize) -> Option<&str> {
+        self.inner.cells.get(row)?.get(col).map(String::as_str)
+    }
+
+    /// Write one line of `text` starting at `(col, row)`, over whatever is there.
+    pub fn write_str(&mut self, col: usize, row: usize, text: &str) {
+        self.grow_to(col + crate::display_width(text) as usize, row + 1);
+        cwrite_str(&mut self.inner, col as i32, row as i32, text);
+    }
+
+    /// Outline a `width` × `height` box with its top-left corner at
+    /// `(col, row)`, in the canvas charset. Boxes smaller than 2 × 2 draw nothing.
+    pub fn draw_box(&mut self, col: usize, row: usize, width: usize, height: usize) {
+        self.grow_to(col + width, row + height);
+        let bc = canvas::box_chars_for_charset(self.inner.charset.clone());
+        cdraw_box(
+            &mut self.inner,
+            col as i32,
+            row as i32,
+            width as i32,
+            height as i32,
+            &bc,
+        );
+    }
+
+    /// The canvas as text, with the line ending, trailing newline and
+    /// trimming of the config it was rendered with.
+    pub fn render_to_string(&self) -> String {
+        let mut lines: Vec<String> = self
+            .inner
+            .cells
+            .iter()
+            .map(|row| {
+                let text = row.concat();
+                if self.trim_trailing {
+                    text.trim_end().to_string()
+                } else {
+                    text
+                }
+            })
+            .collect();
+        if self.trim_trailing {
+            while lines.last().is_some_and(|l| l.is_empty()) {
+                lines.pop();
+            }
+        }
+        if lines.is_empty() {
+            return String::new();
+        }
+        let eol = match self.line_ending {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        };
+        let mut out = lines.join(eol);
+        if self.trailing_newline {
+            out.push_str(eol);
+        }
+        out
+    }
+
+    fn grow_to(&mut self, width: usize, height: usize) {
+        let c = &mut self.inner;
+        let width = (width as i32).max(c.width);
+        let height = (height as i32).max(c.height);
+        for row in &mut c.cells {
+            row.resize(width as usize, " ".to_string());
+        }
+        c.cells
+            .resize(height as usize, vec![" ".to_string(); width as usize]);
+        (c.width, c.height) = (width, height);
+    }
+}
+
+/// Render `src` into a [`Canvas`] instead of a string. An empty diagram
+/// gives an empty canvas.
+pub fn render_to_canvas(src: &str, config: &RenderConfig) -> Result<Canvas, RenderError> {
+    let mut canvas = Canvas::new(0, 0, config.charset.clone());
+    canvas.line_ending = config.line_ending.clone();
+    canvas.trailing_newline = config.trailing_newline;
+    canvas.trim_trailing = config.trim_trailing;
+    let lines = match inline_trivial_dsl(src, config)? {
+        Some(lines) => lines,
+        None => {
+            let Some((ir, direction, config)) = layout_dsl(src, config)? else {
+                return Ok(canvas);
+            };
+            ir_lines(&ir, direction, &config)?
+        }
+    };
+    for (row, line) in lines.iter().enumerate() {
+        canvas.write_str(0, row, line);
+    }
+    Ok(canvas)
+}
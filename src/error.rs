@@ -0,0 +1,79 @@
+//! The error type returned by the rendering entry points.
+
+use std::fmt;
+
+/// Why a diagram could not be rendered. Match on the variant to react
+/// programmatically; `Display` gives a one-line message for users.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// Text the parser cannot read, at a 1-based `line` and `col`.
+    /// Rendering skips such text; only [`crate::check_dsl`] reports it.
+    Parse {
+        line: usize,
+        col: usize,
+        msg: String,
+    },
+    /// A Mermaid diagram type other than a flowchart, named by its header
+    /// keyword (`sequenceDiagram`, `pie`, ...).
+    Unsupported(String),
+    /// A configured limit was hit: `max_cells` or `max_subgraph_depth`.
+    LimitExceeded(String),
+    /// A config that could not be read: malformed JSON or an unknown value
+    /// for one of its keys. Only [`crate::config_from_json`] returns it.
+    Config(String),
+    /// Anything else, e.g. output that is not valid UTF-8.
+    Internal(String),
+}
+
+impl RenderError {
+    /// Prefix the message with `diagram N: ` for [`crate::render_many`].
+    /// Only the free-form messages (`LimitExceeded`, `Internal`) take it.
+    pub(crate) fn in_diagram(self, index: usize) -> Self {
+        match self {
+            RenderError::LimitExceeded(msg) => {
+                RenderError::LimitExceeded(format!("diagram {index}: {msg}"))
+            }
+            RenderError::Internal(msg) => RenderError::Internal(format!("diagram {index}: {msg}")),
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Parse { line, col, msg } => write!(f, "line {line}, column {col}: {msg}"),
+            RenderError::Unsupported(kind) => {
+                write!(
+                    f,
+                    "unsupported diagram type '{kind}': only flowcharts render"
+                )
+            }
+            RenderError::LimitExceeded(msg)
+            | RenderError::Config(msg)
+            | RenderError::Internal(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<std::io::Error> for RenderError {
+    /// [`crate::render_dsl_to_writer`] carries render failures inside an
+    /// `InvalidData` error; those come back out as the original variant.
+    fn from(e: std::io::Error) -> Self {
+        match e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<RenderError>())
+        {
+            Some(inner) => inner.clone(),
+            None => RenderError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<std::string::FromUtf8Error> for RenderError {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        RenderError::Internal(e.to_string())
+    }
+}
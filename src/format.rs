@@ -0,0 +1,267 @@
+//! format_dsl — canonical pretty-printer for Mermaid flowchart source.
+//!
+//! The source is parsed and the AST written back out, so the result renders
+//! exactly like the input: one statement per line, four-space indentation
+//! per subgraph level, canonical edge tokens and the statement order the
+//! parser recorded. Comments (other than `%% pos` and `%% route` layout
+//! directives) and statements the renderer ignores (`classDef`, `click`,
+//! unparseable text) are not kept.
+
+use crate::types::{Edge, EdgeType, Graph, Node, NodeShape, Subgraph};
+use crate::{RenderError, config_default, is_bare_node, rust_parser, split_front_matter};
+
+const INDENT: &str = "    ";
+
+/// Ids the parser reads as keywords at the start of a statement; nodes with
+/// these ids are always written with their shape (`end[end]`).
+const RESERVED: &[&str] = &[
+    "end",
+    "subgraph",
+    "direction",
+    "class",
+    "classDef",
+    "style",
+    "rank",
+    "accTitle",
+    "accDescr",
+];
+
+/// Parse `src` and re-emit it in canonical form. Formatting is idempotent:
+/// formatting the output again returns it unchanged. Fails only with
+/// [`RenderError::LimitExceeded`] when subgraphs nest too deeply.
+pub fn format_dsl(src: &str) -> Result<String, RenderError> {
+    if src.trim().is_empty() {
+        return Ok(String::new());
+    }
+    let (front_matter, body) = split_front_matter(src);
+    let max_depth = config_default().max_subgraph_depth.max(0) as usize;
+    let (graph, _) = rust_parser::parse_flowchart_with_skips(body, max_depth)?;
+
+    let mut out = String::new();
+    if let Some(block) = front_matter {
+        out.push_str("---\n");
+        out.push_str(block);
+        out.push_str("---\n");
+    }
+    out.push_str(&format!("graph {}\n", graph.direction.as_str()));
+
+    if !graph.acc_title.is_empty() {
+        out.push_str(&format!("{INDENT}accTitle: {}\n", graph.acc_title));
+    }
+    if graph.acc_descr.contains('\n') {
+        out.push_str(&format!("{INDENT}accDescr {{\n"));
+        for line in graph.acc_descr.lines() {
+            out.push_str(&format!("{INDENT}{INDENT}{line}\n"));
+        }
+        out.push_str(&format!("{INDENT}}}\n"));
+    } else if !graph.acc_descr.is_empty() {
+        out.push_str(&format!("{INDENT}accDescr: {}\n", graph.acc_descr));
+    }
+
+    let mut f = Formatter { out };
+    f.scope(&graph.nodes, &graph.edges, &graph.subgraphs, 1);
+    f.class_statements(&graph);
+    for style in &graph.style_assignments {
+        f.line(1, &format!("style {} {}", style.key, style.value));
+    }
+    for group in &graph.same_ranks {
+        f.line(1, &format!("rank same {}", group.join(" ")));
+    }
+    for pin in &graph.pins {
+        f.line(1, &format!("%% pos {} {} {}", pin.id, pin.layer, pin.order));
+    }
+    for route in &graph.routes {
+        let (from, to) = (&route.from_id, &route.to_id);
+        f.line(1, &format!("%% route {from} {to} col={}", route.col));
+    }
+    Ok(f.out)
+}
+
+struct Formatter {
+    out: String,
+}
+
+impl Formatter {
+    fn line(&mut self, depth: usize, text: &str) {
+        self.out.push_str(&INDENT.repeat(depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Node lines, then edges, then nested subgraphs: the order the parser
+    /// keeps them in. Nodes the edges introduce in the same order anyway
+    /// get no line of their own.
+    fn scope(&mut self, nodes: &[Node], edges: &[Edge], subgraphs: &[Subgraph], depth: usize) {
+        for node in &nodes[..implicit_start(nodes, edges)] {
+            let mut classes = node_classes(node);
+            let first = classes
+                .next()
+                .map(|c| format!(":::{c}"))
+                .unwrap_or_default();
+            let text = node_text(node);
+            self.line(depth, &format!("{text}{first}"));
+            for class in classes {
+                self.line(depth, &format!("{}:::{class}", node.id));
+            }
+        }
+        for edge in edges {
+            let from = reference(nodes, &edge.from_id);
+            let to = reference(nodes, &edge.to_id);
+            let label = if edge.label.is_empty() {
+                String::new()
+            } else {
+                format!("|{}|", edge.label.replace('\n', "<br>"))
+            };
+            let token = edge_token(&edge.edge_type);
+            let classes: String = (edge.attrs.iter())
+                .filter(|a| a.key == "class")
+                .map(|a| format!(":::{}", a.value))
+                .collect();
+            self.line(depth, &format!("{from} {token}{label}{classes} {to}"));
+        }
+        for sg in subgraphs {
+            self.line(depth, &format!("subgraph {}", subgraph_name(&sg.name)));
+            if sg.has_direction {
+                self.line(depth + 1, &format!("direction {}", sg.direction.as_str()));
+            }
+            self.scope(&sg.nodes, &sg.edges, &sg.subgraphs, depth + 1);
+            self.line(depth, "end");
+        }
+    }
+
+    /// `class A,B name`, merging consecutive assignments of the same class.
+    fn class_statements(&mut self, graph: &Graph) {
+        let mut i = 0;
+        let assignments = &graph.class_assignments;
+        while i < assignments.len() {
+            let class = &assignments[i].value;
+            let mut ids = Vec::new();
+            while i < assignments.len() && &assignments[i].value == class {
+                ids.push(assignments[i].key.as_str());
+                i += 1;
+            }
+            self.line(1, &format!("class {} {class}", ids.join(",")));
+        }
+    }
+}
+
+/// Index of the first node from which every node is bare, unclassed and
+/// first mentioned by `edges` in list order, so re-parsing the edges alone
+/// recreates them in place.
+fn implicit_start(nodes: &[Node], edges: &[Edge]) -> usize {
+    let mut mentioned: Vec<&str> = Vec::new();
+    for e in edges {
+        for id in [e.from_id.as_str(), e.to_id.as_str()] {
+            if !mentioned.contains(&id) {
+                mentioned.push(id);
+            }
+        }
+    }
+    let mut start = nodes.len();
+    while start > 0 {
+        let n = &nodes[start - 1];
+        if !is_bare_node(n) || !n.attrs.is_empty() || !mentioned.contains(&n.id.as_str()) {
+            break;
+        }
+        start -= 1;
+    }
+    loop {
+        let suffix: Vec<&str> = nodes[start..].iter().map(|n| n.id.as_str()).collect();
+        let order: Vec<&str> = (mentioned.iter().copied())
+            .filter(|id| suffix.contains(id))
+            .collect();
+        if order == suffix {
+            return start;
+        }
+        start += 1;
+    }
+}
+
+/// A node id as an edge endpoint, with its shape when the id is reserved.
+/// The parser records every endpoint in the scope's own node list.
+fn reference(nodes: &[Node], id: &str) -> String {
+    match nodes.iter().find(|n| n.id == id) {
+        Some(node) if RESERVED.contains(&id) => node_text(node),
+        _ => id.to_string(),
+    }
+}
+
+fn node_classes(node: &Node) -> impl Iterator<Item = &str> {
+    node.attrs
+        .iter()
+        .filter(|a| a.key == "class")
+        .map(|a| a.value.as_str())
+}
+
+/// `A`, `A[Label]`, `A(Label)`, `A{Label}` or `A((Label))`; labels the
+/// bracket syntax cannot carry verbatim are quoted.
+fn node_text(node: &Node) -> String {
+    if is_bare_node(node) && !RESERVED.contains(&node.id.as_str()) {
+        return node.id.clone();
+    }
+    let (open, close) = match node.shape {
+        NodeShape::Rounded => ("(", ")"),
+        NodeShape::Diamond => ("{", "}"),
+        NodeShape::Circle => ("((", "))"),
+        _ => ("[", "]"),
+    };
+    let label = &node.label;
+    let (o, c) = (open.chars().next().unwrap(), close.chars().next().unwrap());
+    let mut depth = 0;
+    let balanced = label.chars().all(|ch| {
+        depth += (ch == o) as i32 - (ch == c) as i32;
+        depth >= 0
+    }) && depth == 0;
+    let needs_quotes = !balanced
+        || label.is_empty()
+        || label.trim() != label
+        || label.contains('\n')
+        || label.starts_with('"')
+        // `A((x))` would read a rounded `(x)` label as a circle
+        || (node.shape == NodeShape::Rounded && label.starts_with('('));
+    if !needs_quotes {
+        format!("{}{open}{label}{close}", node.id)
+    } else {
+        format!("{}{open}{}{close}", node.id, quoted(label))
+    }
+}
+
+/// The rest of the `subgraph` line is the name; names that would not survive
+/// that (line breaks, comments, edge blanks, a leading quote) are quoted.
+fn subgraph_name(name: &str) -> String {
+    let plain = !name.contains(['\n', '\r'])
+        && !name.contains("%%")
+        && name.trim() == name
+        && !name.starts_with('"');
+    if !plain {
+        quoted(name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn quoted(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+fn edge_token(edge_type: &EdgeType) -> &'static str {
+    match edge_type {
+        EdgeType::Line => "---",
+        EdgeType::DottedArrow => "-.->",
+        EdgeType::DottedLine => "-.-",
+        EdgeType::ThickArrow => "==>",
+        EdgeType::ThickLine => "===",
+        EdgeType::BidirArrow => "<-->",
+        EdgeType::BidirDotted => "<-.->",
+        EdgeType::BidirThick => "<==>",
+        EdgeType::CircleArrow => "--o",
+        EdgeType::CrossArrow => "--x",
+        EdgeType::BidirCircle => "o--o",
+        EdgeType::BidirCross => "x--x",
+        _ => "-->",
+    }
+}
@@ -33,6 +33,8 @@ pub struct EdgeData {
     /// "ThickArrow", "ThickLine", "BidirArrow", "BidirDotted", "BidirThick".
     pub edge_type: String,
     pub label: Option<String>,
+    /// Classes from `A -->:::name B`, in source order.
+    pub classes: Vec<String>,
 }
 
 /// Directed graph wrapper — the central data structure for layout phases.
@@ -66,15 +68,16 @@ pub fn graph_new() -> Graph {
 
 // ── Mutation ─────────────────────────────────────────────────────────────────
 
-/// Add a node. No-op if a node with the same `id` already exists.
-pub fn graph_add_node(
-    g: &mut Graph,
-    id: &str,
-    label: &str,
-    shape: &str,
-    subgraph: Option<&str>,
-) {
-    if g.node_index.contains_key(id) {
+/// Add a node. If a node with the same `id` already exists its label and
+/// shape are kept; it only takes on `subgraph` when it had no membership yet
+/// (a node declared at top level, or created by an edge, then listed in a
+/// subgraph).
+pub fn graph_add_node(g: &mut Graph, id: &str, label: &str, shape: &str, subgraph: Option<&str>) {
+    if let Some(&idx) = g.node_index.get(id) {
+        let existing = &mut g.digraph[idx];
+        if existing.subgraph.is_none() {
+            existing.subgraph = subgraph.map(|s| s.to_string());
+        }
         return;
     }
     let data = NodeData {
@@ -98,14 +101,21 @@ pub fn graph_add_edge(
     edge_type: &str,
     label: Option<&str>,
 ) {
-    graph_ensure_node(g, from_id);
-    graph_ensure_node(g, to_id);
-    let from_idx = g.node_index[from_id];
-    let to_idx = g.node_index[to_id];
     let data = EdgeData {
         edge_type: edge_type.to_string(),
         label: label.map(|l| l.to_string()),
+        classes: Vec::new(),
     };
+    graph_add_edge_data(g, from_id, to_id, data);
+}
+
+/// [`graph_add_edge`] with every field of the edge given, e.g. a copy of an
+/// edge from another graph that keeps its classes.
+pub fn graph_add_edge_data(g: &mut Graph, from_id: &str, to_id: &str, data: EdgeData) {
+    graph_ensure_node(g, from_id);
+    graph_ensure_node(g, to_id);
+    let from_idx = g.node_index[from_id];
+    let to_idx = g.node_index[to_id];
     g.digraph.add_edge(from_idx, to_idx, data);
 }
 
@@ -279,6 +289,11 @@ mod tests {
         // Original data kept
         let idx = g.node_index["A"];
         assert_eq!(g.digraph[idx].label, "Node A");
+        // ...apart from a first subgraph membership
+        graph_add_node(&mut g, "A", "A", "Rectangle", Some("G"));
+        graph_add_node(&mut g, "A", "A", "Rectangle", Some("H"));
+        assert_eq!(g.digraph[idx].label, "Node A");
+        assert_eq!(g.digraph[idx].subgraph.as_deref(), Some("G"));
     }
 
     #[test]
@@ -74,6 +74,7 @@
 //     gw_node_label(g, id)       -> String
 //     gw_node_shape(g, id)       -> String
 //     gw_copy(g)                 -> Graph
+//     gw_is_dag(g)               -> bool
 //     gw_edges_full(g)           -> EdgeInfoList
 //
 //   FAS helpers (encapsulate the set-membership scan)
@@ -387,6 +388,10 @@ pub fn gw_copy(g: Graph) -> Graph {
     graph_copy(&g)
 }
 
+pub fn gw_is_dag(g: Graph) -> bool {
+    graph_is_dag(&g)
+}
+
 /// Return all edges as an EdgeInfoList: (from_id, to_id, edge_type, label).
 /// label="" when the original EdgeData.label was None.
 pub fn gw_edges_full(g: Graph) -> EdgeInfoList {
@@ -831,6 +836,7 @@ pub struct EdgeRouteInfo {
     pub label: String,    // "" = no label
     pub edge_type: String,
     pub waypoints: Vec<(i32, i32)>,
+    pub classes: Vec<String>,
 }
 
 pub type EdgeRouteList = std::rc::Rc<std::cell::RefCell<Vec<EdgeRouteInfo>>>;
@@ -853,9 +859,15 @@ pub fn erl_push(
         label,
         edge_type,
         waypoints,
+        classes: Vec::new(),
     });
 }
 
+/// Edge classes (`A -->:::name B`) of a routed edge; `erl_push` leaves them empty.
+pub fn erl_set_classes(el: EdgeRouteList, idx: i32, classes: Vec<String>) {
+    el.borrow_mut()[idx as usize].classes = classes;
+}
+
 pub fn erl_len(el: EdgeRouteList) -> i32 {
     el.borrow().len() as i32
 }
@@ -876,6 +888,10 @@ pub fn erl_get_etype(el: EdgeRouteList, idx: i32) -> String {
     el.borrow()[idx as usize].edge_type.clone()
 }
 
+pub fn erl_get_classes(el: EdgeRouteList, idx: i32) -> Vec<String> {
+    el.borrow()[idx as usize].classes.clone()
+}
+
 pub fn erl_get_waypoint_count(el: EdgeRouteList, idx: i32) -> i32 {
     el.borrow()[idx as usize].waypoints.len() as i32
 }
@@ -88,23 +88,86 @@ mod layout {
     include!(concat!(env!("OUT_DIR"), "/layout.rs"));
 }
 
+pub use charset::{BoxCharsOverride, CharSet, box_chars_override_none, diamond_corners_unicode};
+pub use config::{
+    DrawOrder, LineEnding, RenderConfig, TextAlign, TitlePos, config_default, config_new,
+};
+pub use types::{Direction, EdgeType, Graph, NodeShape};
+
+// Programmatic graph construction (hand-written Rust)
+pub mod builder;
+pub use builder::GraphBuilder;
+
+// Canonical source formatter (hand-written Rust)
+pub mod format;
+pub use format::format_dsl;
+
+// Occupancy grid and pluggable edge routers (hand-written Rust)
+pub mod routing;
+pub use routing::{AStarRouter, EdgeRouter};
+
+// Error type of the render entry points (hand-written Rust)
+pub mod error;
+pub use error::RenderError;
+
+// Rendered diagrams as editable cell grids (hand-written Rust)
+pub mod compose;
+pub use compose::{Canvas, render_to_canvas};
+
 // ── Rust-native parser (bypasses broken .hom parser due to .clone() semantics) ──
 
 mod rust_parser {
     //! Recursive descent parser for Mermaid flowchart syntax.
     //! Produces the same types as the .hom parser module.
-    use super::parser;
+    use super::{RenderError, parser};
 
     struct Cursor {
         src: Vec<char>,
         pos: usize,
+        /// `class ids name` statements seen anywhere, as (node id, class).
+        class_assignments: Vec<parser::Attr>,
+        /// `style id styles` statements seen anywhere, as (node id, styles).
+        style_assignments: Vec<parser::Attr>,
+        /// `rank same ids` statements seen anywhere, one id list each.
+        same_ranks: Vec<Vec<String>>,
+        /// Positions of characters skipped because no statement parses there.
+        skipped: Vec<usize>,
+        /// Subgraph blocks currently open, and how many may be.
+        depth: usize,
+        max_depth: usize,
+        /// Set (and parsing stopped) when a block would exceed `max_depth`.
+        too_deep: bool,
+        /// `accTitle` / `accDescr` text, wherever they appear.
+        acc_title: String,
+        acc_descr: String,
+        /// Mermaid features recognised but not drawn, each named once.
+        unsupported: Vec<String>,
+        /// `%% pos` and `%% route` directives, in source order.
+        pins: Vec<parser::NodePin>,
+        routes: Vec<parser::EdgeRoute>,
+        /// End of the furthest comment read; one reached again after
+        /// backtracking has its directives recorded already.
+        comments_read: usize,
     }
 
     impl Cursor {
-        fn new(s: &str) -> Self {
+        fn new(s: &str, max_depth: usize) -> Self {
             Cursor {
                 src: s.chars().collect(),
                 pos: 0,
+                class_assignments: Vec::new(),
+                style_assignments: Vec::new(),
+                same_ranks: Vec::new(),
+                skipped: Vec::new(),
+                depth: 0,
+                max_depth,
+                too_deep: false,
+                acc_title: String::new(),
+                acc_descr: String::new(),
+                unsupported: Vec::new(),
+                pins: Vec::new(),
+                routes: Vec::new(),
+                comments_read: 0,
             }
         }
         fn eof(&self) -> bool {
@@ -133,27 +196,91 @@ mod rust_parser {
         fn ch(&self) -> char {
             if self.eof() { '\0' } else { self.src[self.pos] }
         }
+        /// Consume `kw` only when it stands alone, followed by a space or tab.
+        fn consume_keyword(&mut self, kw: &str) -> bool {
+            let n = kw.chars().count();
+            if self.peek_str(kw) && matches!(self.src.get(self.pos + n), Some(' ' | '\t')) {
+                self.pos += n;
+                true
+            } else {
+                false
+            }
+        }
+        fn match_class_name(&mut self) -> String {
+            let start = self.pos;
+            while !self.eof()
+                && (self.ch().is_alphanumeric() || self.ch() == '_' || self.ch() == '-')
+            {
+                self.pos += 1;
+            }
+            self.src[start..self.pos].iter().collect()
+        }
+        fn skip_to_eol(&mut self) {
+            while !self.eof() && self.ch() != '\n' {
+                self.pos += 1;
+            }
+        }
         fn skip_ws(&mut self) {
             loop {
                 if self.pos < self.src.len() && (self.ch() == ' ' || self.ch() == '\t') {
                     self.pos += 1;
                 } else if self.peek_str("%%") {
-                    while self.pos < self.src.len() && self.ch() != '\n' {
-                        self.pos += 1;
-                    }
+                    self.skip_comment();
                 } else {
                     break;
                 }
             }
         }
+        /// Record `feature` as read but not drawn, once.
+        fn note_unsupported(&mut self, feature: &str) {
+            if !self.unsupported.iter().any(|f| f == feature) {
+                self.unsupported.push(feature.to_string());
+            }
+        }
+        /// Step over a character no statement could start at. A `;`
+        /// statement separator is expected there and not recorded.
+        fn skip_unparsed(&mut self) {
+            if self.ch() != ';' {
+                self.skipped.push(self.pos);
+            }
+            self.pos += 1;
+        }
+        /// Step over a `%%` comment to the end of its line, recording the
+        /// `%% pos A 3 0` pin or `%% route A B col=10` hint it may hold.
+        /// Malformed directives are ignored.
+        fn skip_comment(&mut self) {
+            let start = self.pos + 2;
+            self.skip_to_eol();
+            if start <= self.comments_read {
+                return;
+            }
+            self.comments_read = self.pos;
+            let text: String = self.src[start..self.pos].iter().collect();
+            let words: Vec<&str> = text.split_whitespace().collect();
+            if let ["pos", id, layer, order] = words[..]
+                && let (Ok(layer), Ok(order)) = (layer.parse::<u16>(), order.parse::<u16>())
+            {
+                self.pins.push(parser::NodePin {
+                    id: id.to_string(),
+                    layer: layer as i32,
+                    order: order as i32,
+                });
+            } else if let ["route", from, to, col] = words[..]
+                && let Some(Ok(col)) = col.strip_prefix("col=").map(str::parse::<u16>)
+            {
+                self.routes.push(parser::EdgeRoute {
+                    from_id: from.to_string(),
+                    to_id: to.to_string(),
+                    col: col as i32,
+                });
+            }
+        }
         fn skip_ws_and_newlines(&mut self) {
             loop {
                 if self.pos < self.src.len() && matches!(self.ch(), ' ' | '\t' | '\n' | '\r') {
                     self.pos += 1;
                 } else if self.peek_str("%%") {
-                    while self.pos < self.src.len() && self.ch() != '\n' {
-                        self.pos += 1;
-                    }
+                    self.skip_comment();
                 } else {
                     break;
                 }
@@ -174,8 +301,19 @@ mod rust_parser {
             let start = self.pos;
             if self.pos < self.src.len() && (self.ch().is_ascii_alphabetic() || self.ch() == '_') {
                 self.pos += 1;
+                // A dot joins two id parts (`node.one`); one followed by
+                // anything else starts a connector such as `-.->`.
+                let joins_parts = |c: &Self| {
+                    c.ch() == '.'
+                        && c.src
+                            .get(c.pos + 1)
+                            .is_some_and(|&n| n.is_ascii_alphanumeric() || n == '_')
+                };
                 while self.pos < self.src.len()
-                    && (self.ch().is_ascii_alphanumeric() || self.ch() == '_' || self.ch() == '-')
+                    && (self.ch().is_ascii_alphanumeric()
+                        || self.ch() == '_'
+                        || self.ch() == '-'
+                        || joins_parts(self))
                 {
                     self.pos += 1;
                 }
@@ -228,13 +366,24 @@ mod rust_parser {
         buf
     }
 
-    fn parse_node_label(c: &mut Cursor, closers: &[char]) -> String {
-        c.skip_ws();
+    /// Label text up to the first `close` that isn't matched by an earlier
+    /// `open` (so `A[arr[0]]` keeps `arr[0]`), or the end of the line.
+    fn parse_node_label(c: &mut Cursor, open: char, close: char) -> String {
+        // Spaces only: `%%` inside the brackets is label text, not a comment
+        while !c.eof() && (c.ch() == ' ' || c.ch() == '\t') {
+            c.pos += 1;
+        }
         if !c.eof() && c.ch() == '"' {
             return parse_quoted_string(c);
         }
         let start = c.pos;
-        while !c.eof() && !closers.contains(&c.ch()) && c.ch() != '\n' {
+        let mut depth = 0;
+        while !c.eof() && c.ch() != '\n' && !(c.ch() == close && depth == 0) {
+            if c.ch() == open {
+                depth += 1;
+            } else if c.ch() == close {
+                depth -= 1;
+            }
             c.pos += 1;
         }
         c.src[start..c.pos]
@@ -246,19 +395,19 @@ mod rust_parser {
 
     fn parse_node_shape(c: &mut Cursor) -> (bool, parser::NodeShape, String) {
         if c.consume_str("((") {
-            let label = parse_node_label(c, &[')']);
+            let label = parse_node_label(c, '(', ')');
             c.consume_str("))");
             (true, parser::NodeShape::Circle, label)
         } else if c.consume_str("(") {
-            let label = parse_node_label(c, &[')']);
+            let label = parse_node_label(c, '(', ')');
             c.consume_str(")");
             (true, parser::NodeShape::Rounded, label)
         } else if c.consume_str("{") {
-            let label = parse_node_label(c, &['}']);
+            let label = parse_node_label(c, '{', '}');
             c.consume_str("}");
             (true, parser::NodeShape::Diamond, label)
         } else if c.consume_str("[") {
-            let label = parse_node_label(c, &[']']);
+            let label = parse_node_label(c, '[', ']');
             c.consume_str("]");
             (true, parser::NodeShape::Rectangle, label)
         } else {
@@ -273,65 +422,186 @@ mod rust_parser {
             return parser::node_bare(String::new());
         }
         let (found, shape, label) = parse_node_shape(c);
-        if found {
+        let mut node = if found {
+            // Unquoted whitespace trims to nothing and falls back to the id;
+            // a quoted blank (`A[" "]`) survives as an intentional spacer.
+            let label = if label.is_empty() { id.clone() } else { label };
             parser::node_new(id.clone(), label, shape)
         } else {
             parser::node_bare(id)
+        };
+        // `A@{ shape: ... }` node data is read past but not applied
+        if c.peek_str("@{") {
+            while !c.eof() && c.ch() != '}' {
+                c.pos += 1;
+            }
+            c.consume_str("}");
+            c.note_unsupported("@{}");
+        }
+        // `A:::name` / `A[Label]:::name` attaches a class
+        let saved = c.pos;
+        if c.consume_str(":::") {
+            let class = c.match_class_name();
+            if class.is_empty() {
+                c.pos = saved;
+            } else {
+                node.attrs.push(parser::Attr {
+                    key: "class".to_string(),
+                    value: class,
+                });
+            }
         }
+        node
     }
 
     struct EdgeMatch {
         token: &'static str,
         etype: parser::EdgeType,
+        /// Left-pointing (`B <-- A`): the edge runs from the right-hand node.
+        reversed: bool,
     }
 
     const EDGE_PATTERNS: &[EdgeMatch] = &[
         EdgeMatch {
             token: "<-.->",
             etype: parser::EdgeType::BidirDotted,
+            reversed: false,
+        },
+        EdgeMatch {
+            token: "o--o",
+            etype: parser::EdgeType::BidirCircle,
+            reversed: false,
+        },
+        EdgeMatch {
+            token: "x--x",
+            etype: parser::EdgeType::BidirCross,
+            reversed: false,
         },
         EdgeMatch {
             token: "<==>",
             etype: parser::EdgeType::BidirThick,
+            reversed: false,
         },
         EdgeMatch {
             token: "<-->",
             etype: parser::EdgeType::BidirArrow,
+            reversed: false,
+        },
+        EdgeMatch {
+            token: "<-.-",
+            etype: parser::EdgeType::DottedArrow,
+            reversed: true,
         },
         EdgeMatch {
             token: "-.->",
             etype: parser::EdgeType::DottedArrow,
+            reversed: false,
+        },
+        EdgeMatch {
+            token: "<==",
+            etype: parser::EdgeType::ThickArrow,
+            reversed: true,
         },
         EdgeMatch {
             token: "==>",
             etype: parser::EdgeType::ThickArrow,
+            reversed: false,
+        },
+        EdgeMatch {
+            token: "<--",
+            etype: parser::EdgeType::Arrow,
+            reversed: true,
         },
         EdgeMatch {
             token: "-->",
             etype: parser::EdgeType::Arrow,
+            reversed: false,
         },
         EdgeMatch {
             token: "-.-",
             etype: parser::EdgeType::DottedLine,
+            reversed: false,
         },
         EdgeMatch {
             token: "===",
             etype: parser::EdgeType::ThickLine,
+            reversed: false,
         },
         EdgeMatch {
             token: "---",
             etype: parser::EdgeType::Line,
+            reversed: false,
+        },
+        EdgeMatch {
+            token: "--o",
+            etype: parser::EdgeType::CircleArrow,
+            reversed: false,
+        },
+        EdgeMatch {
+            token: "--x",
+            etype: parser::EdgeType::CrossArrow,
+            reversed: false,
         },
     ];
 
-    fn parse_edge_connector(c: &mut Cursor) -> parser::EdgeType {
+    /// Connectors carrying their label inline (`A -- text --> B`): an opening
+    /// token, the closing token that ends the text, and the edge type.
+    const INLINE_LABEL_PATTERNS: &[(&str, &str, parser::EdgeType)] = &[
+        ("o--", "--o", parser::EdgeType::BidirCircle),
+        ("x--", "--x", parser::EdgeType::BidirCross),
+        ("--", "-->", parser::EdgeType::Arrow),
+        ("--", "---", parser::EdgeType::Line),
+        ("--", "--o", parser::EdgeType::CircleArrow),
+        ("--", "--x", parser::EdgeType::CrossArrow),
+        ("-.", ".->", parser::EdgeType::DottedArrow),
+        ("-.", ".-", parser::EdgeType::DottedLine),
+        ("==", "==>", parser::EdgeType::ThickArrow),
+        ("==", "===", parser::EdgeType::ThickLine),
+    ];
+
+    /// Connector type and whether it points left (`<--`).
+    fn parse_edge_connector(c: &mut Cursor) -> (parser::EdgeType, bool) {
         c.skip_ws();
         for em in EDGE_PATTERNS {
             if c.consume_str(em.token) {
-                return em.etype.clone();
+                return (em.etype.clone(), em.reversed);
+            }
+        }
+        (parser::EdgeType::None, false)
+    }
+
+    /// An inline-label connector such as `-- text -->` or `o-- text --o`:
+    /// the opening token must be followed by whitespace and the closing one
+    /// preceded by it. Returns the edge type and the trimmed text; leaves the
+    /// cursor in place and returns `None` when nothing matches on this line.
+    fn parse_inline_label_connector(c: &mut Cursor) -> Option<(parser::EdgeType, String)> {
+        c.skip_ws();
+        let is_space = |i: usize| matches!(c.src.get(i), Some(' ' | '\t'));
+        // (text start, closing token start, closing token length, type) of
+        // the pattern whose closing token comes first
+        let mut best: Option<(usize, usize, usize, &parser::EdgeType)> = None;
+        for (open, close, etype) in INLINE_LABEL_PATTERNS {
+            let start = c.pos + open.len();
+            if !c.peek_str(open) || !is_space(start) {
+                continue;
+            }
+            let close: Vec<char> = close.chars().collect();
+            let found = (start + 1..c.src.len())
+                .take_while(|&i| c.src[i] != '\n')
+                .find(|&i| is_space(i - 1) && c.src[i..].starts_with(&close));
+            if let Some(i) = found
+                && best.is_none_or(|(_, at, _, _)| i < at)
+            {
+                best = Some((start, i, close.len(), etype));
             }
         }
-        parser::EdgeType::None
+        let (start, at, len, etype) = best?;
+        let text: String = c.src[start..at].iter().collect();
+        if text.trim().is_empty() {
+            return None;
+        }
+        c.pos = at + len;
+        Some((etype.clone(), label_line_breaks(text.trim())))
     }
 
     fn parse_edge_label(c: &mut Cursor) -> String {
@@ -345,9 +615,50 @@ mod rust_parser {
         }
         let text: String = c.src[start..c.pos].iter().collect();
         c.consume_str("|");
-        text.trim().to_string()
+        label_line_breaks(text.trim())
+    }
+
+    /// `<br>` (also `<br/>`, `<br />`, any case) and a literal `\n` in an
+    /// edge label start a new line; each line is trimmed.
+    fn label_line_breaks(text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let lower = rest.to_ascii_lowercase();
+            let tag = ["<br>", "<br/>", "<br />", "\\n"]
+                .iter()
+                .find(|t| lower.starts_with(*t));
+            if let Some(tag) = tag {
+                out.push('\n');
+                rest = &rest[tag.len()..];
+            } else {
+                let ch = rest.chars().next().unwrap();
+                out.push(ch);
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+        out.split('\n')
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// `:::name` right after a connector or its label (`A -->|x|:::hot B`)
+    /// attaches a class to that edge; empty when absent.
+    fn parse_edge_class(c: &mut Cursor) -> String {
+        let saved = c.pos;
+        if !c.consume_str(":::") {
+            return String::new();
+        }
+        let class = c.match_class_name();
+        if class.is_empty() {
+            c.pos = saved;
+        }
+        class
     }
 
+    /// `end` closing a subgraph body. A shaped or classed `end[Finish]` /
+    /// `end:::x` is a node named `end`, as is any longer id like `ending` or `end.x`.
     fn at_end_keyword(c: &Cursor) -> bool {
         if !c.peek_str("end") {
             return false;
@@ -357,7 +668,12 @@ mod rust_parser {
             return true;
         }
         let ch = c.src[after];
-        !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+        let rest: String = c.src[after..].iter().take(3).collect();
+        let dotted = ch == '.'
+            && c.src
+                .get(after + 1)
+                .is_some_and(|n| n.is_ascii_alphanumeric() || *n == '_');
+        !(ch.is_ascii_alphanumeric() || "_-[({".contains(ch) || rest == ":::" || dotted)
     }
 
     fn parse_statement_into(
@@ -371,9 +687,138 @@ mod rust_parser {
             return false;
         }
 
+        // `accTitle: text`, `accDescr: text` or a multi-line `accDescr { ... }`
+        for key in ["accTitle", "accDescr"] {
+            let saved = c.pos;
+            let after = c.src.get(c.pos + key.len());
+            if !c.peek_str(key) || !matches!(after, Some(':' | '{' | ' ' | '\t')) {
+                continue;
+            }
+            c.pos += key.len();
+            c.skip_ws();
+            let text = if c.consume_str(":") {
+                let start = c.pos;
+                c.skip_to_eol();
+                c.src[start..c.pos].iter().collect::<String>()
+            } else if c.consume_str("{") {
+                let start = c.pos;
+                while !c.eof() && c.ch() != '}' {
+                    c.pos += 1;
+                }
+                let text: String = c.src[start..c.pos].iter().collect();
+                c.consume_str("}");
+                let lines: Vec<&str> = text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .collect();
+                lines.join("\n")
+            } else {
+                c.pos = saved;
+                continue;
+            };
+            let text = text.trim().to_string();
+            if key == "accTitle" {
+                c.acc_title = text;
+            } else {
+                c.acc_descr = text;
+            }
+            return true;
+        }
+
+        // `style A fill:#f00,stroke-width:4px` — kept whole, split when resolved
+        let saved = c.pos;
+        if c.consume_keyword("style") {
+            c.skip_ws();
+            let id = c.match_node_id();
+            c.skip_ws();
+            let start = c.pos;
+            c.skip_to_eol();
+            let styles: String = c.src[start..c.pos].iter().collect();
+            let styles = styles.trim().trim_end_matches(';').trim_end();
+            if !id.is_empty() && !styles.is_empty() {
+                c.style_assignments.push(parser::Attr {
+                    key: id,
+                    value: styles.to_string(),
+                });
+                return true;
+            }
+        }
+        c.pos = saved;
+
+        // `rank same A B C` (Graphviz-style): put the nodes on one layer
+        let saved = c.pos;
+        if c.consume_keyword("rank") {
+            c.skip_ws();
+            if c.consume_keyword("same") {
+                let mut ids = Vec::new();
+                loop {
+                    c.skip_ws();
+                    c.consume_str(",");
+                    c.skip_ws();
+                    let id = c.match_node_id();
+                    if id.is_empty() {
+                        break;
+                    }
+                    ids.push(id);
+                }
+                if !ids.is_empty() {
+                    c.same_ranks.push(ids);
+                    c.skip_to_eol();
+                    return true;
+                }
+            }
+        }
+        c.pos = saved;
+
+        // Interaction, per-edge styling and `classDef name styles` (class
+        // styles are not rendered): noted, then skipped
+        for kw in ["click", "linkStyle", "classDef"] {
+            if c.consume_keyword(kw) {
+                c.note_unsupported(kw);
+                c.skip_to_eol();
+                return true;
+            }
+        }
+
+        // `class A,B name`
+        let saved = c.pos;
+        if c.consume_keyword("class") {
+            let mut ids = Vec::new();
+            loop {
+                c.skip_ws();
+                let id = c.match_node_id();
+                if id.is_empty() {
+                    break;
+                }
+                ids.push(id);
+                c.skip_ws();
+                if !c.consume_str(",") {
+                    break;
+                }
+            }
+            c.skip_ws();
+            let class = c.match_class_name();
+            if !ids.is_empty() && !class.is_empty() {
+                for id in ids {
+                    c.class_assignments.push(parser::Attr {
+                        key: id,
+                        value: class.clone(),
+                    });
+                }
+                c.skip_to_eol();
+                return true;
+            }
+        }
+        c.pos = saved;
+
         // Try subgraph
         let saved = c.pos;
         let sg = parse_subgraph_block(c);
+        if c.too_deep {
+            c.pos = c.src.len();
+            return true;
+        }
         if !sg.name.is_empty() {
             subgraphs.push(sg);
             return true;
@@ -382,42 +827,61 @@ mod rust_parser {
 
         // Try edge statement
         let saved = c.pos;
-        let src_node = parse_node_ref(c);
-        if !src_node.id.is_empty() {
-            let mut chain_segs: Vec<(parser::EdgeType, String, parser::Node)> = Vec::new();
+        let src_nodes = parse_node_group(c);
+        if !src_nodes.is_empty() {
+            let mut chain_segs = Vec::new();
             loop {
                 let seg_saved = c.pos;
-                let etype = parse_edge_connector(c);
+                let (mut etype, reversed) = parse_edge_connector(c);
+                let mut lbl = String::new();
                 if etype == parser::EdgeType::None {
                     c.pos = seg_saved;
-                    break;
+                    match parse_inline_label_connector(c) {
+                        Some((inline_type, text)) => (etype, lbl) = (inline_type, text),
+                        None => break,
+                    }
+                } else {
+                    lbl = parse_edge_label(c);
                 }
-                let lbl = parse_edge_label(c);
-                let tgt = parse_node_ref(c);
-                if tgt.id.is_empty() {
+                let class = parse_edge_class(c);
+                let tgts = parse_node_group(c);
+                if tgts.is_empty() {
                     c.pos = seg_saved;
                     break;
                 }
-                chain_segs.push((etype, lbl, tgt));
-            }
-
-            if !chain_segs.is_empty() {
-                upsert_node(nodes, src_node.clone());
-                let mut prev_id = src_node.id.clone();
-                for (etype, lbl, tgt) in chain_segs {
-                    let mut e = parser::edge_new(prev_id.clone(), tgt.id.clone(), etype);
-                    e.label = lbl;
-                    upsert_node(nodes, tgt.clone());
-                    edges.push(e);
-                    prev_id = tgt.id;
-                }
-                c.skip_ws();
-                c.consume_newline();
-                return true;
+                chain_segs.push((etype, reversed, lbl, class, tgts));
             }
 
-            // Not an edge — try as bare node
-            upsert_node(nodes, src_node);
+            // `A & B --> C & D` links every node on one side to every node
+            // on the other
+            let mut prev_ids: Vec<String> = src_nodes.iter().map(|n| n.id.clone()).collect();
+            for node in src_nodes {
+                upsert_node(nodes, node);
+            }
+            for (etype, reversed, lbl, class, tgts) in chain_segs {
+                for prev_id in &prev_ids {
+                    for tgt in &tgts {
+                        let (from, to) = if reversed {
+                            (tgt.id.clone(), prev_id.clone())
+                        } else {
+                            (prev_id.clone(), tgt.id.clone())
+                        };
+                        let mut e = parser::edge_new(from, to, etype.clone());
+                        e.label = lbl.clone();
+                        if !class.is_empty() {
+                            e.attrs.push(parser::Attr {
+                                key: "class".to_string(),
+                                value: class.clone(),
+                            });
+                        }
+                        edges.push(e);
+                    }
+                }
+                prev_ids = tgts.iter().map(|n| n.id.clone()).collect();
+                for tgt in tgts {
+                    upsert_node(nodes, tgt);
+                }
+            }
             c.skip_ws();
             c.consume_newline();
             return true;
@@ -426,9 +890,45 @@ mod rust_parser {
         false
     }
 
+    /// One node reference, or several joined by `&` (`A & B[Label]`).
+    /// Empty when no node starts here.
+    fn parse_node_group(c: &mut Cursor) -> Vec<parser::Node> {
+        let first = parse_node_ref(c);
+        if first.id.is_empty() {
+            return Vec::new();
+        }
+        let mut group = vec![first];
+        loop {
+            let saved = c.pos;
+            c.skip_ws();
+            let next = if c.consume_str("&") {
+                parse_node_ref(c)
+            } else {
+                parser::node_bare(String::new())
+            };
+            if next.id.is_empty() {
+                c.pos = saved;
+                break;
+            }
+            group.push(next);
+        }
+        group
+    }
+
+    /// Record a node reference. A shaped/labelled definition replaces an
+    /// earlier one in place, so `A --> B` followed by `B{X}` upgrades B.
+    /// Attributes (e.g. `:::class`) accumulate across references.
     fn upsert_node(nodes: &mut Vec<parser::Node>, node: parser::Node) {
-        if !nodes.iter().any(|n| n.id == node.id) {
-            nodes.push(node);
+        match nodes.iter_mut().find(|n| n.id == node.id) {
+            Some(existing) => {
+                let mut attrs = std::mem::take(&mut existing.attrs);
+                attrs.extend(node.attrs.iter().cloned());
+                if !super::is_bare_node(&node) {
+                    *existing = node;
+                }
+                existing.attrs = attrs;
+            }
+            None => nodes.push(node),
         }
     }
 
@@ -439,11 +939,16 @@ mod rust_parser {
             c.pos = saved;
             return parser::subgraph_new(String::new());
         }
-        // "subgraph" must be followed by non-identifier char
-        if !c.eof() && (c.ch().is_ascii_alphanumeric() || c.ch() == '_' || c.ch() == '-') {
+        // "subgraph" must be followed by whitespace: `subgraphFoo` and
+        // `subgraph[x]` are nodes
+        if !c.eof() && !matches!(c.ch(), ' ' | '\t' | '\n' | '\r') {
             c.pos = saved;
             return parser::subgraph_new(String::new());
         }
+        if c.depth >= c.max_depth {
+            c.too_deep = true;
+            return parser::subgraph_new(String::new());
+        }
 
         c.skip_ws();
         // Parse name/label
@@ -465,12 +970,13 @@ mod rust_parser {
 
         let mut sg = parser::subgraph_new(name);
 
-        // Optional "direction XX"
+        // Optional "direction XX" (a `directions` node is not the keyword)
         let dir_saved = c.pos;
         c.skip_ws();
-        if c.consume_str("direction") {
+        if c.consume_keyword("direction") {
             c.skip_ws();
             sg.direction = parse_direction(c);
+            sg.has_direction = true;
             c.skip_ws();
             c.consume_newline();
         } else {
@@ -478,6 +984,7 @@ mod rust_parser {
         }
 
         // Parse body
+        c.depth += 1;
         while !c.eof() {
             c.skip_ws();
             if at_end_keyword(c) {
@@ -489,18 +996,31 @@ mod rust_parser {
             let ok = parse_statement_into(c, &mut sg.nodes, &mut sg.edges, &mut sg.subgraphs);
             if !ok {
                 if !c.consume_newline() {
-                    c.pos += 1;
+                    c.skip_unparsed();
                 }
             }
         }
+        c.depth -= 1;
         sg
     }
 
     fn parse_header(c: &mut Cursor) -> parser::Direction {
         let saved = c.pos;
         c.skip_ws_and_newlines();
-        let ok = c.consume_str("flowchart") || c.consume_str("graph");
-        if !ok {
+        let ok = if c.consume_str("flowchart") {
+            // Variant headers: `flowchart-elk`, `flowchart-v2`
+            if c.ch() == '-' && c.src.get(c.pos + 1).is_some_and(|ch| ch.is_alphanumeric()) {
+                c.pos += 1;
+                while !c.eof() && c.ch().is_alphanumeric() {
+                    c.pos += 1;
+                }
+            }
+            true
+        } else {
+            c.consume_str("graph")
+        };
+        // `flowchartX` or `graphs` is a node id, not a header
+        if !ok || c.ch().is_alphanumeric() || c.ch() == '_' {
             c.pos = saved;
             return parser::Direction::TD;
         }
@@ -509,17 +1029,28 @@ mod rust_parser {
         c.skip_ws();
         // skip optional trailing comment
         if c.peek_str("%%") {
-            while !c.eof() && c.ch() != '\n' {
-                c.pos += 1;
-            }
+            c.skip_comment();
         }
         c.skip_ws();
         c.consume_newline();
         d
     }
 
+    /// Parse with the default nesting limit; input nested too deeply parses
+    /// to an empty graph.
     pub fn parse_flowchart(src: &str) -> parser::Graph {
-        let mut c = Cursor::new(src);
+        let max_depth = super::config_default().max_subgraph_depth as usize;
+        parse_flowchart_with_skips(src, max_depth).map_or_else(|_| parser::graph_new(), |(g, _)| g)
+    }
+
+    /// Parse leniently, also returning the (char) positions of every
+    /// character that was skipped because no statement starts there. Fails
+    /// when subgraphs nest more than `max_depth` levels deep.
+    pub fn parse_flowchart_with_skips(
+        src: &str,
+        max_depth: usize,
+    ) -> Result<(parser::Graph, Vec<usize>), RenderError> {
+        let mut c = Cursor::new(src, max_depth);
         let mut g = parser::graph_new();
         g.direction = parse_header(&mut c);
 
@@ -530,19 +1061,154 @@ mod rust_parser {
                     let ok =
                         parse_statement_into(&mut c, &mut g.nodes, &mut g.edges, &mut g.subgraphs);
                     if !ok {
-                        c.pos += 1;
+                        c.skip_unparsed();
                     }
                 }
             }
         }
-        g
+        if c.too_deep {
+            return Err(RenderError::LimitExceeded(format!(
+                "subgraphs nested deeper than {max_depth} levels"
+            )));
+        }
+        g.class_assignments = c.class_assignments;
+        g.style_assignments = c.style_assignments;
+        g.same_ranks = c.same_ranks;
+        g.acc_title = c.acc_title;
+        g.acc_descr = c.acc_descr;
+        g.unsupported = c.unsupported;
+        g.pins = c.pins;
+        g.routes = c.routes;
+        Ok((g, c.skipped))
     }
 }
 
 // ── Bridge: parser AST → graph::Graph ───────────────────────────────────────
 
+/// A bare reference (`A`) carries no shape or label of its own.
+fn is_bare_node(node: &parser::Node) -> bool {
+    node.label == node.id && node.shape == parser::NodeShape::Rectangle
+}
+
+/// Resolve every node id to its most specific definition across the whole
+/// AST (top level and all subgraphs): a shaped/labelled definition beats a
+/// bare reference regardless of which appears first.
+fn resolve_node_definitions(parsed: &parser::Graph) -> HashMap<String, parser::Node> {
+    fn visit(resolved: &mut HashMap<String, parser::Node>, nodes: &[parser::Node]) {
+        for node in nodes {
+            match resolved.get(&node.id) {
+                Some(_) if is_bare_node(node) => {}
+                _ => {
+                    resolved.insert(node.id.clone(), node.clone());
+                }
+            }
+        }
+    }
+    fn visit_sg(resolved: &mut HashMap<String, parser::Node>, sg: &parser::Subgraph) {
+        visit(resolved, &sg.nodes);
+        for nested in &sg.subgraphs {
+            visit_sg(resolved, nested);
+        }
+    }
+
+    let mut resolved = HashMap::new();
+    visit(&mut resolved, &parsed.nodes);
+    for sg in &parsed.subgraphs {
+        visit_sg(&mut resolved, sg);
+    }
+    resolved
+}
+
+/// Classes of one edge (`A -->:::name B`), deduplicated in source order.
+fn edge_classes(edge: &parser::Edge) -> Vec<String> {
+    let mut classes: Vec<String> = Vec::new();
+    for attr in edge.attrs.iter().filter(|a| a.key == "class") {
+        if !classes.contains(&attr.value) {
+            classes.push(attr.value.clone());
+        }
+    }
+    classes
+}
+
+/// Classes per node id from every `:::name` reference and `class` statement,
+/// deduplicated in source order. `class` statements naming unknown ids are ignored.
+fn node_classes(parsed: &parser::Graph) -> HashMap<String, Vec<String>> {
+    fn add(out: &mut HashMap<String, Vec<String>>, id: &str, class: &str) {
+        let list = out.entry(id.to_string()).or_default();
+        if !list.iter().any(|c| c == class) {
+            list.push(class.to_string());
+        }
+    }
+    fn visit_sg(out: &mut HashMap<String, Vec<String>>, sg: &parser::Subgraph) {
+        for node in &sg.nodes {
+            for attr in node.attrs.iter().filter(|a| a.key == "class") {
+                add(out, &node.id, &attr.value);
+            }
+        }
+        for nested in &sg.subgraphs {
+            visit_sg(out, nested);
+        }
+    }
+
+    let mut out = HashMap::new();
+    for node in &parsed.nodes {
+        for attr in node.attrs.iter().filter(|a| a.key == "class") {
+            add(&mut out, &node.id, &attr.value);
+        }
+    }
+    for sg in &parsed.subgraphs {
+        visit_sg(&mut out, sg);
+    }
+    let known = resolve_node_definitions(parsed);
+    for assignment in &parsed.class_assignments {
+        if known.contains_key(&assignment.key) {
+            add(&mut out, &assignment.key, &assignment.value);
+        }
+    }
+    out
+}
+
+/// `style` statement properties per node id, in order of first mention, a
+/// repeated property taking its latest value. Statements naming unknown ids
+/// (never defined before or after) are ignored.
+fn node_styles(parsed: &parser::Graph) -> HashMap<String, Vec<(String, String)>> {
+    let known = resolve_node_definitions(parsed);
+    let mut out: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for assignment in &parsed.style_assignments {
+        if !known.contains_key(&assignment.key) {
+            continue;
+        }
+        let list = out.entry(assignment.key.clone()).or_default();
+        for (prop, value) in assignment
+            .value
+            .split(',')
+            .filter_map(|s| s.split_once(':'))
+        {
+            let (prop, value) = (prop.trim().to_string(), value.trim().to_string());
+            match list.iter_mut().find(|(p, _)| *p == prop) {
+                Some(entry) => entry.1 = value,
+                None => list.push((prop, value)),
+            }
+        }
+    }
+    out
+}
+
+/// Whether a node's `style` asks for a border wider than Mermaid's 1px
+/// default, drawn in ASCII as a heavy border.
+fn style_is_heavy(styles: &[(String, String)]) -> bool {
+    styles.iter().any(|(prop, value)| {
+        prop == "stroke-width"
+            && value
+                .trim_end_matches("px")
+                .parse::<f64>()
+                .is_ok_and(|w| w > 1.0)
+    })
+}
+
 fn ast_to_graph(parsed: &parser::Graph) -> graph::Graph {
     let mut g = graph::graph_new();
+    let resolved = resolve_node_definitions(parsed);
 
     fn shape_str(s: &parser::NodeShape) -> &'static str {
         match s {
@@ -563,11 +1229,16 @@ fn ast_to_graph(parsed: &parser::Graph) -> graph::Graph {
             parser::EdgeType::BidirArrow => "BidirArrow",
             parser::EdgeType::BidirDotted => "BidirDotted",
             parser::EdgeType::BidirThick => "BidirThick",
+            parser::EdgeType::CircleArrow => "CircleArrow",
+            parser::EdgeType::CrossArrow => "CrossArrow",
+            parser::EdgeType::BidirCircle => "BidirCircle",
+            parser::EdgeType::BidirCross => "BidirCross",
             parser::EdgeType::None => "Arrow",
         }
     }
 
     for node in &parsed.nodes {
+        let node = &resolved[&node.id];
         graph::graph_add_node(&mut g, &node.id, &node.label, shape_str(&node.shape), None);
     }
     for edge in &parsed.edges {
@@ -576,16 +1247,19 @@ fn ast_to_graph(parsed: &parser::Graph) -> graph::Graph {
         } else {
             Some(edge.label.as_str())
         };
-        graph::graph_add_edge(
-            &mut g,
-            &edge.from_id,
-            &edge.to_id,
-            etype_str(&edge.edge_type),
-            label,
-        );
+        let data = graph::EdgeData {
+            edge_type: etype_str(&edge.edge_type).to_string(),
+            label: label.map(str::to_string),
+            classes: edge_classes(edge),
+        };
+        graph::graph_add_edge_data(&mut g, &edge.from_id, &edge.to_id, data);
     }
 
-    fn add_sg(g: &mut graph::Graph, sg: &parser::Subgraph) {
+    fn add_sg(
+        g: &mut graph::Graph,
+        sg: &parser::Subgraph,
+        resolved: &HashMap<String, parser::Node>,
+    ) {
         fn sh(s: &parser::NodeShape) -> &'static str {
             match s {
                 parser::NodeShape::Rectangle => "Rectangle",
@@ -605,10 +1279,15 @@ fn ast_to_graph(parsed: &parser::Graph) -> graph::Graph {
                 parser::EdgeType::BidirArrow => "BidirArrow",
                 parser::EdgeType::BidirDotted => "BidirDotted",
                 parser::EdgeType::BidirThick => "BidirThick",
+                parser::EdgeType::CircleArrow => "CircleArrow",
+                parser::EdgeType::CrossArrow => "CrossArrow",
+                parser::EdgeType::BidirCircle => "BidirCircle",
+                parser::EdgeType::BidirCross => "BidirCross",
                 parser::EdgeType::None => "Arrow",
             }
         }
         for node in &sg.nodes {
+            let node = &resolved[&node.id];
             graph::graph_add_node(g, &node.id, &node.label, sh(&node.shape), Some(&sg.name));
         }
         for edge in &sg.edges {
@@ -617,15 +1296,20 @@ fn ast_to_graph(parsed: &parser::Graph) -> graph::Graph {
             } else {
                 Some(edge.label.as_str())
             };
-            graph::graph_add_edge(g, &edge.from_id, &edge.to_id, et(&edge.edge_type), label);
+            let data = graph::EdgeData {
+                edge_type: et(&edge.edge_type).to_string(),
+                label: label.map(str::to_string),
+                classes: edge_classes(edge),
+            };
+            graph::graph_add_edge_data(g, &edge.from_id, &edge.to_id, data);
         }
         for nested in &sg.subgraphs {
-            add_sg(g, nested);
+            add_sg(g, nested, resolved);
         }
     }
 
     for sg in &parsed.subgraphs {
-        add_sg(&mut g, sg);
+        add_sg(&mut g, sg, &resolved);
     }
 
     g
@@ -636,13 +1320,137 @@ fn ast_to_graph(parsed: &parser::Graph) -> graph::Graph {
 // (nested while loops generate shadow variables instead of reassignment).
 
 use std::collections::{HashMap, HashSet, VecDeque};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// merge_mutual: fold each `A --> B` / `B --> A` pair into one bidirectional
+/// edge, kept where the first of the two was declared. Only one-way arrows
+/// of the same stroke merge, and only when at most one of them has a label.
+fn merge_mutual_edges(g: &graph::Graph) -> graph::Graph {
+    let mut merged = graph::graph_new();
+    for idx in g.digraph.node_indices() {
+        let nd = &g.digraph[idx];
+        graph::graph_add_node(
+            &mut merged,
+            &nd.id,
+            &nd.label,
+            &nd.shape,
+            nd.subgraph.as_deref(),
+        );
+    }
+    let edges: Vec<(&str, &str, &graph::EdgeData)> = g
+        .digraph
+        .edge_indices()
+        .map(|eidx| {
+            let (a, b) = g.digraph.edge_endpoints(eidx).unwrap();
+            (
+                g.digraph[a].id.as_str(),
+                g.digraph[b].id.as_str(),
+                &g.digraph[eidx],
+            )
+        })
+        .collect();
+    let mut dropped = vec![false; edges.len()];
+    for (i, &(from, to, ed)) in edges.iter().enumerate() {
+        if dropped[i] {
+            continue;
+        }
+        let mut data = ed.clone();
+        let bidir = match ed.edge_type.as_str() {
+            "Arrow" => Some("BidirArrow"),
+            "DottedArrow" => Some("BidirDotted"),
+            "ThickArrow" => Some("BidirThick"),
+            _ => None,
+        };
+        let partner = (i + 1..edges.len()).find(|&j| {
+            let (pf, pt, pd) = edges[j];
+            !dropped[j]
+                && from != to
+                && (pf, pt) == (to, from)
+                && pd.edge_type == ed.edge_type
+                && (ed.label.is_none() || pd.label.is_none())
+        });
+        if let (Some(bidir), Some(j)) = (bidir, partner) {
+            dropped[j] = true;
+            let other = edges[j].2;
+            data.edge_type = bidir.to_string();
+            data.label = data.label.or_else(|| other.label.clone());
+            for class in &other.classes {
+                if !data.classes.contains(class) {
+                    data.classes.push(class.clone());
+                }
+            }
+        }
+        graph::graph_add_edge_data(&mut merged, from, to, data);
+    }
+    merged
+}
+
+/// summarize: while `g` has more than `max_nodes` nodes, fold leaves (no
+/// out-edges, one in-edge, outside any subgraph) into a `(+N more)` line on
+/// their parent's label, parents with the most leaves first.
+fn summarize_leaves(g: &graph::Graph, max_nodes: usize) -> graph::Graph {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for id in graph::graph_nodes(g) {
+        let preds = graph::graph_predecessors(g, &id);
+        let is_leaf = preds.len() == 1
+            && preds[0] != id
+            && graph::graph_out_degree(g, &id) == 0
+            && g.digraph[g.node_index[&id]].subgraph.is_none();
+        if !is_leaf {
+            continue;
+        }
+        match groups.iter_mut().find(|(p, _)| *p == preds[0]) {
+            Some((_, leaves)) => leaves.push(id),
+            None => groups.push((preds[0].clone(), vec![id])),
+        }
+    }
+    groups.sort_by_key(|(_, leaves)| std::cmp::Reverse(leaves.len()));
+
+    let mut count = graph::graph_node_count(g);
+    let mut folded: HashMap<String, usize> = HashMap::new();
+    let mut dropped: HashSet<String> = HashSet::new();
+    for (parent, leaves) in groups {
+        if count <= max_nodes {
+            break;
+        }
+        count -= leaves.len();
+        folded.insert(parent, leaves.len());
+        dropped.extend(leaves);
+    }
+
+    let mut out = graph::graph_new();
+    for idx in g.digraph.node_indices() {
+        let nd = &g.digraph[idx];
+        if dropped.contains(&nd.id) {
+            continue;
+        }
+        let label = match folded.get(&nd.id) {
+            Some(n) => format!("{}\n(+{n} more)", nd.label),
+            None => nd.label.clone(),
+        };
+        graph::graph_add_node(&mut out, &nd.id, &label, &nd.shape, nd.subgraph.as_deref());
+    }
+    for eidx in g.digraph.edge_indices() {
+        let (a, b) = g.digraph.edge_endpoints(eidx).unwrap();
+        let (from, to) = (&g.digraph[a].id, &g.digraph[b].id);
+        if !dropped.contains(to) {
+            graph::graph_add_edge_data(&mut out, from, to, g.digraph[eidx].clone());
+        }
+    }
+    out
+}
 
 /// Phase 1: Remove cycles by reversing back edges (DFS-based).
 fn remove_cycles_rust(g: &graph::Graph) -> (graph::Graph, Vec<(String, String)>) {
+    // Acyclic fast path: nothing to reverse, so skip the DFS and rebuild.
     if graph::graph_is_dag(g) {
         return (graph::graph_copy(g), vec![]);
     }
+    reverse_back_edges(g)
+}
 
+/// DFS back-edge detection: reverse every edge that closes a cycle.
+fn reverse_back_edges(g: &graph::Graph) -> (graph::Graph, Vec<(String, String)>) {
     let nodes = graph::graph_nodes(g);
     let mut visited: HashSet<String> = HashSet::new();
     let mut on_stack: HashSet<String> = HashSet::new();
@@ -695,35 +1503,57 @@ fn remove_cycles_rust(g: &graph::Graph) -> (graph::Graph, Vec<(String, String)>)
         let ed = &g.digraph[eidx];
         if back_set.contains(&(from_id.clone(), to_id.clone())) {
             // Reverse this edge
-            graph::graph_add_edge(
-                &mut dag,
-                &to_id,
-                &from_id,
-                &ed.edge_type,
-                ed.label.as_deref(),
-            );
+            graph::graph_add_edge_data(&mut dag, &to_id, &from_id, ed.clone());
         } else {
-            graph::graph_add_edge(
-                &mut dag,
-                &from_id,
-                &to_id,
-                &ed.edge_type,
-                ed.label.as_deref(),
-            );
+            graph::graph_add_edge_data(&mut dag, &from_id, &to_id, ed.clone());
         }
     }
 
     (dag, back_edges)
 }
 
+/// Phases 1-2: cycle removal, then layering. A DAG skips the back-edge
+/// search and the rebuilt copy: the topological order from the acyclicity
+/// check layers it directly. The rebuilt graph comes back only when edges
+/// had to be reversed; otherwise `g` is the DAG to lay out.
+fn remove_cycles_and_layer(
+    g: &graph::Graph,
+    super_source: bool,
+) -> (
+    Option<graph::Graph>,
+    Vec<(String, String)>,
+    HashMap<String, i32>,
+) {
+    let (rebuilt, reversed) = match graph::graph_topo_sort(g) {
+        Some(topo) if !super_source => return (None, Vec::new(), assign_layers_topo(g, &topo)),
+        Some(_) => (None, Vec::new()),
+        None => {
+            let (dag, reversed) = reverse_back_edges(g);
+            (Some(dag), reversed)
+        }
+    };
+    let dag = rebuilt.as_ref().unwrap_or(g);
+    let layers = if super_source {
+        assign_layers_super_source(dag)
+    } else {
+        assign_layers_rust(dag)
+    };
+    (rebuilt, reversed, layers)
+}
+
 /// Phase 2: Assign layers using longest-path method (topological order).
 fn assign_layers_rust(g: &graph::Graph) -> HashMap<String, i32> {
     let topo = graph::graph_topo_sort(g).unwrap_or_else(|| graph::graph_nodes(g));
+    assign_layers_topo(g, &topo)
+}
+
+/// Longest-path layering along `topo`, a topological order of `g`.
+fn assign_layers_topo(g: &graph::Graph, topo: &[String]) -> HashMap<String, i32> {
     let mut layers: HashMap<String, i32> = HashMap::new();
-    for node in &topo {
+    for node in topo {
         layers.insert(node.clone(), 0);
     }
-    for node in &topo {
+    for node in topo {
         let curr = layers[node];
         for succ in graph::graph_successors(g, node) {
             let succ_layer = layers.get(&succ).copied().unwrap_or(0);
@@ -735,8 +1565,140 @@ fn assign_layers_rust(g: &graph::Graph) -> HashMap<String, i32> {
     layers
 }
 
-/// Phase 3-4: Build layer ordering (group nodes by layer, sort within layer).
-fn build_ordering(g: &graph::Graph, layers: &HashMap<String, i32>) -> Vec<Vec<String>> {
+/// Id of the virtual root used by the `super_source` layering option.
+const SUPER_SOURCE_ID: &str = "__super_source__";
+
+/// Phase 2 (super_source option): layer through a virtual super-source linked
+/// to every in-degree-0 node so all roots share one anchor, then sink each
+/// root to sit directly above its nearest successor instead of defaulting to
+/// the top layer. The virtual node is dropped before returning.
+fn assign_layers_super_source(g: &graph::Graph) -> HashMap<String, i32> {
+    let roots: Vec<String> = graph::graph_nodes(g)
+        .into_iter()
+        .filter(|n| graph::graph_predecessors(g, n).is_empty())
+        .collect();
+    let mut aug = graph::graph_copy(g);
+    graph::graph_add_node(&mut aug, SUPER_SOURCE_ID, "", "Rectangle", None);
+    for root in &roots {
+        graph::graph_add_edge(&mut aug, SUPER_SOURCE_ID, root, "Arrow", None);
+    }
+
+    let mut layers = assign_layers_rust(&aug);
+    layers.remove(SUPER_SOURCE_ID);
+    for root in &roots {
+        let nearest = graph::graph_successors(g, root)
+            .iter()
+            .filter_map(|s| layers.get(s).copied())
+            .min();
+        if let Some(layer) = nearest {
+            layers.insert(root.clone(), layer - 1);
+        }
+    }
+
+    let min_layer = layers.values().min().copied().unwrap_or(0);
+    for layer in layers.values_mut() {
+        *layer -= min_layer;
+    }
+    layers
+}
+
+/// Prefix for dummy nodes inserted along long edges; never rendered.
+const DUMMY_PREFIX: &str = "__dummy_";
+
+/// A long edge split into one dummy node per intermediate layer.
+struct DummyChain {
+    from_id: String,
+    to_id: String,
+    dummy_ids: Vec<String>,
+}
+
+/// Phase 2b (straighten only): split every edge spanning more than one layer
+/// into a chain of unit-length edges through dummy nodes. Returns the augmented
+/// graph, its layer map, and the chains keyed by their original endpoints.
+/// Dummy ids are `__dummy_<len>_<from>_<to>_<step>`, with `<len>` the length
+/// of `<from>`, so they do not depend on edge order and ids containing `_`
+/// cannot collide (`a_b --> c` and `a --> b_c`); a repeated pair's later
+/// chains add `#<n>` after `<to>`.
+fn insert_dummy_nodes_rust(
+    dag: &graph::Graph,
+    layers: &HashMap<String, i32>,
+) -> (graph::Graph, HashMap<String, i32>, Vec<DummyChain>) {
+    let mut aug = graph::graph_new();
+    for node in graph::graph_nodes(dag) {
+        let nd = &dag.digraph[dag.node_index[&node]];
+        graph::graph_add_node(
+            &mut aug,
+            &nd.id,
+            &nd.label,
+            &nd.shape,
+            nd.subgraph.as_deref(),
+        );
+    }
+
+    let mut aug_layers = layers.clone();
+    let mut chains: Vec<DummyChain> = Vec::new();
+    let mut pair_counts: HashMap<(String, String), usize> = HashMap::new();
+    for eidx in dag.digraph.edge_indices() {
+        let (a, b) = dag.digraph.edge_endpoints(eidx).unwrap();
+        let from_id = dag.digraph[a].id.clone();
+        let to_id = dag.digraph[b].id.clone();
+        let ed = &dag.digraph[eidx];
+        let from_layer = layers.get(&from_id).copied().unwrap_or(0);
+        let span = layers.get(&to_id).copied().unwrap_or(0) - from_layer;
+        if span <= 1 {
+            graph::graph_add_edge_data(&mut aug, &from_id, &to_id, ed.clone());
+            continue;
+        }
+
+        let repeat = pair_counts
+            .entry((from_id.clone(), to_id.clone()))
+            .or_insert(0);
+        let pair = format!("{}_{from_id}_{to_id}", from_id.len());
+        let base = match *repeat {
+            0 => format!("{DUMMY_PREFIX}{pair}"),
+            n => format!("{DUMMY_PREFIX}{pair}#{n}"),
+        };
+        *repeat += 1;
+        let mut dummy_ids = Vec::new();
+        let mut prev = from_id.clone();
+        for step in 1..span {
+            let dummy_id = format!("{base}_{}", step - 1);
+            graph::graph_add_node(&mut aug, &dummy_id, "", "Rectangle", None);
+            aug_layers.insert(dummy_id.clone(), from_layer + step);
+            graph::graph_add_edge(&mut aug, &prev, &dummy_id, &ed.edge_type, None);
+            prev = dummy_id.clone();
+            dummy_ids.push(dummy_id);
+        }
+        graph::graph_add_edge(&mut aug, &prev, &to_id, &ed.edge_type, ed.label.as_deref());
+        chains.push(DummyChain {
+            from_id,
+            to_id,
+            dummy_ids,
+        });
+    }
+
+    (aug, aug_layers, chains)
+}
+
+/// Barycenter passes for [`build_ordering`]: `max_passes`, or none when
+/// `minimize_crossings` is off.
+fn ordering_passes(config: &RenderConfig) -> usize {
+    if config.minimize_crossings {
+        config.max_passes.max(0) as usize
+    } else {
+        0
+    }
+}
+
+/// Phase 3-4: Build layer ordering (group nodes by layer, sort within layer),
+/// then run `passes` barycenter passes (none keeps the initial order).
+/// Pinned nodes (`%% pos`) keep their requested position through every pass.
+fn build_ordering(
+    g: &graph::Graph,
+    layers: &HashMap<String, i32>,
+    pins: &NodePins,
+    passes: usize,
+) -> Vec<Vec<String>> {
     let max_layer = layers.values().max().copied().unwrap_or(0);
     let mut layer_groups: Vec<Vec<String>> = vec![vec![]; (max_layer + 1) as usize];
     for (id, &layer) in layers {
@@ -745,13 +1707,20 @@ fn build_ordering(g: &graph::Graph, layers: &HashMap<String, i32>) -> Vec<Vec<St
         }
     }
 
-    // Deterministic initial order before barycenter passes
-    for group in &mut layer_groups {
-        group.sort();
+    // Deterministic initial order before barycenter passes. The roots keep
+    // their declaration order (the order they were added to the graph), which
+    // the stable sorts below preserve among equal scores.
+    for (li, group) in layer_groups.iter_mut().enumerate() {
+        if li == 0 {
+            group.sort_by_key(|id| g.node_index[id].index());
+        } else {
+            group.sort();
+        }
+        apply_order_pins(group, pins);
     }
 
     // Barycenter crossing minimization: order by average position of neighbors
-    for _pass in 0..4 {
+    for _pass in 0..passes {
         // Forward pass: order layer[i] by average position of predecessors in layer[i-1]
         for li in 1..layer_groups.len() {
             let prev_positions: HashMap<String, f64> = layer_groups[li - 1]
@@ -777,6 +1746,7 @@ fn build_ordering(g: &graph::Graph, layers: &HashMap<String, i32>) -> Vec<Vec<St
                 .collect();
             scored.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
             layer_groups[li] = scored.into_iter().map(|(id, _)| id).collect();
+            apply_order_pins(&mut layer_groups[li], pins);
         }
         // Backward pass: order layer[i] by average position of successors in layer[i+1]
         for li in (0..layer_groups.len().saturating_sub(1)).rev() {
@@ -803,33 +1773,36 @@ fn build_ordering(g: &graph::Graph, layers: &HashMap<String, i32>) -> Vec<Vec<St
                 .collect();
             scored.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
             layer_groups[li] = scored.into_iter().map(|(id, _)| id).collect();
+            apply_order_pins(&mut layer_groups[li], pins);
         }
     }
 
     layer_groups
 }
 
-/// Ensure first segment exits vertically (down) and last segment enters vertically (down).
+/// Ensure first segment exits vertically and last segment enters vertically,
+/// downward or, for a back edge (`up`), upward.
 /// Mirrors the legacy `ensure_vertical_endpoints` from the reference Sugiyama implementation.
 /// This guarantees correct arrowhead direction (▼ for TD, ► for LR after transpose).
-fn ensure_vertical_endpoints(wps: &mut Vec<(i32, i32)>) {
+fn ensure_vertical_endpoints(wps: &mut Vec<(i32, i32)>, up: bool) {
     if wps.len() < 2 {
         return;
     }
-    // Fix last segment: if horizontal, lift the second-to-last point up one row
-    // so the final segment becomes a short downward step.
+    let step = if up { -1 } else { 1 };
+    // Fix last segment: if horizontal, move the second-to-last point back one
+    // row so the final segment becomes a short step in the edge's direction.
     let n = wps.len();
     let (lx, ly) = wps[n - 1];
     let (px, py) = wps[n - 2];
-    if ly == py && lx != px && py > 0 {
-        let new_y = py - 1;
+    if ly == py && lx != px && py - step >= 0 {
+        let new_y = py - step;
         wps[n - 2] = (px, new_y);
         wps.insert(n - 1, (lx, new_y));
     }
-    // Fix first segment: if horizontal, push the second point down one row
-    // so the initial segment becomes a short downward exit.
+    // Fix first segment: if horizontal, move the second point on one row
+    // so the initial segment becomes a short vertical exit.
     if wps.len() >= 2 && wps[0].1 == wps[1].1 && wps[0].0 != wps[1].0 {
-        let new_y = wps[0].1 + 1;
+        let new_y = wps[0].1 + step;
         let sx = wps[1].0;
         let x0 = wps[0].0;
         wps[1] = (sx, new_y);
@@ -837,6 +1810,25 @@ fn ensure_vertical_endpoints(wps: &mut Vec<(i32, i32)>) {
     }
 }
 
+/// label_rows: keep the row just above an edge's arrowhead free for its label
+/// by lifting a final horizontal jog that sits on it up one row.
+fn reserve_label_row(wps: &mut [(i32, i32)]) {
+    let n = wps.len();
+    if n < 4 {
+        return;
+    }
+    let (lx, ly) = wps[n - 1];
+    let (px, py) = wps[n - 2];
+    let jy = wps[n - 3].1;
+    let before_y = wps[n - 4].1;
+    // Last segment vertical and one row long, preceded by a horizontal jog
+    // whose own lead-in leaves room to move it up.
+    if px == lx && py == ly - 1 && jy == py && before_y < py - 1 {
+        wps[n - 2].1 -= 1;
+        wps[n - 3].1 -= 1;
+    }
+}
+
 /// Collected edge info for routing.
 struct EdgeInfo {
     from_id: String,
@@ -845,20 +1837,118 @@ struct EdgeInfo {
     label: String,
 }
 
+/// Round an even box extent up to odd so `x + w / 2` is the true centre cell.
+fn round_up_odd(w: i32) -> i32 {
+    if w % 2 == 0 { w + 1 } else { w }
+}
+
+/// Box size `(w, h)` for a node as drawn: the label plus borders and
+/// padding, at least `min_w` wide. Circles are made taller as they widen so
+/// they look round rather than flattened (a cell is about twice as tall as
+/// it is wide). `odd_widths` rounds the side edges leave through: the width
+/// in TD/BT, the height in LR/RL.
+fn node_dims(
+    label: &str,
+    shape: &str,
+    min_w: i32,
+    config: &RenderConfig,
+    is_lr_or_rl: bool,
+) -> (i32, i32) {
+    let label_w = label.lines().map(display_width).max().unwrap_or(0);
+    let label_h = std::cmp::max(label.lines().count() as i32, 1);
+    let mut w = std::cmp::max(label_w + 2 + 2 * config.padding, min_w);
+    if config.odd_widths && !is_lr_or_rl {
+        w = round_up_odd(w);
+    }
+    let mut h = label_h + 2;
+    if shape == "Circle" {
+        h = std::cmp::max(h, (w + 1) / 2);
+    }
+    if config.odd_widths && is_lr_or_rl {
+        h = round_up_odd(h);
+    }
+    (w, h)
+}
+
+/// Rows a gap needs to show a one-line edge label on a row of its own: the
+/// exit stub, the label, the line's turn and the arrowhead. Each further
+/// label line adds a row.
+const LABEL_GAP_ROWS: i32 = 4;
+
 /// Phase 5: Assign coordinates to nodes.
 fn assign_coordinates_rust(
     g: &graph::Graph,
     ordering: &[Vec<String>],
-    padding: i32,
+    config: &RenderConfig,
     is_lr_or_rl: bool,
     dim_overrides: &HashMap<String, (i32, i32)>,
 ) -> graph::NodeLayoutList {
     let nll = graph::nll_new();
-    // For LR/RL, swap h_gap and v_gap so that after transposing the visual
-    // gaps match the expected output (h_gap becomes row-spacing, v_gap becomes col-spacing).
-    let h_gap = if is_lr_or_rl { 3i32 } else { 4i32 };
-    let v_gap = if is_lr_or_rl { 4i32 } else { 3i32 };
-    let min_node_h = 3i32;
+    // Unset gaps take the direction's defaults. For LR/RL, swap h_gap and
+    // v_gap so that after transposing the visual gaps match the expected
+    // output (h_gap becomes row-spacing, v_gap becomes col-spacing).
+    let (h_default, v_default) = if is_lr_or_rl {
+        LR_DEFAULT_GAPS
+    } else {
+        TD_DEFAULT_GAPS
+    };
+    let (h, v) = (
+        config.h_gap.unwrap_or(h_default),
+        config.v_gap.unwrap_or(v_default),
+    );
+    let (h_gap, v_gap) = if is_lr_or_rl { (v, h) } else { (h, v) };
+    let min_node_h = if config.skeleton { 1 } else { 3 };
+
+    // label_rows: a gap that a labelled edge enters its target through gets one extra row.
+    // label_gaps: the gap a labelled edge leaves its source through, where its
+    // label lands, is tall enough for all of the label's lines.
+    let mut labelled_gaps: HashSet<usize> = HashSet::new();
+    let mut label_gaps: HashMap<usize, i32> = HashMap::new();
+    if (config.label_rows || config.label_gaps) && !is_lr_or_rl {
+        let layer_of: HashMap<&str, usize> = ordering
+            .iter()
+            .enumerate()
+            .flat_map(|(li, ids)| ids.iter().map(move |id| (id.as_str(), li)))
+            .collect();
+        for eidx in g.digraph.edge_indices() {
+            if g.digraph[eidx].label.as_deref().unwrap_or("").is_empty() {
+                continue;
+            }
+            let (a, b) = g.digraph.edge_endpoints(eidx).unwrap();
+            let (Some(&la), Some(&lb)) = (
+                layer_of.get(g.digraph[a].id.as_str()),
+                layer_of.get(g.digraph[b].id.as_str()),
+            ) else {
+                continue;
+            };
+            if config.label_rows && lb > 0 {
+                labelled_gaps.insert(lb - 1);
+            }
+            if config.label_gaps && la != lb {
+                let rows = g.digraph[eidx]
+                    .label
+                    .as_deref()
+                    .unwrap_or("")
+                    .lines()
+                    .count();
+                let gap = label_gaps.entry(la.min(lb)).or_insert(0);
+                *gap = (*gap).max(LABEL_GAP_ROWS + rows as i32 - 1);
+            }
+        }
+    }
+
+    // Long edge labels: in TD/BT a label runs right from its target's entry
+    // column, so the gap after that target widens to fit it; in LR/RL it sits
+    // in the gap between layers, which widens instead.
+    let mut label_into: HashMap<&str, i32> = HashMap::new();
+    for eidx in g.digraph.edge_indices() {
+        let label_w = label_width(g.digraph[eidx].label.as_deref().unwrap_or(""));
+        if label_w > 0 {
+            let (_, b) = g.digraph.edge_endpoints(eidx).unwrap();
+            let widest = label_into.entry(g.digraph[b].id.as_str()).or_insert(0);
+            *widest = (*widest).max(label_w);
+        }
+    }
 
     let mut y_offset = 0i32;
     for (layer_idx, layer_nodes) in ordering.iter().enumerate() {
@@ -871,10 +1961,13 @@ fn assign_coordinates_rust(
             } else {
                 let idx = g.node_index[node_id];
                 let nd = &g.digraph[idx];
-                let label_w = nd.label.lines().map(|l| l.len()).max().unwrap_or(0) as i32;
-                let label_h = std::cmp::max(nd.label.lines().count() as i32, 1);
-                let w_vis = std::cmp::max(label_w + 2 + 2 * padding, 5);
-                let h_vis = std::cmp::max(label_h + 2, min_node_h);
+                let (mut w_vis, mut h_vis) =
+                    node_dims(&nd.label, &nd.shape, 5, config, is_lr_or_rl);
+                h_vis = std::cmp::max(h_vis, min_node_h);
+                if config.skeleton && !node_id.starts_with(DUMMY_PREFIX) {
+                    let label_w = nd.label.lines().map(display_width).max().unwrap_or(0);
+                    (w_vis, h_vis) = (label_w, 1);
+                }
                 // For LR/RL: swap width and height in TD layout space so that after
                 // transposing the coordinates, nodes appear with the correct aspect ratio.
                 if is_lr_or_rl {
@@ -891,7 +1984,12 @@ fn assign_coordinates_rust(
         // Second pass: place nodes
         let mut x_offset = 0i32;
         for (i, node_id) in layer_nodes.iter().enumerate() {
-            let (w, h) = dims[i];
+            let (w, mut h) = dims[i];
+            // layer_max_height: collapsed subgraphs and long-edge dummies
+            // keep their own size
+            if config.layer_max_height && !dim_overrides.contains_key(node_id) {
+                h = layer_max_h;
+            }
             let idx = g.node_index[node_id];
             let nd = &g.digraph[idx];
             graph::nll_push(
@@ -906,9 +2004,34 @@ fn assign_coordinates_rust(
                 nd.label.clone(),
                 nd.shape.clone(),
             );
-            x_offset += w + h_gap;
+            let label_w = if is_lr_or_rl {
+                0
+            } else {
+                label_into.get(node_id.as_str()).copied().unwrap_or(0)
+            };
+            // Long-edge columns keep a blank cell between them and their
+            // neighbours even at h_gap 0, so parallel lines never merge.
+            let beside_dummy = node_id.starts_with(DUMMY_PREFIX)
+                || layer_nodes
+                    .get(i + 1)
+                    .is_some_and(|next| next.starts_with(DUMMY_PREFIX));
+            let gap = if beside_dummy { h_gap.max(1) } else { h_gap };
+            x_offset += w + std::cmp::max(gap, label_w - (w - w / 2) + 1);
+        }
+        let next_label_w = match ordering.get(layer_idx + 1) {
+            Some(next) if is_lr_or_rl => next
+                .iter()
+                .filter_map(|id| label_into.get(id.as_str()))
+                .max()
+                .copied()
+                .unwrap_or(0),
+            _ => 0,
+        };
+        let min_gap = label_gaps.get(&layer_idx).copied().unwrap_or(0);
+        y_offset += layer_max_h + v_gap.max(next_label_w + 3).max(min_gap);
+        if labelled_gaps.contains(&layer_idx) {
+            y_offset += 1;
         }
-        y_offset += layer_max_h + v_gap;
     }
 
     // Center layers: find max total width, then offset each layer to center
@@ -933,21 +2056,123 @@ fn assign_coordinates_rust(
     nll
 }
 
+/// Phase 5b (straighten only): move each dummy chain onto one shared column so
+/// a long edge renders as a single vertical run instead of a zig-zag.
+///
+/// Candidate columns are the source exit, the target entry, then the chain's
+/// current columns; the first one clear of every other node in the chain's
+/// layers wins. Chains with no clear column keep their barycenter positions.
+fn straighten_dummy_chains(nodes: &graph::NodeLayoutList, chains: &[DummyChain]) {
+    let nn = graph::nll_len(nodes.clone());
+    let center = |id: &str| -> Option<i32> {
+        let i = graph::nll_id_to_index(nodes.clone(), id.to_string());
+        if i < 0 {
+            return None;
+        }
+        Some(graph::nll_get_x(nodes.clone(), i) + graph::nll_get_width(nodes.clone(), i) / 2)
+    };
+
+    for chain in chains {
+        let dummy_idxs: Vec<i32> = chain
+            .dummy_ids
+            .iter()
+            .map(|d| graph::nll_id_to_index(nodes.clone(), d.clone()))
+            .filter(|&i| i >= 0)
+            .collect();
+        if dummy_idxs.is_empty() {
+            continue;
+        }
+
+        let mut candidates: Vec<i32> = Vec::new();
+        candidates.extend(center(&chain.from_id));
+        candidates.extend(center(&chain.to_id));
+        candidates.extend(chain.dummy_ids.iter().filter_map(|d| center(d)));
+
+        let is_clear = |col: i32| {
+            dummy_idxs.iter().all(|&di| {
+                let layer = graph::nll_get_layer(nodes.clone(), di);
+                (0..nn).all(|j| {
+                    if j == di || graph::nll_get_layer(nodes.clone(), j) != layer {
+                        return true;
+                    }
+                    let x = graph::nll_get_x(nodes.clone(), j);
+                    let w = graph::nll_get_width(nodes.clone(), j);
+                    col < x - 1 || col > x + w
+                })
+            })
+        };
+
+        if let Some(col) = candidates.into_iter().find(|&c| c >= 0 && is_clear(c)) {
+            for &di in &dummy_idxs {
+                graph::nll_set_x(nodes.clone(), di, col);
+            }
+        }
+    }
+}
+
+/// Build orthogonal waypoints that follow a dummy chain: drop from the source,
+/// jog between layers only where the next dummy sits in a different column,
+/// and finish on the target's entry point.
+fn chain_waypoints(
+    nodes: &graph::NodeLayoutList,
+    exit: (i32, i32),
+    entry: (i32, i32),
+    dummy_ids: &[String],
+) -> Vec<(i32, i32)> {
+    let mut wps = vec![exit];
+    let (mut cx, mut cy) = exit;
+    for d in dummy_ids {
+        let i = graph::nll_id_to_index(nodes.clone(), d.clone());
+        if i < 0 {
+            continue;
+        }
+        let dx = graph::nll_get_x(nodes.clone(), i);
+        let dy = graph::nll_get_y(nodes.clone(), i);
+        if dx != cx {
+            let jog_y = (cy + dy) / 2;
+            wps.push((cx, jog_y));
+            wps.push((dx, jog_y));
+            cx = dx;
+        }
+        cy = dy;
+    }
+    let (ex, ey) = entry;
+    if ex != cx {
+        let jog_y = (cy + ey) / 2;
+        wps.push((cx, jog_y));
+        wps.push((ex, jog_y));
+    }
+    wps.push(entry);
+    wps
+}
+
 /// Phase 6: Route edges using A* pathfinding with fallback.
+///
+/// Edges with a dummy chain (straighten mode) follow the chain instead, and
+/// edges with a `%% route` column run down that column.
 fn route_edges_rust(
     g: &graph::Graph,
     nodes: &graph::NodeLayoutList,
     reversed: &[(String, String)],
+    chains: &[DummyChain],
+    columns: &EdgeColumns,
+    max_cells: i32,
+    router: &dyn EdgeRouter,
+    mut trace: Option<&mut Vec<EdgeTrace>>,
 ) -> graph::EdgeRouteList {
     let routes = graph::erl_new();
     let nn = graph::nll_len(nodes.clone());
 
-    // Build occupancy grid
-    let mut max_x: i32 = 40;
-    let mut max_y: i32 = 10;
+    // Build occupancy grid, with a 10-cell margin past the outermost boxes
+    let mut max_x: i32 = 0;
+    let mut max_y: i32 = 0;
     for i in 0..nn {
-        let rx = graph::nll_get_x(nodes.clone(), i) + graph::nll_get_width(nodes.clone(), i) + 10;
-        let ry = graph::nll_get_y(nodes.clone(), i) + graph::nll_get_height(nodes.clone(), i) + 10;
+        let rx = graph::nll_get_x(nodes.clone(), i)
+            .saturating_add(graph::nll_get_width(nodes.clone(), i))
+            .saturating_add(10);
+        let ry = graph::nll_get_y(nodes.clone(), i)
+            .saturating_add(graph::nll_get_height(nodes.clone(), i))
+            .saturating_add(10);
         if rx > max_x {
             max_x = rx;
         }
@@ -955,11 +2180,15 @@ fn route_edges_rust(
             max_y = ry;
         }
     }
+    // The boxes alone overflow `max_cells`, so painting will refuse the
+    // canvas anyway; don't allocate a grid to route on.
+    if (max_x - 10) as i64 * (max_y - 10) as i64 > max_cells as i64 {
+        return routes;
+    }
 
-    let mut grid = pathfinder::grid_new(max_x, max_y);
+    let mut grid = routing::Grid::new(max_x, max_y);
     for i in 0..nn {
-        pathfinder::grid_mark_blocked(
-            &mut grid,
+        grid.mark_blocked(
             graph::nll_get_x(nodes.clone(), i),
             graph::nll_get_y(nodes.clone(), i),
             graph::nll_get_width(nodes.clone(), i),
@@ -969,6 +2198,10 @@ fn route_edges_rust(
 
     // Collect all edges with metadata
     let reversed_set: HashSet<(String, String)> = reversed.iter().cloned().collect();
+    let chain_map: HashMap<(String, String), &DummyChain> = chains
+        .iter()
+        .map(|c| ((c.from_id.clone(), c.to_id.clone()), c))
+        .collect();
     for eidx in g.digraph.edge_indices() {
         let (a, b) = g.digraph.edge_endpoints(eidx).unwrap();
         let from_id = g.digraph[a].id.clone();
@@ -978,12 +2211,10 @@ fn route_edges_rust(
             continue;
         }
 
-        let is_rev = reversed_set.contains(&(from_id.clone(), to_id.clone()));
-        let (vis_from, vis_to) = if is_rev {
-            (to_id.clone(), from_id.clone())
-        } else {
-            (from_id.clone(), to_id.clone())
-        };
+        // A back edge keeps its direction: it leaves the top of its (lower)
+        // source and comes up into the bottom of its target.
+        let up = reversed_set.contains(&(from_id.clone(), to_id.clone()));
+        let (vis_from, vis_to) = (from_id.clone(), to_id.clone());
 
         let from_idx = graph::nll_id_to_index(nodes.clone(), vis_from.clone());
         let to_idx = graph::nll_id_to_index(nodes.clone(), vis_to.clone());
@@ -991,34 +2222,99 @@ fn route_edges_rust(
             continue;
         }
 
-        let exit_x = graph::nll_get_x(nodes.clone(), from_idx)
-            + graph::nll_get_width(nodes.clone(), from_idx) / 2;
-        let exit_y = graph::nll_get_y(nodes.clone(), from_idx)
-            + graph::nll_get_height(nodes.clone(), from_idx);
-        let entry_x = graph::nll_get_x(nodes.clone(), to_idx)
-            + graph::nll_get_width(nodes.clone(), to_idx) / 2;
-        let entry_y = graph::nll_get_y(nodes.clone(), to_idx) - 1;
-
-        let mut path = pathfinder::a_star(&mut grid, exit_x, exit_y, entry_x, entry_y);
-        let plen = graph::point_list_len(&path);
+        let (exit_x, exit_y) = if up {
+            let col = back_port(nodes, from_idx);
+            (col, graph::nll_get_y(nodes.clone(), from_idx) - 1)
+        } else {
+            let x = graph::nll_get_x(nodes.clone(), from_idx)
+                + graph::nll_get_width(nodes.clone(), from_idx) / 2;
+            let y = graph::nll_get_y(nodes.clone(), from_idx)
+                + graph::nll_get_height(nodes.clone(), from_idx);
+            (x, y)
+        };
+        let to_y = graph::nll_get_y(nodes.clone(), to_idx);
+        let to_bottom = to_y + graph::nll_get_height(nodes.clone(), to_idx);
+        // Diamonds take the arrowhead on their vertex (the border's centre
+        // cell) rather than the row beyond it, so the edge visibly hits the point.
+        let diamond = graph::nll_get_shape(nodes.clone(), to_idx) == "Diamond";
+        let (entry_x, mut entry_y) = if up {
+            let col = back_port(nodes, to_idx);
+            (col, if diamond { to_bottom - 1 } else { to_bottom })
+        } else {
+            let x = graph::nll_get_x(nodes.clone(), to_idx)
+                + graph::nll_get_width(nodes.clone(), to_idx) / 2;
+            (x, if diamond { to_y } else { to_y - 1 })
+        };
+        // Target shares a row band with the source: enter from below instead.
+        let same_layer =
+            !up && to_y < exit_y && to_bottom > graph::nll_get_y(nodes.clone(), from_idx);
+        if same_layer {
+            entry_y = to_bottom;
+        }
 
-        let mut waypoints = if plen > 0 {
-            pathfinder::simplify_path(&mut path)
+        let chain_key = if up {
+            (vis_to.clone(), vis_from.clone())
         } else {
-            // Fallback: orthogonal L-path
-            let mid_y = (exit_y + entry_y) / 2;
-            vec![
-                (exit_x, exit_y),
-                (exit_x, mid_y),
-                (entry_x, mid_y),
-                (entry_x, entry_y),
-            ]
+            (vis_from.clone(), vis_to.clone())
+        };
+        let (mut waypoints, method) = if let Some(chain) = chain_map.get(&chain_key) {
+            // Chains run down the layers; a back edge walks its chain in reverse
+            let wp = if up {
+                let mut wp = chain_waypoints(
+                    nodes,
+                    (entry_x, entry_y),
+                    (exit_x, exit_y),
+                    &chain.dummy_ids,
+                );
+                wp.reverse();
+                wp
+            } else {
+                chain_waypoints(
+                    nodes,
+                    (exit_x, exit_y),
+                    (entry_x, entry_y),
+                    &chain.dummy_ids,
+                )
+            };
+            (wp, RouteMethod::DummyChain)
+        } else if let Some(&col) = columns.get(&(vis_from.clone(), vis_to.clone())) {
+            let wp = pinned_column_path((exit_x, exit_y), (entry_x, entry_y), col);
+            (wp, RouteMethod::Pinned)
+        } else if same_layer {
+            let wp = same_layer_detour(nodes, from_idx, (exit_x, exit_y), (entry_x, entry_y));
+            (wp, RouteMethod::SameLayer)
+        } else {
+            let path = router.route(&mut grid, (exit_x, exit_y), (entry_x, entry_y));
+            if let Some(path) = path.filter(|p| p.len() >= 2) {
+                (path, RouteMethod::AStar)
+            } else {
+                // Fallback: orthogonal L-path
+                let mid_y = (exit_y + entry_y) / 2;
+                let wp = vec![
+                    (exit_x, exit_y),
+                    (exit_x, mid_y),
+                    (entry_x, mid_y),
+                    (entry_x, entry_y),
+                ];
+                (wp, RouteMethod::Fallback)
+            }
         };
 
         // Fix vertical endpoints
-        ensure_vertical_endpoints(&mut waypoints);
+        ensure_vertical_endpoints(&mut waypoints, up);
         let fixed_wp = waypoints;
 
+        if let Some(traces) = trace.as_deref_mut() {
+            traces.push(EdgeTrace {
+                from: vis_from.clone(),
+                to: vis_to.clone(),
+                exit: (exit_x, exit_y),
+                entry: (entry_x, entry_y),
+                method,
+                waypoints: fixed_wp.clone(),
+            });
+        }
+
         let label = ed.label.clone().unwrap_or_default();
         graph::erl_push(
             routes.clone(),
@@ -1028,11 +2324,79 @@ fn route_edges_rust(
             ed.edge_type.clone(),
             fixed_wp,
         );
+        graph::erl_set_classes(
+            routes.clone(),
+            graph::erl_len(routes.clone()) - 1,
+            ed.classes.clone(),
+        );
     }
 
     routes
 }
 
+/// `%% route` path: one row out of the exit, across to `col`, along it to
+/// the row before the entry, then across and in.
+fn pinned_column_path(exit: (i32, i32), entry: (i32, i32), col: i32) -> Vec<(i32, i32)> {
+    let ((exit_x, exit_y), (entry_x, entry_y)) = (exit, entry);
+    let step = if entry_y < exit_y { -1 } else { 1 };
+    let turn_out = exit_y + step;
+    // Ends closer than three rows turn in on the same row they turned out on
+    let turn_in = if (entry_y - step - turn_out) * step > 0 {
+        entry_y - step
+    } else {
+        turn_out
+    };
+    let mut wps = vec![
+        exit,
+        (exit_x, turn_out),
+        (col, turn_out),
+        (col, turn_in),
+        (entry_x, turn_in),
+        entry,
+    ];
+    wps.dedup();
+    wps
+}
+
+/// Column where back edges meet node `idx`: the one right of the centre
+/// column forward edges use, so the two directions never share a cell of the
+/// border. Back edges on the same border share it and fan out like forward ones.
+fn back_port(nodes: &graph::NodeLayoutList, idx: i32) -> i32 {
+    let x = graph::nll_get_x(nodes.clone(), idx);
+    let w = graph::nll_get_width(nodes.clone(), idx);
+    (x + w / 2 + 1).min(x + w - 2).max(x + 1)
+}
+
+/// Route between two boxes of the same layer: drop out of the source, run
+/// along the row below every box between the two columns, and come up into
+/// the target's bottom edge, so the line never crosses an intervening node.
+fn same_layer_detour(
+    nodes: &graph::NodeLayoutList,
+    from_idx: i32,
+    (exit_x, exit_y): (i32, i32),
+    (entry_x, entry_y): (i32, i32),
+) -> Vec<(i32, i32)> {
+    let top = graph::nll_get_y(nodes.clone(), from_idx);
+    let (lo, hi) = (exit_x.min(entry_x), exit_x.max(entry_x));
+    let mut lowest = exit_y.max(entry_y);
+    for i in 0..graph::nll_len(nodes.clone()) {
+        let x = graph::nll_get_x(nodes.clone(), i);
+        let y = graph::nll_get_y(nodes.clone(), i);
+        let w = graph::nll_get_width(nodes.clone(), i);
+        let h = graph::nll_get_height(nodes.clone(), i);
+        if x <= hi && x + w > lo && y < exit_y && y + h > top {
+            lowest = lowest.max(y + h);
+        }
+    }
+    let detour_y = lowest + 1;
+    vec![
+        (exit_x, exit_y),
+        (exit_x, detour_y),
+        (entry_x, detour_y),
+        (entry_x, entry_y),
+    ]
+}
+
 // ── Canvas direct-mutation helpers ──────────────────────────────────────────
 // canvas.hom functions take Canvas by value (.clone()), so mutations are lost.
 // These helpers mutate c.cells directly via &mut Canvas.
@@ -1065,15 +2429,36 @@ fn cset_merge(c: &mut canvas::Canvas, col: i32, row: i32, ch: String) {
     }
 }
 
+/// Write `s` from `col`, one cell per display column: a wide (CJK) character
+/// takes its cell plus an empty one after it so the row still lines up.
 fn cwrite_str(c: &mut canvas::Canvas, col: i32, row: i32, s: &str) {
-    for (i, ch) in s.chars().enumerate() {
-        let cc = col + i as i32;
-        if cc >= 0 && cc < c.width && row >= 0 && row < c.height {
-            c.cells[row as usize][cc as usize] = ch.to_string();
+    let mut cc = col;
+    for ch in s.chars() {
+        let w = ch.width().unwrap_or(1).max(1) as i32;
+        for k in 0..w {
+            let cell = cc + k;
+            if cell >= 0 && cell < c.width && row >= 0 && row < c.height {
+                c.cells[row as usize][cell as usize] = if k == 0 {
+                    ch.to_string()
+                } else {
+                    String::new()
+                };
+            }
         }
+        cc += w;
     }
 }
 
+/// Terminal display columns of `s` (wide CJK characters count as two).
+fn display_width(s: &str) -> i32 {
+    s.width() as i32
+}
+
+/// Display columns of the widest line of a (possibly multi-line) label.
+fn label_width(label: &str) -> i32 {
+    label.lines().map(display_width).max().unwrap_or(0)
+}
+
 fn cdraw_box(c: &mut canvas::Canvas, x: i32, y: i32, w: i32, h: i32, bc: &canvas::BoxChars) {
     if w < 2 || h < 2 {
         return;
@@ -1094,30 +2479,137 @@ fn cdraw_box(c: &mut canvas::Canvas, x: i32, y: i32, w: i32, h: i32, bc: &canvas
     }
 }
 
+/// Circle outline: rounded caps inset one column on the top and bottom rows,
+/// `(` `)` down the sides.
+fn cdraw_circle(c: &mut canvas::Canvas, x: i32, y: i32, w: i32, h: i32, horizontal: &str) {
+    if w < 4 || h < 2 {
+        return;
+    }
+    let corners = if c.charset == CharSet::Ascii {
+        [".", ".", "'", "'"]
+    } else {
+        ["╭", "╮", "╰", "╯"]
+    };
+    let (x1, y1) = (x + w - 1, y + h - 1);
+    cset(c, x + 1, y, corners[0].to_string());
+    cset(c, x1 - 1, y, corners[1].to_string());
+    cset(c, x + 1, y1, corners[2].to_string());
+    cset(c, x1 - 1, y1, corners[3].to_string());
+    for col in (x + 2)..(x1 - 1) {
+        cset(c, col, y, horizontal.to_string());
+        cset(c, col, y1, horizontal.to_string());
+    }
+    for row in (y + 1)..y1 {
+        cset(c, x, row, "(".to_string());
+        cset(c, x1, row, ")".to_string());
+    }
+}
+
 // ── Renderer helpers ────────────────────────────────────────────────────────
 
-fn paint_node(c: &mut canvas::Canvas, x: i32, y: i32, w: i32, h: i32, label: &str, shape: &str) {
+/// Built-in class (`A:::emphasis`) that draws the node with a heavy border.
+const EMPHASIS_CLASS: &str = "emphasis";
+
+/// Built-in edge classes (`A -->:::thick B`, `A -->:::dotted B`): the edge is
+/// drawn with that stroke, keeping its arrowheads. The last one listed wins;
+/// other classes leave the edge type as written.
+fn styled_edge_type(edge_type: &str, classes: &[String]) -> String {
+    let stroke = classes.iter().rev().find_map(|c| match c.as_str() {
+        "thick" => Some("Thick"),
+        "dotted" => Some("Dotted"),
+        _ => None,
+    });
+    let Some(stroke) = stroke else {
+        return edge_type.to_string();
+    };
+    if edge_type.starts_with("Bidir") {
+        format!("Bidir{stroke}")
+    } else if edge_type.ends_with("Line") {
+        format!("{stroke}Line")
+    } else {
+        format!("{stroke}Arrow")
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn paint_node(
+    c: &mut canvas::Canvas,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    label: &str,
+    shape: &str,
+    heavy: bool,
+    overrides: &BoxCharsOverride,
+    align: &TextAlign,
+) {
     let cs = c.charset.clone();
-    let bc = match shape {
-        "Rounded" => canvas::box_chars_rounded(cs),
-        "Diamond" => canvas::box_chars_diamond(cs),
-        "Circle" => canvas::box_chars_circle(cs),
-        _ => canvas::box_chars_for_charset(cs),
+    let mut bc = match shape {
+        "Rounded" => canvas::box_chars_rounded(cs.clone()),
+        "Diamond" => canvas::box_chars_diamond(cs.clone()),
+        "Circle" => canvas::box_chars_circle(cs.clone()),
+        _ => canvas::box_chars_for_charset(cs.clone()),
     };
-    cdraw_box(c, x, y, w, h, &bc);
+    if let ("Diamond", [tl, tr, bl, br]) = (shape, overrides.diamond_corners.as_slice()) {
+        bc.top_left = tl.clone();
+        bc.top_right = tr.clone();
+        bc.bottom_left = bl.clone();
+        bc.bottom_right = br.clone();
+    }
+    if heavy {
+        // Heavy box for rectangles (rounded corners have no heavy form);
+        // diamonds and circles keep their corners and thicken the sides.
+        let hb = canvas::box_chars_heavy(cs);
+        match shape {
+            "Diamond" | "Circle" => {
+                bc.horizontal = hb.horizontal;
+                if bc.vertical != " " {
+                    bc.vertical = hb.vertical;
+                }
+            }
+            _ => bc = hb,
+        }
+    }
+    let lines: Vec<&str> = label.split('\n').collect();
+    // Centred vertically: a box taller than its label keeps the spare rows
+    // split above and below it (any odd one below)
+    let first_row = y + 1 + std::cmp::max(0, h - 2 - lines.len() as i32) / 2;
+    if shape == "Circle" {
+        cdraw_circle(c, x, y, w, h, &bc.horizontal);
+    } else {
+        cdraw_box(c, x, y, w, h, &bc);
+    }
 
     let inner_w = std::cmp::max(0, w - 2);
-    let lines: Vec<&str> = label.split('\n').collect();
+    // Left/right-aligned lines share the column block of the widest line
+    let widest = lines.iter().map(|l| display_width(l)).max().unwrap_or(0);
+    let block = std::cmp::max(0, inner_w - widest) / 2;
     for (i, line) in lines.iter().enumerate() {
-        let label_row = y + 1 + i as i32;
-        let line_len = line.len() as i32;
-        let pad = std::cmp::max(0, inner_w - line_len) / 2;
+        let label_row = first_row + i as i32;
+        let pad = match align {
+            TextAlign::Left => block,
+            TextAlign::Center => std::cmp::max(0, inner_w - display_width(line)) / 2,
+            TextAlign::Right => block + widest - display_width(line),
+        };
         let col_start = x + 1 + pad;
         cwrite_str(c, col_start, label_row, line);
     }
 }
 
-fn paint_edge(c: &mut canvas::Canvas, waypoints: &[(i32, i32)], edge_type: &str, label: &str) {
+/// Draw one routed edge. `beside` holds the boxes of layers the edge skips
+/// when pass_through_marks is on (empty otherwise): where its line runs
+/// right along one of their sides it is drawn with the pass-through glyph
+/// (`┊`/`┈`, ASCII `!`/`~`) so it doesn't read as a connection.
+fn paint_edge(
+    c: &mut canvas::Canvas,
+    waypoints: &[(i32, i32)],
+    edge_type: &str,
+    label: &str,
+    label_pos: Option<(i32, i32)>,
+    beside: &[&LayoutRect],
+    boxed_label: bool,
+) {
     if waypoints.len() < 2 {
         return;
     }
@@ -1125,22 +2617,44 @@ fn paint_edge(c: &mut canvas::Canvas, waypoints: &[(i32, i32)], edge_type: &str,
     let cs = c.charset.clone();
     let bc = canvas::box_chars_for_charset(cs.clone());
 
+    // ASCII thick lines borrow the heavy-border `=`/`#`; dotted use `.`/`:`.
+    let ascii = cs == CharSet::Ascii;
     let (h_ch, v_ch) = match edge_type {
+        "ThickArrow" | "ThickLine" | "BidirThick" if ascii => ("=".to_string(), "#".to_string()),
         "ThickArrow" | "ThickLine" | "BidirThick" => ("═".to_string(), "║".to_string()),
+        "DottedArrow" | "DottedLine" | "BidirDotted" if ascii => (".".to_string(), ":".to_string()),
         "DottedArrow" | "DottedLine" | "BidirDotted" => ("╌".to_string(), "╎".to_string()),
         _ => (bc.horizontal.clone(), bc.vertical.clone()),
     };
+    let (h_pass, v_pass) = if ascii { ("~", "!") } else { ("┈", "┊") };
+    // Line glyph for (col, row): the pass-through one when a skipped box's
+    // side runs right next to it.
+    let line_ch = |col: i32, row: i32, horizontal: bool| {
+        let touches = beside.iter().any(|r| {
+            if horizontal {
+                (row == r.y - 1 || row == r.y + r.h) && col >= r.x && col < r.x + r.w
+            } else {
+                (col == r.x - 1 || col == r.x + r.w) && row >= r.y && row < r.y + r.h
+            }
+        });
+        match (touches, horizontal) {
+            (true, true) => h_pass.to_string(),
+            (true, false) => v_pass.to_string(),
+            (false, true) => h_ch.clone(),
+            (false, false) => v_ch.clone(),
+        }
+    };
 
     for i in 0..waypoints.len() - 1 {
         let (x0, y0) = waypoints[i];
         let (x1, y1) = waypoints[i + 1];
         if y0 == y1 {
             for col in (x0.min(x1) + 1)..x0.max(x1) {
-                cset_merge(c, col, y0, h_ch.clone());
+                cset_merge(c, col, y0, line_ch(col, y0, true));
             }
         } else if x0 == x1 {
             for row in (y0.min(y1) + 1)..y0.max(y1) {
-                cset_merge(c, x0, row, v_ch.clone());
+                cset_merge(c, x0, row, line_ch(x0, row, false));
             }
         }
     }
@@ -1178,7 +2692,16 @@ fn paint_edge(c: &mut canvas::Canvas, waypoints: &[(i32, i32)], edge_type: &str,
                 arms.up = true;
             }
         }
-        cset_merge(c, px, py, canvas::arms_to_char(arms, cs.clone()));
+        // Only corners take the junction glyph; a straight waypoint keeps
+        // the edge's own style so dotted and thick lines stay unbroken.
+        let ch = if (arms.left || arms.right) && (arms.up || arms.down) {
+            canvas::arms_to_char(arms, cs.clone())
+        } else if arms.left || arms.right {
+            line_ch(px, py, true)
+        } else {
+            line_ch(px, py, false)
+        };
+        cset_merge(c, px, py, ch);
     }
 
     // Arrowheads
@@ -1192,42 +2715,116 @@ fn paint_edge(c: &mut canvas::Canvas, waypoints: &[(i32, i32)], edge_type: &str,
     ];
     let bidir_types = ["BidirArrow", "BidirDotted", "BidirThick"];
 
-    if arrow_types.contains(&edge_type) {
+    if arrow_types.contains(&edge_type)
+        && let Some(arrow) = arrowhead(&bc, waypoints.iter().rev())
+    {
         let (last_x, last_y) = waypoints[waypoints.len() - 1];
-        let (prev_x, prev_y) = waypoints[waypoints.len() - 2];
-        let arrow = if last_y < prev_y {
-            bc.arrow_up.clone()
-        } else if last_y > prev_y {
-            bc.arrow_down.clone()
-        } else if last_x > prev_x {
-            bc.arrow_right.clone()
-        } else {
-            bc.arrow_left.clone()
-        };
         cset(c, last_x, last_y, arrow);
     }
 
-    if bidir_types.contains(&edge_type) && waypoints.len() >= 2 {
+    if bidir_types.contains(&edge_type)
+        && let Some(arrow) = arrowhead(&bc, waypoints.iter())
+    {
         let (first_x, first_y) = waypoints[0];
-        let (second_x, second_y) = waypoints[1];
-        let arrow = if first_y < second_y {
-            bc.arrow_up.clone()
-        } else if first_y > second_y {
-            bc.arrow_down.clone()
-        } else if first_x > second_x {
-            bc.arrow_right.clone()
-        } else {
-            bc.arrow_left.clone()
-        };
         cset(c, first_x, first_y, arrow);
     }
 
-    if !label.is_empty() && waypoints.len() >= 2 {
-        let mid = waypoints.len() / 2;
-        let (lx, ly) = waypoints[mid];
-        let label_y = std::cmp::max(0, ly - 1);
-        cwrite_str(c, lx, label_y, label);
+    // `--o` / `--x` (and `o--o` / `x--x`) end in a circle or cross instead
+    let marker = match edge_type {
+        "CircleArrow" | "BidirCircle" => Some("o"),
+        "CrossArrow" | "BidirCross" => Some("x"),
+        _ => None,
+    };
+    if let Some(marker) = marker {
+        let (last_x, last_y) = waypoints[waypoints.len() - 1];
+        cset(c, last_x, last_y, marker.to_string());
+        if edge_type.starts_with("Bidir") {
+            cset(c, waypoints[0].0, waypoints[0].1, marker.to_string());
+        }
+    }
+
+    if let Some((lx, ly)) = edge_label_origin(waypoints, label, label_pos) {
+        let top = ly - (label.lines().count() as i32 - 1);
+        for (i, line) in label.lines().enumerate() {
+            let row = top + i as i32;
+            if row < 0 {
+                continue;
+            }
+            if boxed_label {
+                for x in lx - 1..=lx + display_width(line) {
+                    cset(c, x, row, " ".to_string());
+                }
+            }
+            cwrite_str(c, lx, row, line);
+        }
+    }
+}
+
+/// Drop every waypoint that lies on the straight run between its neighbours
+/// (repeats included), keeping the ends and every turn. A point the path
+/// doubles back from is a turn and stays.
+fn simplify_waypoints(waypoints: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let mut out: Vec<(i32, i32)> = Vec::with_capacity(waypoints.len());
+    for (i, &p) in waypoints.iter().enumerate() {
+        let (Some(&prev), Some(&next)) = (out.last(), waypoints.get(i + 1)) else {
+            out.push(p);
+            continue;
+        };
+        let between = |a: i32, b: i32, c: i32| a.min(c) <= b && b <= a.max(c);
+        let on_run = (prev.0 == p.0 && p.0 == next.0 && between(prev.1, p.1, next.1))
+            || (prev.1 == p.1 && p.1 == next.1 && between(prev.0, p.0, next.0));
+        if !on_run {
+            out.push(p);
+        }
+    }
+    out
+}
+
+/// Arrowhead for the tip `points[0]`, pointing away from the nearest point
+/// after it that differs from the tip (repeated tip points form zero-length
+/// segments that give no direction). `None` if every point is the tip.
+fn arrowhead<'a>(
+    bc: &canvas::BoxChars,
+    mut points: impl Iterator<Item = &'a (i32, i32)>,
+) -> Option<String> {
+    let &(tip_x, tip_y) = points.next()?;
+    let &(prev_x, prev_y) = points.find(|&&p| p != (tip_x, tip_y))?;
+    let arrow = if tip_y < prev_y {
+        &bc.arrow_up
+    } else if tip_y > prev_y {
+        &bc.arrow_down
+    } else if tip_x > prev_x {
+        &bc.arrow_right
+    } else {
+        &bc.arrow_left
+    };
+    Some(arrow.clone())
+}
+
+/// First cell of an edge label's last line: `label_pos` when set, otherwise
+/// the row above the middle waypoint. A multi-line label whose edge ends
+/// going down sits instead just right of that final run, last line on the
+/// row above the arrowhead. Earlier lines stack upward from there.
+fn edge_label_origin(
+    waypoints: &[(i32, i32)],
+    label: &str,
+    label_pos: Option<(i32, i32)>,
+) -> Option<(i32, i32)> {
+    if label_pos.is_some() {
+        return label_pos;
+    }
+    if label.is_empty() || waypoints.len() < 2 {
+        return None;
     }
+    if let [.., (px, py), (tx, ty)] = waypoints[..]
+        && label.contains('\n')
+        && px == tx
+        && ty > py
+    {
+        return Some((tx + 1, ty - 1));
+    }
+    let (lx, ly) = waypoints[waypoints.len() / 2];
+    Some((lx, std::cmp::max(0, ly - 1)))
 }
 
 fn paint_exit_stubs(
@@ -1299,20 +2896,29 @@ fn paint_exit_stubs_ir(c: &mut canvas::Canvas, ir: &LayoutIR) {
         let (first_wp_x, first_wp_y) = edge.waypoints[0];
 
         // Find the source rect that contains/borders the first waypoint
-        // (the rect whose border is closest to the first waypoint)
+        // (the rect whose border is closest to the first waypoint; a rect
+        // directly touching it beats one a row away, e.g. a subgraph member
+        // sitting just inside its container's bottom border)
         let mut best: Option<&LayoutRect> = None;
-        let mut best_dist = i32::MAX;
+        let mut best_key = (true, i32::MAX);
         for r in &ir.rects {
             // Check if the waypoint is just outside one of the rect's borders
             let cx = r.x + r.w / 2;
             let cy = r.y + r.h / 2;
             let dist = (first_wp_x - cx).abs() + (first_wp_y - cy).abs();
-            let on_border = (first_wp_y >= r.y + r.h && first_wp_y <= r.y + r.h + 1)
-                || (first_wp_y < r.y && first_wp_y >= r.y - 1)
-                || (first_wp_x >= r.x + r.w && first_wp_x <= r.x + r.w + 1)
-                || (first_wp_x < r.x && first_wp_x >= r.x - 1);
-            if on_border && dist < best_dist {
-                best_dist = dist;
+            let beside_x = first_wp_x >= r.x && first_wp_x < r.x + r.w;
+            let beside_y = first_wp_y >= r.y && first_wp_y < r.y + r.h;
+            let on_border = (beside_x && first_wp_y >= r.y + r.h && first_wp_y <= r.y + r.h + 1)
+                || (beside_x && first_wp_y < r.y && first_wp_y >= r.y - 1)
+                || (beside_y && first_wp_x >= r.x + r.w && first_wp_x <= r.x + r.w + 1)
+                || (beside_y && first_wp_x < r.x && first_wp_x >= r.x - 1);
+            let flush = first_wp_y == r.y + r.h
+                || first_wp_y == r.y - 1
+                || first_wp_x == r.x + r.w
+                || first_wp_x == r.x - 1;
+            let key = (!flush, dist);
+            if on_border && key < best_key {
+                best_key = key;
                 best = Some(r);
             }
         }
@@ -1326,18 +2932,22 @@ fn paint_exit_stubs_ir(c: &mut canvas::Canvas, ir: &LayoutIR) {
         let center_y = r.y + r.h / 2;
 
         let (stub_x, stub_y, arm_dir) = if first_wp_y >= r.y + r.h {
-            (center_x, r.y + r.h - 1, "down")
+            (first_wp_x, r.y + r.h - 1, "down")
         } else if first_wp_y < r.y {
-            (center_x, r.y, "up")
+            (first_wp_x, r.y, "up")
         } else if first_wp_x >= r.x + r.w {
-            (r.x + r.w - 1, center_y, "right")
+            (r.x + r.w - 1, first_wp_y, "right")
         } else if first_wp_x < r.x {
-            (r.x, center_y, "left")
+            (r.x, first_wp_y, "left")
         } else {
             (center_x, r.y + r.h - 1, "down")
         };
 
         let existing = cget(c, stub_x, stub_y);
+        if let Some(stub) = heavy_border_stub(&existing, arm_dir) {
+            cset(c, stub_x, stub_y, stub.to_string());
+            continue;
+        }
         let ea = canvas::arms_from_char(existing);
         if ea.valid {
             let mut merged = ea.clone();
@@ -1353,6 +2963,20 @@ fn paint_exit_stubs_ir(c: &mut canvas::Canvas, ir: &LayoutIR) {
     }
 }
 
+/// Light-branch tee on a heavy (emphasis) border side, e.g. `━` + down → `┯`.
+fn heavy_border_stub(existing: &str, arm_dir: &str) -> Option<&'static str> {
+    match (existing, arm_dir) {
+        ("━", "down") => Some("┯"),
+        ("━", "up") => Some("┷"),
+        ("┃", "right") => Some("┠"),
+        ("┃", "left") => Some("┨"),
+        _ => None,
+    }
+}
+
+/// Swap x/y in place. Only ever applied to the lists the current render
+/// just built: layouts are never cached or shared between renders, so a
+/// second LR render cannot transpose the first one's coordinates again.
 fn transpose_layout(nodes: &graph::NodeLayoutList, edges: &graph::EdgeRouteList) {
     for n in nodes.borrow_mut().iter_mut() {
         std::mem::swap(&mut n.x, &mut n.y);
@@ -1365,11 +2989,15 @@ fn transpose_layout(nodes: &graph::NodeLayoutList, edges: &graph::EdgeRouteList)
     }
 }
 
-fn flip_vertical(s: &str) -> String {
+/// One line of a BT drawing: vertical glyphs mirrored (the caller reverses
+/// the line order).
+fn flip_vertical_line(line: &str) -> String {
     let remap = |c: char| -> char {
         match c {
             '▼' => '▲',
             '▲' => '▼',
+            '↓' => '↑',
+            '↑' => '↓',
             'v' => '^',
             '^' => 'v',
             '┌' => '└',
@@ -1382,23 +3010,27 @@ fn flip_vertical(s: &str) -> String {
             '╯' => '╮',
             '┬' => '┴',
             '┴' => '┬',
+            '┏' => '┗',
+            '┗' => '┏',
+            '┓' => '┛',
+            '┛' => '┓',
+            '┯' => '┷',
+            '┷' => '┯',
             other => other,
         }
     };
-    let lines: Vec<&str> = s.trim_end_matches('\n').split('\n').collect();
-    let flipped: Vec<String> = lines
-        .iter()
-        .rev()
-        .map(|line| line.chars().map(remap).collect::<String>())
-        .collect();
-    flipped.join("\n") + "\n"
+    line.chars().map(remap).collect()
 }
 
-fn flip_horizontal(s: &str) -> String {
+/// One line of an RL drawing: padded to `width`, reversed, glyphs mirrored,
+/// and with trailing blanks trimmed when `trim` is set.
+fn flip_horizontal_line(line: &str, width: usize, trim: bool) -> String {
     let remap = |c: char| -> char {
         match c {
             '►' => '◄',
             '◄' => '►',
+            '→' => '←',
+            '←' => '→',
             '>' => '<',
             '<' => '>',
             '┌' => '┐',
@@ -1411,31 +3043,37 @@ fn flip_horizontal(s: &str) -> String {
             '╯' => '╰',
             '├' => '┤',
             '┤' => '├',
+            '┏' => '┓',
+            '┓' => '┏',
+            '┗' => '┛',
+            '┛' => '┗',
+            '┠' => '┨',
+            '┨' => '┠',
             other => other,
         }
     };
-    let lines: Vec<&str> = s.trim_end_matches('\n').split('\n').collect();
-    let max_w = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
-    let flipped: Vec<String> = lines
-        .iter()
-        .map(|line| {
-            let mut chars: Vec<char> = line.chars().collect();
-            while chars.len() < max_w {
-                chars.push(' ');
-            }
-            chars.reverse();
-            let remapped: String = chars.into_iter().map(remap).collect();
-            remapped.trim_end().to_string()
-        })
-        .collect();
-    flipped.join("\n") + "\n"
+    let mut chars: Vec<char> = line.chars().collect();
+    while chars.len() < width {
+        chars.push(' ');
+    }
+    chars.reverse();
+    let remapped: String = chars.into_iter().map(remap).collect();
+    if trim {
+        remapped.trim_end().to_string()
+    } else {
+        remapped
+    }
 }
 
 // ── Compound node (subgraph collapse/expand) ───────────────────────────────
 
 const COMPOUND_PREFIX: &str = "__sg_";
+
+/// The source id behind a layout node id: compound nodes drop their prefix.
+fn source_id(id: &str) -> &str {
+    id.strip_prefix(COMPOUND_PREFIX).unwrap_or(id)
+}
 const SG_INNER_GAP: i32 = 1;
-const SG_PAD_X: i32 = 1;
 
 struct CompoundInfo {
     sg_name: String,
@@ -1446,36 +3084,354 @@ struct CompoundInfo {
     max_member_height: i32,
     member_labels: Vec<String>,
     member_shapes: Vec<String>,
+    /// Members stacked top-to-bottom (true) or side by side (false), in visual space.
+    stack_vertical: bool,
+    /// Members placed in reverse declaration order (RL/BT subgraphs).
+    reverse: bool,
+    /// Nesting depth of the subgraph (0 for top-level).
+    depth: usize,
+    /// Blank `(columns, rows)` around the members (`subgraph_margin_x/y`).
+    margin: (i32, i32),
+    /// Rows the title takes below the top border: 1 inside, 0 on the border.
+    title_rows: i32,
 }
 
-/// Collect subgraph member lists from parsed AST.
-fn collect_subgraph_members(parsed: &parser::Graph) -> Vec<(String, Vec<String>)> {
-    fn collect_sg(sg: &parser::Subgraph, out: &mut Vec<(String, Vec<String>)>) {
+/// Collect subgraph member lists from parsed AST, with each subgraph's
+/// nesting depth (0 for top-level subgraphs).
+fn collect_subgraph_members(parsed: &parser::Graph) -> Vec<(String, Vec<String>, usize)> {
+    fn collect_sg(
+        sg: &parser::Subgraph,
+        depth: usize,
+        out: &mut Vec<(String, Vec<String>, usize)>,
+    ) {
         if !sg.name.is_empty() {
             let ids: Vec<String> = sg.nodes.iter().map(|n| n.id.clone()).collect();
-            out.push((sg.name.clone(), ids));
+            out.push((sg.name.clone(), ids, depth));
         }
         for nested in &sg.subgraphs {
-            collect_sg(nested, out);
+            collect_sg(nested, depth + 1, out);
         }
     }
     let mut result = Vec::new();
+    for sg in &parsed.subgraphs {
+        collect_sg(sg, 0, &mut result);
+    }
+    result
+}
+
+/// Collect explicit `direction XY` declarations, keyed by subgraph name.
+fn collect_subgraph_directions(parsed: &parser::Graph) -> HashMap<String, parser::Direction> {
+    fn collect_sg(sg: &parser::Subgraph, out: &mut HashMap<String, parser::Direction>) {
+        if sg.has_direction {
+            out.insert(sg.name.clone(), sg.direction.clone());
+        }
+        for nested in &sg.subgraphs {
+            collect_sg(nested, out);
+        }
+    }
+    let mut result = HashMap::new();
     for sg in &parsed.subgraphs {
         collect_sg(sg, &mut result);
     }
     result
 }
 
-/// Collapse subgraph members into compound nodes for layout.
+/// Swimlanes: a TD diagram with two or more top-level subgraphs that all
+/// declare `direction LR` and between them hold every node. Returns their
+/// compound ids in source order, or nothing when the diagram doesn't match.
+fn swimlane_ids(parsed: &parser::Graph, direction: &str) -> Vec<String> {
+    fn members(sg: &parser::Subgraph, out: &mut HashSet<String>) {
+        out.extend(sg.nodes.iter().map(|n| n.id.clone()));
+        for nested in &sg.subgraphs {
+            members(nested, out);
+        }
+    }
+    let lanes = &parsed.subgraphs;
+    let all_lr = lanes
+        .iter()
+        .all(|sg| sg.has_direction && matches!(sg.direction, parser::Direction::LR));
+    if direction != "TD" || lanes.len() < 2 || !all_lr {
+        return Vec::new();
+    }
+    let mut in_lanes = HashSet::new();
+    for sg in lanes {
+        members(sg, &mut in_lanes);
+    }
+    if !parsed.nodes.iter().all(|n| in_lanes.contains(&n.id)) {
+        return Vec::new();
+    }
+    lanes
+        .iter()
+        .map(|sg| format!("{COMPOUND_PREFIX}{}", sg.name))
+        .collect()
+}
+
+/// Stretch every lane to the span of all of them so they stack as
+/// full-width bands. Members keep their offsets from the left border.
+fn widen_swimlanes(nodes: &graph::NodeLayoutList, lanes: &[String]) {
+    let mut nodes = nodes.borrow_mut();
+    let spans: Vec<(i32, i32)> = nodes
+        .iter()
+        .filter(|n| lanes.contains(&n.id))
+        .map(|n| (n.x, n.x + n.width))
+        .collect();
+    let (Some(left), Some(right)) = (
+        spans.iter().map(|s| s.0).min(),
+        spans.iter().map(|s| s.1).max(),
+    ) else {
+        return;
+    };
+    for n in nodes.iter_mut().filter(|n| lanes.contains(&n.id)) {
+        n.x = left;
+        n.width = right - left;
+    }
+}
+
+/// Decide how a subgraph's members are arranged in visual space: returns
+/// `(stack_vertical, reverse)`.
+///
+/// An explicit `direction` lays members out along that axis; without one they
+/// sit side by side across the graph's flow. The RL/BT mirror applied to the
+/// whole drawing is pre-compensated so an explicit direction still reads right.
+fn member_arrangement(sg_dir: Option<&parser::Direction>, direction: &str) -> (bool, bool) {
+    let (stack_vertical, reverse) = match sg_dir {
+        Some(parser::Direction::LR) => (false, false),
+        Some(parser::Direction::RL) => (false, true),
+        Some(parser::Direction::TD) => (true, false),
+        Some(parser::Direction::BT) => (true, true),
+        None => return (direction == "LR" || direction == "RL", false),
+    };
+    let mirrored = if stack_vertical {
+        direction == "BT"
+    } else {
+        direction == "RL"
+    };
+    (stack_vertical, reverse != mirrored)
+}
+
+/// Reorder the container rects in place so borders paint outermost-first:
+/// shallower subgraphs before nested ones, larger boxes first within a depth.
+/// Inner borders and titles then land on top of the outer ones. Other rects
+/// keep their slots.
+fn order_containers_outermost_first(rects: &mut [LayoutRect], compounds: &[CompoundInfo]) {
+    let depth: HashMap<&str, usize> = compounds
+        .iter()
+        .map(|c| (c.sg_name.as_str(), c.depth))
+        .collect();
+    let slots: Vec<usize> = (0..rects.len())
+        .filter(|&i| rects[i].shape == "Container")
+        .collect();
+    let mut containers: Vec<LayoutRect> = slots.iter().map(|&i| rects[i].clone()).collect();
+    containers.sort_by_key(|r| {
+        let d = depth.get(r.label.as_str()).copied().unwrap_or(0);
+        (d, std::cmp::Reverse(r.w * r.h))
+    });
+    for (slot, rect) in slots.into_iter().zip(containers) {
+        rects[slot] = rect;
+    }
+}
+
+/// Shortest progress bar, so small percentages still show a filled cell.
+const PROGRESS_BAR_MIN_WIDTH: i32 = 10;
+
+/// Rewrite every `Task|60%` label as `Task` over a bar row that is 60%
+/// filled (`█`/`░`, or `#`/`.` in ASCII). The bar is as wide as the text, or
+/// [`PROGRESS_BAR_MIN_WIDTH`]; percentages above 100 are capped. Anything but
+/// one to three digits before the `%` (`-50%`, `2.5%`) leaves the label as written.
+fn progress_bar_labels(ast: &mut parser::Graph, charset: &CharSet) {
+    let (full, empty) = match charset {
+        CharSet::Ascii => ("#", "."),
+        _ => ("█", "░"),
+    };
+    map_labels(ast, |nodes, _| {
+        for node in nodes {
+            let Some((text, pct)) = node.label.rsplit_once('|') else {
+                continue;
+            };
+            let Some(Ok(pct)) = pct
+                .strip_suffix('%')
+                .filter(|d| (1..=3).contains(&d.len()) && d.bytes().all(|b| b.is_ascii_digit()))
+                .map(str::parse::<i32>)
+            else {
+                continue;
+            };
+            let text = text.trim_end();
+            let width = text
+                .lines()
+                .map(display_width)
+                .max()
+                .unwrap_or(0)
+                .max(PROGRESS_BAR_MIN_WIDTH);
+            let filled = (pct.min(100) * width + 50) / 100;
+            node.label = format!(
+                "{text}\n{}{}",
+                full.repeat(filled as usize),
+                empty.repeat((width - filled) as usize)
+            );
+        }
+    });
+}
+
+/// Relabel every node as a marker followed by its id (`●A`, or `oA` in
+/// ASCII), the whole of what skeleton mode draws for a node. Shapes become
+/// rectangles so edges end beside the row rather than on a diamond vertex.
+fn skeleton_labels(ast: &mut parser::Graph, charset: &CharSet) {
+    let marker = match charset {
+        CharSet::Ascii => "o",
+        _ => "●",
+    };
+    map_labels(ast, |nodes, _| {
+        for node in nodes {
+            node.label = format!("{marker}{}", node.id);
+            node.shape = parser::NodeShape::Rectangle;
+        }
+    });
+}
+
+/// show_attrs: append a badge row to each label naming the node's classes
+/// (`«service»`, `<<service>>` in ASCII). The built-in emphasis class is
+/// styling, not metadata, and gets no badge.
+fn add_attr_badges(
+    g: &mut graph::Graph,
+    classes: &HashMap<String, Vec<String>>,
+    charset: &CharSet,
+) {
+    let (open, close) = match charset {
+        CharSet::Ascii => ("<<", ">>"),
+        _ => ("«", "»"),
+    };
+    for nd in g.digraph.node_weights_mut() {
+        let Some(names) = classes.get(&nd.id) else {
+            continue;
+        };
+        let badges: Vec<String> = names
+            .iter()
+            .filter(|name| name.as_str() != EMPHASIS_CLASS)
+            .map(|name| format!("{open}{name}{close}"))
+            .collect();
+        if !badges.is_empty() {
+            nd.label = format!("{}\n{}", nd.label, badges.join(" "));
+        }
+    }
+}
+
+/// decode_entities: `&amp;`, `&lt;`, `&gt;`, `&quot;`, `&apos;` and numeric
+/// `&#39;` / `&#x27;` references become the characters they name. Anything
+/// else, including unknown names and invalid code points, is kept as written.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..].find(';').and_then(|end| {
+            let name = &rest[1..end + 1];
+            let ch = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => {
+                    let code =
+                        if let Some(hex) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                            u32::from_str_radix(hex, 16).ok()
+                        } else {
+                            name.strip_prefix('#').and_then(|dec| dec.parse().ok())
+                        };
+                    code.and_then(char::from_u32)
+                }
+            };
+            ch.map(|ch| (ch, end + 2))
+        });
+        match decoded {
+            Some((ch, len)) => {
+                out.push(ch);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// decode_entities: decode HTML entities in every node and edge label.
+fn decode_entity_labels(ast: &mut parser::Graph) {
+    map_labels(ast, |nodes, edges| {
+        for node in nodes {
+            node.label = decode_entities(&node.label);
+        }
+        for edge in edges {
+            edge.label = decode_entities(&edge.label);
+        }
+    });
+}
+
+/// Call `f` with the nodes and edges declared at each level of `ast`: the
+/// top level, then every subgraph, depth first.
+fn map_labels(
+    ast: &mut parser::Graph,
+    mut f: impl FnMut(&mut [parser::Node], &mut [parser::Edge]),
+) {
+    fn visit(
+        sg: &mut parser::Subgraph,
+        f: &mut impl FnMut(&mut [parser::Node], &mut [parser::Edge]),
+    ) {
+        f(&mut sg.nodes, &mut sg.edges);
+        for nested in &mut sg.subgraphs {
+            visit(nested, f);
+        }
+    }
+    f(&mut ast.nodes, &mut ast.edges);
+    for sg in &mut ast.subgraphs {
+        visit(sg, &mut f);
+    }
+}
+
+/// The label rewrites `config` turns on, applied to a copy of `parsed`:
+/// decode_entities first, then skeleton or else progress_bars. `None` when
+/// none of them is on.
+fn relabel_ast(parsed: &parser::Graph, config: &RenderConfig) -> Option<parser::Graph> {
+    if !(config.decode_entities || config.skeleton || config.progress_bars) {
+        return None;
+    }
+    let mut ast = parsed.clone();
+    if config.decode_entities {
+        decode_entity_labels(&mut ast);
+    }
+    if config.skeleton {
+        skeleton_labels(&mut ast, &config.charset);
+    } else if config.progress_bars {
+        progress_bar_labels(&mut ast, &config.charset);
+    }
+    Some(ast)
+}
+
+/// Collapse subgraph members into compound nodes for layout.
 fn collapse_subgraphs(
     g: &graph::Graph,
-    subgraph_members: &[(String, Vec<String>)],
-    padding: i32,
-) -> (graph::Graph, Vec<CompoundInfo>) {
+    subgraph_members: &[(String, Vec<String>, usize)],
+    subgraph_directions: &HashMap<String, parser::Direction>,
+    config: &RenderConfig,
+    direction: &str,
+) -> (graph::Graph, Vec<CompoundInfo>, MemberEdges) {
+    let padding = config.padding;
+    let is_lr_or_rl = direction == "LR" || direction == "RL";
+    let member_width = |w: i32| {
+        if config.odd_widths && !is_lr_or_rl {
+            round_up_odd(w)
+        } else {
+            w
+        }
+    };
     let mut member_to_sg: HashMap<String, String> = HashMap::new();
     let mut compounds: Vec<CompoundInfo> = Vec::new();
 
-    for (sg_name, members) in subgraph_members {
+    for (sg_name, members, depth) in subgraph_members {
         let compound_id = format!("{}{}", COMPOUND_PREFIX, sg_name);
         let mut member_widths = Vec::new();
         let mut member_heights = Vec::new();
@@ -1485,14 +3441,19 @@ fn collapse_subgraphs(
         for mid in members {
             if let Some(&idx) = g.node_index.get(mid.as_str()) {
                 let nd = &g.digraph[idx];
-                let max_line_w = nd.label.lines().map(|l| l.len()).max().unwrap_or(0) as i32;
-                let line_count = std::cmp::max(nd.label.lines().count() as i32, 1);
-                member_widths.push(max_line_w + 2 + 2 * padding);
-                member_heights.push(2 + line_count);
+                if config.skeleton {
+                    let max_line_w = nd.label.lines().map(display_width).max().unwrap_or(0);
+                    member_widths.push(member_width(max_line_w));
+                    member_heights.push(1);
+                } else {
+                    let (w, h) = node_dims(&nd.label, &nd.shape, 0, config, is_lr_or_rl);
+                    member_widths.push(w);
+                    member_heights.push(h);
+                }
                 member_labels.push(nd.label.clone());
                 member_shapes.push(nd.shape.clone());
             } else {
-                member_widths.push(3 + 2 * padding);
+                member_widths.push(member_width(3 + 2 * padding));
                 member_heights.push(3);
                 member_labels.push(mid.clone());
                 member_shapes.push("Rectangle".to_string());
@@ -1501,6 +3462,8 @@ fn collapse_subgraphs(
         }
 
         let max_member_height = member_heights.iter().max().copied().unwrap_or(3);
+        let (stack_vertical, reverse) =
+            member_arrangement(subgraph_directions.get(sg_name), direction);
 
         compounds.push(CompoundInfo {
             sg_name: sg_name.clone(),
@@ -1511,6 +3474,14 @@ fn collapse_subgraphs(
             max_member_height,
             member_labels,
             member_shapes,
+            stack_vertical,
+            reverse,
+            depth: *depth,
+            margin: (
+                config.subgraph_margin_x.max(0),
+                config.subgraph_margin_y.max(0),
+            ),
+            title_rows: title_rows(config),
         });
     }
 
@@ -1562,6 +3533,7 @@ fn collapse_subgraphs(
 
     // Remap edges
     let mut added_edges: HashSet<(String, String)> = HashSet::new();
+    let mut member_edges: MemberEdges = HashMap::new();
     for edge_idx in g.digraph.edge_indices() {
         let (src_idx, tgt_idx) = g.digraph.edge_endpoints(edge_idx).unwrap();
         let src_id = &g.digraph[src_idx].id;
@@ -1575,46 +3547,233 @@ fn collapse_subgraphs(
             continue;
         }
         let key = (actual_src.clone(), actual_tgt.clone());
+        let member = |id: &String| member_to_sg.contains_key(id.as_str()).then(|| id.clone());
+        let variants = member_edges.entry(key.clone()).or_default();
+        let variant = MemberEdge {
+            from_member: member(src_id),
+            to_member: member(tgt_id),
+            edge_type: ed.edge_type.clone(),
+            label: ed.label.clone().unwrap_or_default(),
+            classes: ed.classes.clone(),
+        };
+        if !variants
+            .iter()
+            .any(|v| v.from_member == variant.from_member && v.to_member == variant.to_member)
+        {
+            variants.push(variant);
+        }
         if added_edges.contains(&key) {
             continue;
         }
         added_edges.insert(key);
-        graph::graph_add_edge(
-            &mut collapsed,
-            &actual_src,
-            &actual_tgt,
-            &ed.edge_type,
-            ed.label.as_deref(),
-        );
+        graph::graph_add_edge_data(&mut collapsed, &actual_src, &actual_tgt, ed.clone());
+    }
+
+    member_edges.retain(|_, variants| {
+        variants
+            .iter()
+            .any(|v| v.from_member.is_some() || v.to_member.is_some())
+    });
+    (collapsed, compounds, member_edges)
+}
+
+/// One original edge behind a collapsed compound-to-node edge, naming the
+/// subgraph members it really starts or ends at.
+struct MemberEdge {
+    from_member: Option<String>,
+    to_member: Option<String>,
+    edge_type: String,
+    label: String,
+    classes: Vec<String>,
+}
+
+/// Collapsed `(from, to)` pairs that stand for at least one member edge.
+type MemberEdges = HashMap<(String, String), Vec<MemberEdge>>;
+
+/// Re-attach edges that really start or end at a subgraph member: each
+/// original edge behind a collapsed pair gets its own copy of the route,
+/// extended across the container border to the member's box. A member whose
+/// path would cross another member keeps the container-border attachment.
+/// Works in TD layout space, before any LR/RL transpose.
+fn attach_member_edges(
+    routed: &graph::EdgeRouteList,
+    nodes: &graph::NodeLayoutList,
+    compounds: &[CompoundInfo],
+    member_edges: &MemberEdges,
+) -> graph::EdgeRouteList {
+    // `(x, y, w, h)` of every laid-out node, keyed by id
+    let rects: HashMap<String, (i32, i32, i32, i32)> = (0..graph::nll_len(nodes.clone()))
+        .map(|i| {
+            let rect = (
+                graph::nll_get_x(nodes.clone(), i),
+                graph::nll_get_y(nodes.clone(), i),
+                graph::nll_get_width(nodes.clone(), i),
+                graph::nll_get_height(nodes.clone(), i),
+            );
+            (graph::nll_get_id(nodes.clone(), i), rect)
+        })
+        .collect();
+    let rect_of = |id: &str| rects.get(id).copied();
+    let sg_of_member: HashMap<&str, &CompoundInfo> = compounds
+        .iter()
+        .flat_map(|ci| ci.member_ids.iter().map(move |m| (m.as_str(), ci)))
+        .collect();
+
+    // Column and row where a path from `outside_y` (a row just past the
+    // container border) meets `member`: its centre, on the row beside its
+    // border facing that side. None if another member or a subgraph title
+    // is in the way.
+    let attach = |member: &str, outside_y: i32| -> Option<(i32, i32)> {
+        sg_of_member.get(member)?;
+        let (mx, my, mw, mh) = rect_of(member)?;
+        let col = mx + mw / 2;
+        let (lo, hi, row) = if outside_y < my {
+            (outside_y, my, my - 1)
+        } else {
+            (my + mh - 1, outside_y, my + mh)
+        };
+        let member_in_way = sg_of_member.keys().filter(|&&o| o != member).any(|o| {
+            rect_of(o).is_some_and(|(ox, oy, ow, oh)| {
+                col >= ox && col < ox + ow && oy < hi && oy + oh > lo
+            })
+        });
+        let title_in_way = compounds.iter().any(|ci| {
+            rect_of(&ci.compound_id).is_some_and(|(cx, cy, cw, _)| {
+                let title_w = display_width(&ci.sg_name);
+                let title_col = cx + 1 + (cw - 2 - title_w).max(0) / 2;
+                let title_row = cy + ci.title_rows;
+                title_row > lo && title_row < hi && col >= title_col && col < title_col + title_w
+            })
+        });
+        (!member_in_way && !title_in_way).then_some((col, row))
+    };
+
+    let result = graph::erl_new();
+    for i in 0..graph::erl_len(routed.clone()) {
+        let from = graph::erl_get_from(routed.clone(), i);
+        let to = graph::erl_get_to(routed.clone(), i);
+        let waypoints: Vec<(i32, i32)> = (0..graph::erl_get_waypoint_count(routed.clone(), i))
+            .map(|j| {
+                (
+                    graph::erl_get_waypoint_x(routed.clone(), i, j),
+                    graph::erl_get_waypoint_y(routed.clone(), i, j),
+                )
+            })
+            .collect();
+        let Some(variants) = member_edges.get(&(from.clone(), to.clone())) else {
+            graph::erl_push(
+                result.clone(),
+                from,
+                to,
+                graph::erl_get_label(routed.clone(), i),
+                graph::erl_get_etype(routed.clone(), i),
+                waypoints,
+            );
+            let classes = graph::erl_get_classes(routed.clone(), i);
+            graph::erl_set_classes(result.clone(), graph::erl_len(result.clone()) - 1, classes);
+            continue;
+        };
+        for v in variants {
+            let mut wps = waypoints.clone();
+            if let (Some(m), Some(&(sx, sy))) = (&v.from_member, wps.first()) {
+                if let Some((col, row)) = attach(m, sy) {
+                    wps.splice(0..0, [(col, row), (col, sy)]);
+                }
+            }
+            if let (Some(m), Some(&(tx, ty))) = (&v.to_member, wps.last()) {
+                if let Some((col, row)) = attach(m, ty) {
+                    wps.extend([(col, ty), (col, row)]);
+                }
+            }
+            wps.dedup();
+            graph::erl_push(
+                result.clone(),
+                v.from_member.clone().unwrap_or_else(|| from.clone()),
+                v.to_member.clone().unwrap_or_else(|| to.clone()),
+                v.label.clone(),
+                v.edge_type.clone(),
+                wps,
+            );
+            let last = graph::erl_len(result.clone()) - 1;
+            graph::erl_set_classes(result.clone(), last, v.classes.clone());
+        }
     }
+    result
+}
 
-    (collapsed, compounds)
+/// Visual size of a compound node and each member's offset from its top-left corner.
+fn compound_geometry(ci: &CompoundInfo) -> ((i32, i32), Vec<(i32, i32)>) {
+    let n = ci.member_ids.len();
+    let gaps = if n > 1 {
+        (n as i32 - 1) * SG_INNER_GAP
+    } else {
+        0
+    };
+    let (content_w, content_h) = if n == 0 {
+        // An empty group keeps one member's height of blank rows either way
+        (0, ci.max_member_height)
+    } else if ci.stack_vertical {
+        let max_w = ci.member_widths.iter().max().copied().unwrap_or(0);
+        (max_w, ci.member_heights.iter().sum::<i32>() + gaps)
+    } else {
+        (
+            ci.member_widths.iter().sum::<i32>() + gaps,
+            ci.max_member_height,
+        )
+    };
+    let title_w = display_width(&ci.sg_name) + 4;
+    let inner_w = std::cmp::max(content_w, title_w);
+    let (margin_x, margin_y) = ci.margin;
+    let width = 2 + 2 * margin_x + inner_w;
+    // borders + title row + margins above and below the member content
+    let height = 2 + ci.title_rows + 2 * margin_y + content_h;
+
+    let order: Vec<usize> = if ci.reverse {
+        (0..n).rev().collect()
+    } else {
+        (0..n).collect()
+    };
+    let mut offsets = vec![(0, 0); n];
+    // A side-by-side row narrower than the title is centred under it.
+    let mut cursor = if ci.stack_vertical {
+        0
+    } else {
+        (inner_w - content_w) / 2
+    };
+    for j in order {
+        if ci.stack_vertical {
+            let centered = (inner_w - ci.member_widths[j]) / 2;
+            offsets[j] = (
+                1 + margin_x + centered,
+                1 + ci.title_rows + margin_y + cursor,
+            );
+            cursor += ci.member_heights[j] + SG_INNER_GAP;
+        } else {
+            offsets[j] = (1 + margin_x + cursor, 1 + ci.title_rows + margin_y);
+            cursor += ci.member_widths[j] + SG_INNER_GAP;
+        }
+    }
+    ((width, height), offsets)
 }
 
 /// Compute width/height overrides for compound nodes.
 fn compute_compound_dimensions(compounds: &[CompoundInfo]) -> HashMap<String, (i32, i32)> {
     let mut overrides = HashMap::new();
     for ci in compounds {
-        let total_member_w: i32 = ci.member_widths.iter().sum();
-        let gaps = if ci.member_ids.len() > 1 {
-            (ci.member_ids.len() as i32 - 1) * SG_INNER_GAP
-        } else {
-            0
-        };
-        let content_w = total_member_w + gaps;
-        let title_w = ci.sg_name.len() as i32 + 4;
-        let inner_w = std::cmp::max(content_w, title_w);
-        let width = 2 + 2 * SG_PAD_X + inner_w;
-        let height = 2 + 1 + ci.max_member_height; // border top + title row + member height
-        overrides.insert(ci.compound_id.clone(), (width, height));
+        let (size, _) = compound_geometry(ci);
+        overrides.insert(ci.compound_id.clone(), size);
     }
     overrides
 }
 
 /// Expand compound nodes: place member nodes inside compound bounds.
+///
+/// For LR/RL the layout space is transposed later, so member offsets and
+/// sizes are swapped here to land in the right visual position.
 fn expand_compound_nodes(
     nodes: &graph::NodeLayoutList,
     compounds: &[CompoundInfo],
+    is_lr_or_rl: bool,
 ) -> graph::NodeLayoutList {
     let compound_map: HashMap<String, &CompoundInfo> = compounds
         .iter()
@@ -1648,22 +3807,27 @@ fn expand_compound_nodes(
         );
 
         if let Some(ci) = compound_map.get(&id) {
-            let mut member_x = x + 1 + SG_PAD_X;
-            let member_y = y + 2; // below border + title row
+            let (_, offsets) = compound_geometry(ci);
             for (j, mid) in ci.member_ids.iter().enumerate() {
+                let (ox, oy) = offsets[j];
+                let (mw, mh) = (ci.member_widths[j], ci.member_heights[j]);
+                let (mx, my, lw, lh) = if is_lr_or_rl {
+                    (x + oy, y + ox, mh, mw)
+                } else {
+                    (x + ox, y + oy, mw, mh)
+                };
                 graph::nll_push(
                     result.clone(),
                     mid.clone(),
                     layer,
                     i,
-                    member_x,
-                    member_y,
-                    ci.member_widths[j],
-                    ci.member_heights[j],
+                    mx,
+                    my,
+                    lw,
+                    lh,
                     ci.member_labels[j].clone(),
                     ci.member_shapes[j].clone(),
                 );
-                member_x += ci.member_widths[j] + SG_INNER_GAP;
             }
         }
     }
@@ -1671,286 +3835,3722 @@ fn expand_compound_nodes(
     result
 }
 
-/// Paint a compound (subgraph container) node: border + centered title.
-fn paint_compound_node(c: &mut canvas::Canvas, x: i32, y: i32, w: i32, h: i32, sg_name: &str) {
+/// Rows a subgraph title takes below the top border for `subgraph_title`.
+fn title_rows(config: &RenderConfig) -> i32 {
+    match config.subgraph_title {
+        TitlePos::Inside => 1,
+        TitlePos::OnBorder => 0,
+    }
+}
+
+/// Paint a compound (subgraph container) node: border + centered title,
+/// either on the row below the top border or set into the border itself
+/// between single blanks (`┌─ Group ─┐`).
+fn paint_compound_node(c: &mut canvas::Canvas, r: &LayoutRect, title_pos: &TitlePos) {
     let cs = c.charset.clone();
     let bc = canvas::box_chars_for_charset(cs);
-    cdraw_box(c, x, y, w, h, &bc);
+    cdraw_box(c, r.x, r.y, r.w, r.h, &bc);
+
+    let title_w = display_width(&r.label);
+    let inner_w = std::cmp::max(0, r.w - 2);
+    let title_pad = std::cmp::max(0, inner_w - title_w) / 2;
+    let title_col = r.x + 1 + title_pad;
+    match title_pos {
+        TitlePos::Inside => cwrite_str(c, title_col, r.y + 1, &r.label),
+        TitlePos::OnBorder => {
+            cset(c, title_col - 1, r.y, " ".to_string());
+            cwrite_str(c, title_col, r.y, &r.label);
+            cset(c, title_col + title_w, r.y, " ".to_string());
+        }
+    }
+}
 
-    let inner_w = std::cmp::max(0, w - 2);
-    let title_pad = std::cmp::max(0, inner_w - sg_name.len() as i32) / 2;
-    let title_col = x + 1 + title_pad;
-    let title_row = y + 1;
-    cwrite_str(c, title_col, title_row, sg_name);
+/// Warn about nodes with no edges at all, outside any subgraph — usually a
+/// typo in an edge endpoint. A diagram with a single node is never flagged.
+fn orphan_warnings(parsed: &parser::Graph) -> Vec<String> {
+    let g = ast_to_graph(parsed);
+    let nodes = graph::graph_nodes(&g);
+    if nodes.len() < 2 {
+        return Vec::new();
+    }
+    let members: HashSet<String> = collect_subgraph_members(parsed)
+        .into_iter()
+        .flat_map(|(_, ids, _)| ids)
+        .collect();
+    nodes
+        .into_iter()
+        .filter(|id| {
+            !members.contains(id)
+                && graph::graph_in_degree(&g, id) == 0
+                && graph::graph_out_degree(&g, id) == 0
+        })
+        .map(|id| format!("orphan node '{id}' has no edges; check for a typo in an edge"))
+        .collect()
 }
 
-// ── Public API ──────────────────────────────────────────────────────────────
+// ── Layout pins ─────────────────────────────────────────────────────────────
 
-/// Parse a Mermaid flowchart string and render it to ASCII/Unicode art.
-pub fn render_dsl(
-    src: &str,
-    unicode: bool,
-    padding: usize,
-    _direction: Option<&str>,
-) -> Result<String, String> {
-    // Phase 0: Parse
-    let parsed = rust_parser::parse_flowchart(src);
-    if parsed.nodes.is_empty() && parsed.edges.is_empty() && parsed.subgraphs.is_empty() {
-        return Ok(String::new());
-    }
+/// `(layer, order)` a node is pinned to, keyed by node id.
+type NodePins = HashMap<String, (i32, i32)>;
 
-    let parsed_direction = match parsed.direction {
-        parser::Direction::LR => "LR",
-        parser::Direction::RL => "RL",
-        parser::Direction::BT => "BT",
-        _ => "TD",
-    };
-    let direction = _direction.unwrap_or(parsed_direction);
+/// The parsed `%% pos A 3 0` directives: node `A` goes to layer 3 (counted
+/// from the top, or left in LR) at position 0 within it. Being comments,
+/// they are invisible to Mermaid itself. A later pin for the same node wins.
+fn node_pins(parsed: &parser::Graph) -> NodePins {
+    (parsed.pins.iter())
+        .map(|pin| (pin.id.clone(), (pin.layer, pin.order)))
+        .collect()
+}
 
-    let ir = run_layout_pipeline(&parsed, padding, direction);
+/// Column an edge's middle run is pinned to, keyed by `(from, to)` ids.
+type EdgeColumns = HashMap<(String, String), i32>;
+
+/// The parsed `%% route A B col=10` directives: the edge from `A` to `B`
+/// runs between the layers down column 10 (a row in LR/RL, counted from the
+/// left of the layout before any BT/RL flip) instead of where the router
+/// would put it. A later one for the same edge wins.
+fn edge_columns(parsed: &parser::Graph) -> EdgeColumns {
+    (parsed.routes.iter())
+        .map(|route| ((route.from_id.clone(), route.to_id.clone()), route.col))
+        .collect()
+}
 
-    // 1:1 IR → canvas (no logic, just draw primitives)
-    let cs = if unicode {
-        canvas::CharSet::Unicode
-    } else {
-        canvas::CharSet::Ascii
-    };
+/// Every `%%` layout directive of a diagram.
+#[derive(Clone, Debug, Default)]
+struct LayoutPins {
+    nodes: NodePins,
+    columns: EdgeColumns,
+}
 
-    let mut max_col: i32 = 40;
-    let mut max_row: i32 = 10;
-    for r in &ir.rects {
-        max_col = max_col.max(r.x + r.w + 2);
-        max_row = max_row.max(r.y + r.h + 4);
+fn layout_pins(parsed: &parser::Graph) -> LayoutPins {
+    LayoutPins {
+        nodes: node_pins(parsed),
+        columns: edge_columns(parsed),
     }
-    for e in &ir.edges {
-        for &(wx, wy) in &e.waypoints {
-            max_col = max_col.max(wx + 4);
-            max_row = max_row.max(wy + 4);
+}
+
+/// Move pinned nodes to their layers, then push each unpinned successor
+/// below its predecessor again so every other edge still points down.
+fn apply_layer_pins(g: &graph::Graph, layers: &mut HashMap<String, i32>, pins: &NodePins) {
+    let mut pinned = false;
+    for (id, &(layer, _)) in pins {
+        if let Some(l) = layers.get_mut(id) {
+            *l = layer;
+            pinned = true;
         }
     }
+    if !pinned {
+        return;
+    }
+    push_successors_down(g, layers, |id| pins.contains_key(id));
+}
 
-    let mut c = canvas::canvas_new(max_col, max_row, cs);
-
-    // Draw containers first (behind), then nodes on top
-    for r in &ir.rects {
-        if r.shape == "Container" {
-            paint_compound_node(&mut c, r.x, r.y, r.w, r.h, &r.label);
+/// Push every successor not `fixed` below its predecessor again, in
+/// topological order, after some nodes were moved to other layers.
+fn push_successors_down(
+    g: &graph::Graph,
+    layers: &mut HashMap<String, i32>,
+    fixed: impl Fn(&str) -> bool,
+) {
+    let topo = graph::graph_topo_sort(g).unwrap_or_else(|| graph::graph_nodes(g));
+    for node in &topo {
+        let curr = layers[node];
+        for succ in graph::graph_successors(g, node) {
+            if !fixed(&succ) && layers.get(&succ).is_some_and(|&l| l <= curr) {
+                layers.insert(succ, curr + 1);
+            }
         }
     }
-    for r in &ir.rects {
-        if r.shape != "Container" {
-            paint_node(&mut c, r.x, r.y, r.w, r.h, &r.label, &r.shape);
+}
+
+/// Move the nodes of each `rank same` group down to the lowest layer any of
+/// them has, then push their successors below them. Ids that are not laid
+/// out here (unknown, or inside a collapsed subgraph) are ignored. Long
+/// edges this creates get dummies like any other.
+fn apply_same_ranks(
+    g: &graph::Graph,
+    layers: &mut HashMap<String, i32>,
+    ranks: &[Vec<String>],
+    pins: &NodePins,
+) {
+    let mut ranked: HashSet<String> = HashSet::new();
+    for group in ranks {
+        let known: Vec<&String> = group.iter().filter(|id| layers.contains_key(*id)).collect();
+        let Some(bottom) = known.iter().map(|id| layers[*id]).max() else {
+            continue;
+        };
+        if known.len() < 2 {
+            continue;
+        }
+        for id in known {
+            layers.insert(id.clone(), bottom);
+            ranked.insert(id.clone());
         }
     }
+    if !ranked.is_empty() {
+        push_successors_down(g, layers, |id| ranked.contains(id) || pins.contains_key(id));
+    }
+}
 
-    for e in &ir.edges {
-        paint_edge(&mut c, &e.waypoints, &e.edge_type, &e.label);
+/// Put the pinned nodes of one layer back at their positions (clamped to
+/// the layer's size), keeping the others in their current order.
+fn apply_order_pins(group: &mut Vec<String>, pins: &NodePins) {
+    let mut pinned: Vec<(i32, String)> = Vec::new();
+    group.retain(|id| match pins.get(id) {
+        Some(&(_, order)) => {
+            pinned.push((order, id.clone()));
+            false
+        }
+        None => true,
+    });
+    pinned.sort();
+    for (order, id) in pinned {
+        let at = (order as usize).min(group.len());
+        group.insert(at, id);
     }
+}
 
-    paint_exit_stubs_ir(&mut c, &ir);
+// ── Front matter ────────────────────────────────────────────────────────────
 
-    // Render canvas to string (implemented directly to avoid .hom codegen issues)
-    let mut rendered = {
-        let mut lines: Vec<String> = Vec::new();
-        for row in &c.cells {
-            let line: String = row.join("");
-            lines.push(line.trim_end().to_string());
-        }
-        // Trim trailing empty lines
-        while lines.last().map(|l| l.is_empty()).unwrap_or(false) {
-            lines.pop();
-        }
-        lines.join("\n") + "\n"
-    };
+/// Mermaid's default `nodeSpacing`/`rankSpacing`, in pixels. Front-matter
+/// spacings scale the default gaps by `px / MERMAID_DEFAULT_SPACING`.
+const MERMAID_DEFAULT_SPACING: f64 = 50.0;
 
-    // Direction transforms
-    if direction == "BT" {
-        rendered = flip_vertical(&rendered);
-    } else if direction == "RL" {
-        rendered = flip_horizontal(&rendered);
+/// Split a leading `---` ... `---` block off `src`.
+/// Returns the block body (if any) and the diagram source that follows it.
+fn split_front_matter(src: &str) -> (Option<&str>, &str) {
+    let trimmed = src.trim_start();
+    let Some(rest) = trimmed.strip_prefix("---") else {
+        return (None, src);
+    };
+    let Some(first_nl) = rest.find('\n') else {
+        return (None, src);
+    };
+    if !rest[..first_nl].trim().is_empty() {
+        return (None, src);
+    }
+    let block_start = first_nl + 1;
+    let mut pos = block_start;
+    while pos <= rest.len() {
+        let end = rest[pos..].find('\n').map_or(rest.len(), |i| pos + i);
+        if rest[pos..end].trim() == "---" {
+            let after = (end + 1).min(rest.len());
+            return (Some(&rest[block_start..pos]), &rest[after..]);
+        }
+        pos = end + 1;
     }
+    (None, src)
+}
 
-    Ok(rendered)
+/// Layout settings read from `config.flowchart` in the front matter (pixels).
+#[derive(Debug, Default, PartialEq)]
+struct FrontMatter {
+    node_spacing: Option<f64>,
+    rank_spacing: Option<f64>,
+    padding: Option<f64>,
 }
 
-/// Shared layout result used by both ASCII and SVG renderers.
-/// A positioned rectangle — node or container.
-#[derive(Clone, Debug)]
-pub struct LayoutRect {
-    pub x: i32,
-    pub y: i32,
-    pub w: i32,
-    pub h: i32,
-    pub label: String,
-    /// "Rectangle", "Rounded", "Diamond", "Circle", "Container"
-    pub shape: String,
+#[cfg(feature = "yaml")]
+fn parse_front_matter(block: &str) -> FrontMatter {
+    let Ok(doc) = serde_yaml::from_str::<serde_yaml::Value>(block) else {
+        return FrontMatter::default();
+    };
+    let flowchart = &doc["config"]["flowchart"];
+    FrontMatter {
+        node_spacing: flowchart["nodeSpacing"].as_f64(),
+        rank_spacing: flowchart["rankSpacing"].as_f64(),
+        padding: flowchart["padding"].as_f64(),
+    }
 }
 
-/// A routed edge with waypoints.
-#[derive(Clone, Debug)]
-pub struct LayoutEdge {
-    pub waypoints: Vec<(i32, i32)>,
-    pub edge_type: String,
-    pub label: String,
+/// Without the `yaml` feature the block is still stripped, just not read.
+#[cfg(not(feature = "yaml"))]
+fn parse_front_matter(_block: &str) -> FrontMatter {
+    FrontMatter::default()
 }
 
-/// Flat, primitive layout IR — no compound node hacks.
-/// Both ASCII and SVG renderers consume this directly.
-pub struct LayoutIR {
-    pub rects: Vec<LayoutRect>,
-    pub edges: Vec<LayoutEdge>,
+fn front_matter(src: &str) -> FrontMatter {
+    match split_front_matter(src) {
+        (Some(block), _) => parse_front_matter(block),
+        (None, _) => FrontMatter::default(),
+    }
 }
 
-/// Run the full layout pipeline (parse → graph → layout → route).
-/// Returns clean primitives: rects + edges.
-fn run_layout_pipeline(parsed: &parser::Graph, padding: usize, direction: &str) -> LayoutIR {
-    let g = ast_to_graph(parsed);
-    let is_lr_or_rl = direction == "LR" || direction == "RL";
+/// Default `(h_gap, v_gap)` for TD/BT diagrams, as drawn: four blank
+/// columns between siblings and three rows for the arrow between layers.
+const TD_DEFAULT_GAPS: (i32, i32) = (4, 3);
+
+/// Default `(h_gap, v_gap)` for LR/RL diagrams. Ranks run across the page,
+/// so arrows between them get more room (six columns rather than TD/BT's
+/// four) and siblings stacked down a rank pack to one blank row rather than
+/// three.
+const LR_DEFAULT_GAPS: (i32, i32) = (6, 1);
+
+impl FrontMatter {
+    /// Set the gaps `config` leaves unset that the front matter gives,
+    /// scaled from the direction's defaults (see [`TD_DEFAULT_GAPS`] and
+    /// [`LR_DEFAULT_GAPS`]); gaps set on `config` win, as
+    /// `direction_override` beats the header. `nodeSpacing` separates
+    /// siblings within a rank, so for LR/RL it maps to the vertical gap;
+    /// `rankSpacing` takes the other axis. Padding still at its default
+    /// takes the front matter's.
+    fn apply(&self, config: &RenderConfig, is_lr_or_rl: bool) -> RenderConfig {
+        let defaults = config_default();
+        let scale = |px: f64, default_cells: i32| {
+            ((px / MERMAID_DEFAULT_SPACING * default_cells as f64).round() as i32).max(1)
+        };
+        let ((h_px, v_px), (h_default, v_default)) = if is_lr_or_rl {
+            ((self.rank_spacing, self.node_spacing), LR_DEFAULT_GAPS)
+        } else {
+            ((self.node_spacing, self.rank_spacing), TD_DEFAULT_GAPS)
+        };
+        let mut out = config.clone();
+        out.h_gap = config.h_gap.or(h_px.map(|px| scale(px, h_default)));
+        out.v_gap = config.v_gap.or(v_px.map(|px| scale(px, v_default)));
+        // Mermaid's default label padding is 15px, which is our one cell.
+        if let (Some(px), true) = (self.padding, config.padding == defaults.padding) {
+            out.padding = (px / 15.0).round().max(0.0) as i32;
+        }
+        out
+    }
+}
 
-    let subgraph_members = collect_subgraph_members(parsed);
-    let has_subgraphs = !subgraph_members.is_empty();
+// ── AST normalisation + warnings ────────────────────────────────────────────
 
-    let (raw_nodes, raw_edges, compounds) = if has_subgraphs {
-        let (collapsed, compounds) = collapse_subgraphs(&g, &subgraph_members, padding as i32);
-        let dim_overrides = compute_compound_dimensions(&compounds);
+/// Parse `src` and normalise the AST for layout.
+/// Returns the graph plus advisory warnings; none of them stop rendering.
+/// Input nested too deeply parses to an empty graph with the error as its
+/// only warning; see [`prepare_ast_limited`] to fail instead.
+fn prepare_ast(src: &str) -> (parser::Graph, Vec<String>) {
+    prepare_ast_limited(src, config_default().max_subgraph_depth)
+        .unwrap_or_else(|e| (parser::graph_new(), vec![e.to_string()]))
+}
 
-        let (dag, reversed) = remove_cycles_rust(&collapsed);
-        let layers = assign_layers_rust(&dag);
-        let ordering = build_ordering(&dag, &layers);
-        let nodes =
-            assign_coordinates_rust(&dag, &ordering, padding as i32, is_lr_or_rl, &dim_overrides);
+/// [`prepare_ast`], failing when subgraphs nest more than `max_depth` levels.
+fn prepare_ast_limited(
+    src: &str,
+    max_depth: i32,
+) -> Result<(parser::Graph, Vec<String>), RenderError> {
+    let (_, body) = split_front_matter(src);
+    if let Some(kind) = unsupported_diagram(body) {
+        return Err(RenderError::Unsupported(kind.to_string()));
+    }
+    let (mut parsed, _) = rust_parser::parse_flowchart_with_skips(body, max_depth.max(0) as usize)?;
+    let mut warnings: Vec<String> = (parsed.unsupported.iter())
+        .map(|feature| format!("unsupported: {feature}"))
+        .collect();
+    warnings.extend(disambiguate_subgraph_names(&mut parsed));
+    Ok((parsed, warnings))
+}
 
-        let expanded = expand_compound_nodes(&nodes, &compounds);
-        let routed = route_edges_rust(&collapsed, &expanded, &reversed);
-        (expanded, routed, compounds)
-    } else {
-        let empty_overrides = HashMap::new();
-        let (dag, reversed) = remove_cycles_rust(&g);
-        let layers = assign_layers_rust(&dag);
-        let ordering = build_ordering(&dag, &layers);
-        let nodes = assign_coordinates_rust(
-            &dag,
-            &ordering,
-            padding as i32,
-            is_lr_or_rl,
-            &empty_overrides,
-        );
-        let routed = route_edges_rust(&g, &nodes, &reversed);
-        (nodes, routed, Vec::new())
-    };
+/// Header keywords of the Mermaid diagram types other than flowcharts.
+const OTHER_DIAGRAM_KINDS: &[&str] = &[
+    "sequenceDiagram",
+    "classDiagram",
+    "classDiagram-v2",
+    "stateDiagram",
+    "stateDiagram-v2",
+    "erDiagram",
+    "journey",
+    "gantt",
+    "pie",
+    "quadrantChart",
+    "requirementDiagram",
+    "gitGraph",
+    "mindmap",
+    "timeline",
+    "sankey-beta",
+    "xychart-beta",
+    "block-beta",
+    "packet-beta",
+    "architecture-beta",
+    "kanban",
+    "C4Context",
+    "C4Container",
+    "C4Component",
+    "C4Dynamic",
+    "C4Deployment",
+];
+
+/// The header keyword of `body` when it declares a diagram type this crate
+/// cannot draw. Blank lines and `%%` comments before the header are skipped.
+fn unsupported_diagram(body: &str) -> Option<&str> {
+    let header = body
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with("%%"))?;
+    let keyword = header.split_whitespace().next()?;
+    OTHER_DIAGRAM_KINDS
+        .iter()
+        .any(|&k| k == keyword)
+        .then_some(keyword)
+}
 
-    if is_lr_or_rl {
-        transpose_layout(&raw_nodes, &raw_edges);
+/// Rename repeated subgraph names (`G`, `G (2)`, ...) so each block becomes its
+/// own container instead of colliding on one compound id. Edges that refer to
+/// the bare name keep targeting the first block.
+fn disambiguate_subgraph_names(parsed: &mut parser::Graph) -> Vec<String> {
+    fn visit(
+        sgs: &mut [parser::Subgraph],
+        seen: &mut HashSet<String>,
+        renames: &mut Vec<(String, String)>,
+    ) {
+        for sg in sgs.iter_mut() {
+            if !seen.insert(sg.name.clone()) {
+                let mut n = 2;
+                while seen.contains(&format!("{} ({})", sg.name, n)) {
+                    n += 1;
+                }
+                let renamed = format!("{} ({})", sg.name, n);
+                seen.insert(renamed.clone());
+                renames.push((sg.name.clone(), renamed.clone()));
+                sg.name = renamed;
+            }
+            visit(&mut sg.subgraphs, seen, renames);
+        }
     }
 
-    // Convert to flat primitives
-    let compound_ids: HashSet<String> = compounds.iter().map(|c| c.compound_id.clone()).collect();
-    let mut rects = Vec::new();
-    let nn = graph::nll_len(raw_nodes.clone());
-    for i in 0..nn {
-        let id = graph::nll_get_id(raw_nodes.clone(), i);
-        if id.starts_with("__dummy_") {
-            continue;
+    let mut seen = HashSet::new();
+    let mut renames = Vec::new();
+    visit(&mut parsed.subgraphs, &mut seen, &mut renames);
+    renames
+        .into_iter()
+        .map(|(name, renamed)| {
+            format!("duplicate subgraph name '{name}'; later block renamed to '{renamed}'")
+        })
+        .collect()
+}
+
+/// Header direction as the string the layout pipeline expects.
+fn direction_str(direction: &parser::Direction) -> &'static str {
+    direction.as_str()
+}
+
+/// Quarter turns follow the cycle TD → LR → BT → RL → TD.
+impl Direction {
+    /// The name used in `graph TD` and `direction_override`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::LR => "LR",
+            Direction::RL => "RL",
+            Direction::BT => "BT",
+            Direction::TD => "TD",
         }
-        let x = graph::nll_get_x(raw_nodes.clone(), i);
-        let y = graph::nll_get_y(raw_nodes.clone(), i);
-        let w = graph::nll_get_width(raw_nodes.clone(), i);
-        let h = graph::nll_get_height(raw_nodes.clone(), i);
-        let label = graph::nll_get_label(raw_nodes.clone(), i);
-        let shape = if compound_ids.contains(&id) {
-            "Container".to_string()
-        } else {
-            graph::nll_get_shape(raw_nodes.clone(), i)
-        };
-        rects.push(LayoutRect {
-            x,
-            y,
-            w,
-            h,
-            label,
-            shape,
-        });
     }
 
-    let en = graph::erl_len(raw_edges.clone());
-    let mut edges = Vec::new();
-    for i in 0..en {
-        let wpc = graph::erl_get_waypoint_count(raw_edges.clone(), i);
-        let mut waypoints = Vec::new();
-        for j in 0..wpc {
-            waypoints.push((
-                graph::erl_get_waypoint_x(raw_edges.clone(), i, j),
-                graph::erl_get_waypoint_y(raw_edges.clone(), i, j),
-            ));
+    /// Inverse of [`Direction::as_str`]; `TB` also reads as `TD`.
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "TD" | "TB" => Some(Direction::TD),
+            "LR" => Some(Direction::LR),
+            "RL" => Some(Direction::RL),
+            "BT" => Some(Direction::BT),
+            _ => None,
         }
-        edges.push(LayoutEdge {
-            waypoints,
-            edge_type: graph::erl_get_etype(raw_edges.clone(), i),
-            label: graph::erl_get_label(raw_edges.clone(), i),
-        });
     }
 
-    LayoutIR { rects, edges }
+    pub fn rotate_cw(&self) -> Direction {
+        match self {
+            Direction::TD => Direction::LR,
+            Direction::LR => Direction::BT,
+            Direction::BT => Direction::RL,
+            Direction::RL => Direction::TD,
+        }
+    }
+
+    pub fn rotate_ccw(&self) -> Direction {
+        self.rotate_cw().rotate_cw().rotate_cw()
+    }
+
+    pub fn opposite(&self) -> Direction {
+        self.rotate_cw().rotate_cw()
+    }
 }
 
-/// Render Mermaid DSL source to geometry-based SVG.
+// ── Public API ──────────────────────────────────────────────────────────────
+
+impl RenderConfig {
+    /// [`config_default`] tightened for dense dashboards: `padding` 0,
+    /// `h_gap` 2, `v_gap` 1 (TD/BT layers still keep the 3 rows an arrow
+    /// needs), `label_align` Left, `subgraph_margin_x` 0, `subgraph_title`
+    /// OnBorder and `label_gaps` off.
+    pub fn compact_preset() -> RenderConfig {
+        let mut config = config_default();
+        config.padding = 0;
+        config.h_gap = Some(2);
+        config.v_gap = Some(1);
+        config.label_align = TextAlign::Left;
+        config.subgraph_margin_x = 0;
+        config.subgraph_title = TitlePos::OnBorder;
+        config.label_gaps = false;
+        config
+    }
+}
+
+/// Basic counts for a parsed diagram plus advisory warnings
+/// (e.g. duplicate subgraph names, orphan nodes). Rendering never fails on warnings.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GraphStats {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub subgraph_count: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Parse a Mermaid flowchart string and report counts and warnings without rendering.
+pub fn graph_stats(src: &str) -> GraphStats {
+    let (parsed, mut warnings) = prepare_ast(src);
+    warnings.extend(orphan_warnings(&parsed));
+    let (node_count, edge_count, subgraph_count) = diagram_counts(&parsed);
+    GraphStats {
+        node_count,
+        edge_count,
+        subgraph_count,
+        warnings,
+    }
+}
+
+/// `(nodes, edges, subgraphs)` in a parsed diagram, counted as
+/// [`graph_stats`] reports them.
+fn diagram_counts(parsed: &parser::Graph) -> (usize, usize, usize) {
+    fn count_sg(sg: &parser::Subgraph, edges: &mut usize, subgraphs: &mut usize) {
+        *edges += sg.edges.len();
+        *subgraphs += 1;
+        for nested in &sg.subgraphs {
+            count_sg(nested, edges, subgraphs);
+        }
+    }
+
+    let mut edge_count = parsed.edges.len();
+    let mut subgraph_count = 0;
+    for sg in &parsed.subgraphs {
+        count_sg(sg, &mut edge_count, &mut subgraph_count);
+    }
+    (
+        resolve_node_definitions(parsed).len(),
+        edge_count,
+        subgraph_count,
+    )
+}
+
+/// Whether the diagram's edges form a directed cycle (a self-loop counts).
+pub fn has_cycle(src: &str) -> bool {
+    let (parsed, _) = prepare_ast(src);
+    !graph::graph_is_dag(&ast_to_graph(&parsed))
+}
+
+/// Edges on the longest downward path: the index of the deepest layer the
+/// layout puts a node on, with cycles broken as for rendering. 0 for a
+/// diagram with no edges.
+pub fn longest_path_length(src: &str) -> usize {
+    let (parsed, _) = prepare_ast(src);
+    let (dag, _) = remove_cycles_rust(&ast_to_graph(&parsed));
+    let layers = assign_layers_rust(&dag);
+    layers.values().max().map_or(0, |&l| l as usize)
+}
+
+/// [`graph_stats`] plus layering metrics as a boxed two-column table, one
+/// metric per row (nodes, edges, subgraphs, layers, cyclic, widest layer),
+/// for CI logs. Layers are counted as rendering assigns them, with cycles
+/// broken and subgraphs not collapsed. The box is drawn in
+/// `config.charset`, and `config.max_subgraph_depth` limits nesting.
+pub fn render_stats_table(src: &str, config: &RenderConfig) -> Result<String, RenderError> {
+    let (parsed, _) = prepare_ast_limited(src, config.max_subgraph_depth)?;
+    let (node_count, edge_count, subgraph_count) = diagram_counts(&parsed);
+    let g = ast_to_graph(&parsed);
+    let (dag, _) = remove_cycles_rust(&g);
+    let mut per_layer: HashMap<i32, usize> = HashMap::new();
+    for layer in assign_layers_rust(&dag).into_values() {
+        *per_layer.entry(layer).or_default() += 1;
+    }
+    let cyclic = if graph::graph_is_dag(&g) { "no" } else { "yes" };
+    let rows = [
+        ("nodes", node_count.to_string()),
+        ("edges", edge_count.to_string()),
+        ("subgraphs", subgraph_count.to_string()),
+        ("layers", per_layer.len().to_string()),
+        ("cyclic", cyclic.to_string()),
+        (
+            "widest layer",
+            per_layer.values().max().unwrap_or(&0).to_string(),
+        ),
+    ];
+
+    // Names left-aligned, values right-aligned, two blanks between
+    let name_w = rows
+        .iter()
+        .map(|(n, _)| display_width(n))
+        .max()
+        .unwrap_or(0);
+    let value_w = rows
+        .iter()
+        .map(|(_, v)| display_width(v))
+        .max()
+        .unwrap_or(0);
+    let w = name_w + value_w + 6;
+    let h = rows.len() as i32 + 2;
+    let mut c = canvas::canvas_new(w, h, config.charset.clone());
+    cdraw_box(
+        &mut c,
+        0,
+        0,
+        w,
+        h,
+        &canvas::box_chars_for_charset(config.charset.clone()),
+    );
+    for (i, (name, value)) in rows.iter().enumerate() {
+        let row = i as i32 + 1;
+        cwrite_str(&mut c, 2, row, name);
+        cwrite_str(&mut c, w - 2 - display_width(value), row, value);
+    }
+    Ok(c.cells.iter().map(|r| r.concat() + "\n").collect())
+}
+
+/// Every node as `(id, label, shape)`, in order of first appearance, after
+/// the same resolution rendering uses: a labelled or shaped definition wins
+/// over bare references wherever they appear.
+pub fn node_summary(src: &str) -> Result<Vec<(String, String, NodeShape)>, RenderError> {
+    fn visit(ids: &mut Vec<String>, sg: &parser::Subgraph) {
+        for node in &sg.nodes {
+            if !ids.contains(&node.id) {
+                ids.push(node.id.clone());
+            }
+        }
+        for nested in &sg.subgraphs {
+            visit(ids, nested);
+        }
+    }
+
+    let (parsed, _) = prepare_ast_limited(src, config_default().max_subgraph_depth)?;
+    let mut ids: Vec<String> = Vec::new();
+    for node in &parsed.nodes {
+        if !ids.contains(&node.id) {
+            ids.push(node.id.clone());
+        }
+    }
+    for sg in &parsed.subgraphs {
+        visit(&mut ids, sg);
+    }
+    let resolved = resolve_node_definitions(&parsed);
+    Ok(ids
+        .into_iter()
+        .map(|id| {
+            let node = &resolved[&id];
+            (id, node.label.clone(), node.shape.clone())
+        })
+        .collect())
+}
+
+/// Lay out `src` and list every pair of node boxes that overlap (see
+/// [`LayoutIR::overlap_warnings`]). Overlaps are a layout bug, not an input
+/// error: rendering draws them anyway. The check runs in debug builds or
+/// when `config.assert_no_overlap` is set; otherwise the list is empty.
+pub fn overlap_warnings(src: &str, config: &RenderConfig) -> Result<Vec<String>, RenderError> {
+    if !(cfg!(debug_assertions) || config.assert_no_overlap) {
+        return Ok(Vec::new());
+    }
+    Ok(layout_dsl(src, config)?.map_or_else(Vec::new, |(ir, _, _)| ir.overlap_warnings()))
+}
+
+/// Strict check: [`RenderError::Parse`] (1-based line and column) at the
+/// first text the parser cannot read. Rendering skips such text instead;
+/// this is for callers that would rather fail on a malformed diagram.
+pub fn check_dsl(src: &str) -> Result<(), RenderError> {
+    let (_, body) = split_front_matter(src);
+    if let Some(kind) = unsupported_diagram(body) {
+        return Err(RenderError::Unsupported(kind.to_string()));
+    }
+    let max_depth = config_default().max_subgraph_depth as usize;
+    let (_, skipped) = rust_parser::parse_flowchart_with_skips(body, max_depth)?;
+    let Some(&pos) = skipped.first() else {
+        return Ok(());
+    };
+    let front_lines = src[..src.len() - body.len()].matches('\n').count();
+    let before: String = body.chars().take(pos).collect();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line = front_lines + before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    let rest: String = body
+        .chars()
+        .skip(pos)
+        .take_while(|&ch| ch != '\n')
+        .collect();
+    Err(RenderError::Parse {
+        line,
+        col: column,
+        msg: format!("cannot parse '{}'", rest.trim_end()),
+    })
+}
+
+/// JSON form of [`RenderConfig`]: camelCase keys, all optional.
+#[cfg(feature = "json")]
+#[derive(serde::Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct JsonConfig {
+    charset: Option<String>,
+    padding: Option<i32>,
+    direction: Option<String>,
+    straighten: Option<bool>,
+    odd_widths: Option<bool>,
+    super_source: Option<bool>,
+    label_rows: Option<bool>,
+    h_gap: Option<i32>,
+    v_gap: Option<i32>,
+    min_canvas_width: Option<i32>,
+    min_canvas_height: Option<i32>,
+    draw_order: Option<String>,
+    line_ending: Option<String>,
+    subgraph_title: Option<String>,
+    label_align: Option<String>,
+    trailing_newline: Option<bool>,
+    progress_bars: Option<bool>,
+    stack_separator: Option<String>,
+    max_cells: Option<i32>,
+    skeleton: Option<bool>,
+    max_subgraph_depth: Option<i32>,
+    trim_trailing: Option<bool>,
+    svg_corner_radius: Option<i32>,
+    subgraph_margin_x: Option<i32>,
+    subgraph_margin_y: Option<i32>,
+    merge_mutual: Option<bool>,
+    decode_entities: Option<bool>,
+    assert_no_overlap: Option<bool>,
+    frame: Option<bool>,
+    frame_padding: Option<i32>,
+    exit_stubs: Option<bool>,
+    label_gaps: Option<bool>,
+    minimize_crossings: Option<bool>,
+    max_passes: Option<i32>,
+    summarize: Option<bool>,
+    show_attrs: Option<bool>,
+    boxed_edge_labels: Option<bool>,
+    pass_through_marks: Option<bool>,
+    inline_trivial: Option<bool>,
+    focus: Option<String>,
+    max_nodes: Option<i32>,
+    box_chars: Option<JsonBoxChars>,
+    layer_max_height: Option<bool>,
+}
+
+/// JSON form of [`BoxCharsOverride`].
+#[cfg(feature = "json")]
+#[derive(serde::Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct JsonBoxChars {
+    diamond_corners: Option<Vec<String>>,
+}
+
+/// Build a [`RenderConfig`] from JSON such as
+/// `{"charset": "ascii", "padding": 2, "direction": "LR", "hGap": 6}`.
 ///
-/// Runs the full layout pipeline then calls `svg_renderer::render()`.
-/// Direction: parsed from the source header; `_direction` overrides it.
-pub fn render_svg_dsl(
+/// Keys mirror the config fields in camelCase (`direction` sets
+/// `direction_override`; `charset` is `"unicode"`, `"unicodeArrows"` or
+/// `"ascii"`; `drawOrder` is `"edgesOnTop"` or `"nodesOnTop"`; `lineEnding`
+/// is `"lf"` or `"crlf"`; `boxChars` is `{"diamondCorners": [tl, tr, bl, br]}`).
+/// Missing keys keep [`config_default`] values; unknown keys are ignored.
+#[cfg(feature = "json")]
+pub fn config_from_json(json: &str) -> Result<RenderConfig, RenderError> {
+    let parsed: JsonConfig = serde_json::from_str(json)
+        .map_err(|e| RenderError::Config(format!("invalid config JSON: {e}")))?;
+    let mut config = config_default();
+    if let Some(charset) = parsed.charset {
+        config.charset = match charset.as_str() {
+            "unicode" => CharSet::Unicode,
+            "unicodeArrows" => CharSet::UnicodeArrows,
+            "ascii" => CharSet::Ascii,
+            other => return Err(RenderError::Config(format!("unknown charset '{other}'"))),
+        };
+    }
+    if parsed.direction.is_some() {
+        config.direction_override = parsed.direction;
+    }
+    config.padding = parsed.padding.unwrap_or(config.padding);
+    config.straighten = parsed.straighten.unwrap_or(config.straighten);
+    config.odd_widths = parsed.odd_widths.unwrap_or(config.odd_widths);
+    config.super_source = parsed.super_source.unwrap_or(config.super_source);
+    config.label_rows = parsed.label_rows.unwrap_or(config.label_rows);
+    config.h_gap = parsed.h_gap.or(config.h_gap);
+    config.v_gap = parsed.v_gap.or(config.v_gap);
+    config.min_canvas_width = parsed.min_canvas_width.unwrap_or(config.min_canvas_width);
+    config.min_canvas_height = parsed.min_canvas_height.unwrap_or(config.min_canvas_height);
+    if let Some(order) = parsed.draw_order {
+        config.draw_order = match order.as_str() {
+            "edgesOnTop" => DrawOrder::EdgesOnTop,
+            "nodesOnTop" => DrawOrder::NodesOnTop,
+            other => return Err(RenderError::Config(format!("unknown drawOrder '{other}'"))),
+        };
+    }
+    if let Some(ending) = parsed.line_ending {
+        config.line_ending = match ending.as_str() {
+            "lf" => LineEnding::Lf,
+            "crlf" => LineEnding::CrLf,
+            other => return Err(RenderError::Config(format!("unknown lineEnding '{other}'"))),
+        };
+    }
+    if let Some(pos) = parsed.subgraph_title {
+        config.subgraph_title = match pos.as_str() {
+            "inside" => TitlePos::Inside,
+            "onBorder" => TitlePos::OnBorder,
+            other => {
+                return Err(RenderError::Config(format!(
+                    "unknown subgraphTitle '{other}'"
+                )));
+            }
+        };
+    }
+    if let Some(align) = parsed.label_align {
+        config.label_align = match align.as_str() {
+            "left" => TextAlign::Left,
+            "center" => TextAlign::Center,
+            "right" => TextAlign::Right,
+            other => return Err(RenderError::Config(format!("unknown labelAlign '{other}'"))),
+        };
+    }
+    config.trailing_newline = parsed.trailing_newline.unwrap_or(config.trailing_newline);
+    config.progress_bars = parsed.progress_bars.unwrap_or(config.progress_bars);
+    config.max_cells = parsed.max_cells.unwrap_or(config.max_cells);
+    config.skeleton = parsed.skeleton.unwrap_or(config.skeleton);
+    config.trim_trailing = parsed.trim_trailing.unwrap_or(config.trim_trailing);
+    config.svg_corner_radius = parsed.svg_corner_radius.unwrap_or(config.svg_corner_radius);
+    config.subgraph_margin_x = parsed.subgraph_margin_x.unwrap_or(config.subgraph_margin_x);
+    config.subgraph_margin_y = parsed.subgraph_margin_y.unwrap_or(config.subgraph_margin_y);
+    config.merge_mutual = parsed.merge_mutual.unwrap_or(config.merge_mutual);
+    config.decode_entities = parsed.decode_entities.unwrap_or(config.decode_entities);
+    config.assert_no_overlap = parsed.assert_no_overlap.unwrap_or(config.assert_no_overlap);
+    config.frame = parsed.frame.unwrap_or(config.frame);
+    config.frame_padding = parsed.frame_padding.unwrap_or(config.frame_padding);
+    config.exit_stubs = parsed.exit_stubs.unwrap_or(config.exit_stubs);
+    config.label_gaps = parsed.label_gaps.unwrap_or(config.label_gaps);
+    config.minimize_crossings = parsed
+        .minimize_crossings
+        .unwrap_or(config.minimize_crossings);
+    config.max_passes = parsed.max_passes.unwrap_or(config.max_passes);
+    config.summarize = parsed.summarize.unwrap_or(config.summarize);
+    config.show_attrs = parsed.show_attrs.unwrap_or(config.show_attrs);
+    config.boxed_edge_labels = parsed.boxed_edge_labels.unwrap_or(config.boxed_edge_labels);
+    config.pass_through_marks = parsed
+        .pass_through_marks
+        .unwrap_or(config.pass_through_marks);
+    config.inline_trivial = parsed.inline_trivial.unwrap_or(config.inline_trivial);
+    config.layer_max_height = parsed.layer_max_height.unwrap_or(config.layer_max_height);
+    if parsed.focus.is_some() {
+        config.focus = parsed.focus;
+    }
+    config.max_nodes = parsed.max_nodes.unwrap_or(config.max_nodes);
+    if let Some(corners) = parsed.box_chars.and_then(|b| b.diamond_corners) {
+        if !corners.is_empty() && corners.len() != 4 {
+            return Err(RenderError::Config(format!(
+                "boxChars.diamondCorners needs 4 glyphs, got {}",
+                corners.len()
+            )));
+        }
+        config.box_chars.diamond_corners = corners;
+    }
+    config.max_subgraph_depth = parsed
+        .max_subgraph_depth
+        .unwrap_or(config.max_subgraph_depth);
+    if parsed.stack_separator.is_some() {
+        config.stack_separator = parsed.stack_separator;
+    }
+    Ok(config)
+}
+
+/// Parse a Mermaid flowchart string and render it to ASCII/Unicode art.
+///
+/// `unicode` is shorthand for the charset: `true` → [`CharSet::Unicode`],
+/// `false` → [`CharSet::Ascii`]. Use [`render_dsl_with_config`] to pick one explicitly.
+pub fn render_dsl(
     src: &str,
+    unicode: bool,
     padding: usize,
     _direction: Option<&str>,
-) -> Result<String, String> {
-    let parsed = rust_parser::parse_flowchart(src);
-    if parsed.nodes.is_empty() && parsed.edges.is_empty() && parsed.subgraphs.is_empty() {
-        return Ok(String::new());
+) -> Result<String, RenderError> {
+    let config = config_new(unicode, padding as i32, _direction.map(|d| d.to_string()));
+    render_dsl_with_config(src, &config)
+}
+
+/// Parse a Mermaid flowchart string and render it with an explicit [`RenderConfig`].
+pub fn render_dsl_with_config(src: &str, config: &RenderConfig) -> Result<String, RenderError> {
+    let mut out = Vec::new();
+    render_dsl_to_writer(src, config, &mut out)?;
+    Ok(String::from_utf8(out)?)
+}
+
+/// Stream [`render_dsl_with_config`]'s output to `writer` line by line,
+/// without assembling the whole string first. A diagram that cannot be
+/// drawn fails with `InvalidData` wrapping its [`RenderError`].
+pub fn render_dsl_to_writer<W: std::io::Write>(
+    src: &str,
+    config: &RenderConfig,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let invalid = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+    if let Some(lines) = inline_trivial_dsl(src, config).map_err(invalid)? {
+        return write_lines(&lines, config, writer);
+    }
+    let layout = layout_dsl(src, config).map_err(invalid)?;
+    let Some((ir, direction, config)) = layout else {
+        return Ok(());
+    };
+    write_ir_text(&ir, direction, &config, writer)
+}
+
+/// inline_trivial: the one-line drawing of `src` (framed if `frame` is on),
+/// or `None` when the option is off or the diagram is not trivial.
+fn inline_trivial_dsl(
+    src: &str,
+    config: &RenderConfig,
+) -> Result<Option<Vec<String>>, RenderError> {
+    if !config.inline_trivial {
+        return Ok(None);
+    }
+    let (parsed, _) = prepare_ast_limited(src, config.max_subgraph_depth)?;
+    Ok(inline_trivial_ast(&parsed, config, front_matter(src)))
+}
+
+/// [`inline_trivial_dsl`] for a parsed diagram, with the front matter, label
+/// rewrites and direction a full layout would apply.
+fn inline_trivial_ast(
+    parsed: &parser::Graph,
+    config: &RenderConfig,
+    front_matter: FrontMatter,
+) -> Option<Vec<String>> {
+    if !config.inline_trivial {
+        return None;
     }
+    let direction =
+        (config.direction_override.as_deref()).unwrap_or(direction_str(&parsed.direction));
+    let config = front_matter.apply(config, direction == "LR" || direction == "RL");
+    let relabelled = relabel_ast(parsed, &config);
+    let parsed = relabelled.as_ref().unwrap_or(parsed);
+    let line = inline_trivial_line(parsed, direction == "RL", &config)?;
+    Some(if config.frame {
+        frame_lines(&[line], &config)
+    } else {
+        vec![line]
+    })
+}
 
-    let parsed_direction = match parsed.direction {
-        parser::Direction::LR => "LR",
-        parser::Direction::RL => "RL",
-        parser::Direction::BT => "BT",
-        _ => "TD",
+/// inline_trivial: `[A] ───► [B]` for a diagram of exactly two nodes joined
+/// by one edge, both ends written with Mermaid's shape brackets and the edge
+/// drawn as usual, label included; `rl` mirrors it to `[B] ◄─── [A]`. `None`
+/// for anything else, or when a label spans several lines.
+fn inline_trivial_line(parsed: &parser::Graph, rl: bool, config: &RenderConfig) -> Option<String> {
+    if !parsed.subgraphs.is_empty() {
+        return None;
+    }
+    let g = ast_to_graph(parsed);
+    if g.digraph.node_count() != 2 || g.digraph.edge_count() != 1 {
+        return None;
+    }
+    let eidx = g.digraph.edge_indices().next()?;
+    let (from, to) = g.digraph.edge_endpoints(eidx)?;
+    if from == to {
+        return None;
+    }
+    let node_text = |n: &graph::NodeData| {
+        let (open, close) = match n.shape.as_str() {
+            "Rounded" => ("(", ")"),
+            "Diamond" => ("{", "}"),
+            "Circle" => ("((", "))"),
+            _ => ("[", "]"),
+        };
+        (!n.label.contains('\n')).then(|| format!("{open}{}{close}", n.label))
     };
-    let direction = _direction.unwrap_or(parsed_direction);
+    let (mut left, mut right) = (node_text(&g.digraph[from])?, node_text(&g.digraph[to])?);
+    if rl {
+        std::mem::swap(&mut left, &mut right);
+    }
+    let edge = &g.digraph[eidx];
+    let label = edge.label.as_deref().unwrap_or("");
+    if label.contains('\n') {
+        return None;
+    }
 
-    let ir = run_layout_pipeline(&parsed, padding, direction);
+    // The edge fills the cells between the nodes bar one blank on each
+    // side, with room for the label and a line cell or two around it
+    let start = display_width(&left) + 1;
+    let end = start + display_width(label) + 3;
+    let mut c = canvas::canvas_new(end + 2 + display_width(&right), 1, config.charset.clone());
+    cwrite_str(&mut c, 0, 0, &left);
+    let (waypoints, label_col) = if rl {
+        ([(end, 0), (start, 0)], end - display_width(label))
+    } else {
+        ([(start, 0), (end, 0)], start + 1)
+    };
+    let label_pos = (!label.is_empty()).then_some((label_col, 0));
+    let edge_type = styled_edge_type(&edge.edge_type, &edge.classes);
+    paint_edge(&mut c, &waypoints, &edge_type, label, label_pos, &[], false);
+    cwrite_str(&mut c, end + 2, 0, &right);
+    Some(c.cells[0].concat())
+}
 
-    Ok(svg_renderer::render_ir(&ir, direction))
+/// [`render_dsl_with_config`] with edges routed by a custom [`EdgeRouter`]
+/// instead of the default [`AStarRouter`].
+pub fn render_dsl_with_router(
+    src: &str,
+    config: &RenderConfig,
+    router: &dyn EdgeRouter,
+) -> Result<String, RenderError> {
+    let mut out = Vec::new();
+    if let Some(lines) = inline_trivial_dsl(src, config)? {
+        write_lines(&lines, config, &mut out)?;
+        return Ok(String::from_utf8(out)?);
+    }
+    let Some((ir, direction, config)) = layout_dsl_routed(src, config, router)? else {
+        return Ok(String::new());
+    };
+    write_ir_text(&ir, direction, &config, &mut out)?;
+    Ok(String::from_utf8(out)?)
 }
 
-// ── WASM bindings ───────────────────────────────────────────────────────────
+/// Render `src` turned by `quarter_turns` × 90° (negative turns go
+/// counter-clockwise; see [`Direction::rotate_cw`]) by rotating its
+/// direction: `direction_override` if set, else the one the source declares.
+pub fn rerender_rotated(
+    src: &str,
+    quarter_turns: i32,
+    config: &RenderConfig,
+) -> Result<String, RenderError> {
+    let (parsed, _) = prepare_ast_limited(src, config.max_subgraph_depth)?;
+    let mut direction = (config.direction_override.as_deref())
+        .and_then(Direction::from_name)
+        .unwrap_or(parsed.direction);
+    for _ in 0..quarter_turns.rem_euclid(4) {
+        direction = direction.rotate_cw();
+    }
+    let mut rotated = config.clone();
+    rotated.direction_override = Some(direction.as_str().to_string());
+    render_dsl_with_config(src, &rotated)
+}
 
-#[cfg(feature = "wasm")]
-#[wasm_bindgen]
-pub fn render(src: &str) -> Result<String, JsError> {
-    render_dsl(src, true, 1, None).map_err(|e| JsError::new(&e))
+/// Render a [`Graph`] built in code (see [`GraphBuilder`]) without going
+/// through Mermaid text. Output matches [`render_dsl_with_config`] on the
+/// equivalent DSL.
+pub fn render_graph(graph: &Graph, config: &RenderConfig) -> Result<String, RenderError> {
+    let mut out = Vec::new();
+    if let Some(lines) = inline_trivial_ast(graph, config, FrontMatter::default()) {
+        write_lines(&lines, config, &mut out)?;
+        return Ok(String::from_utf8(out)?);
+    }
+    let Some((ir, direction, config)) = layout_ast(
+        graph,
+        config,
+        FrontMatter::default(),
+        &layout_pins(graph),
+        &AStarRouter,
+    ) else {
+        return Ok(String::new());
+    };
+    write_ir_text(&ir, direction, &config, &mut out)?;
+    Ok(String::from_utf8(out)?)
 }
 
-#[cfg(feature = "wasm")]
-#[wasm_bindgen(js_name = "renderWithOptions")]
-pub fn render_with_options(
+/// Render several diagrams into one string, stacked top to bottom. Each
+/// source is laid out on its own; consecutive diagrams are split by a blank
+/// line, a separator line as wide as the widest diagram (see
+/// `RenderConfig::stack_separator`) and another blank line. Empty sources
+/// are left out. Errors name the 1-based index of the failing source.
+pub fn render_many(sources: &[&str], config: &RenderConfig) -> Result<String, RenderError> {
+    let eol = match config.line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::CrLf => "\r\n",
+    };
+    let mut single = config.clone();
+    single.trailing_newline = false;
+    let mut diagrams = Vec::new();
+    for (i, src) in sources.iter().enumerate() {
+        let out = render_dsl_with_config(src, &single).map_err(|e| e.in_diagram(i + 1))?;
+        if !out.is_empty() {
+            diagrams.push(out);
+        }
+    }
+    let width = diagrams
+        .iter()
+        .flat_map(|d| d.lines())
+        .map(display_width)
+        .max()
+        .unwrap_or(0);
+    let glyph = config
+        .stack_separator
+        .clone()
+        .unwrap_or_else(|| canvas::box_chars_for_charset(config.charset.clone()).horizontal);
+    let separator = format!(
+        "{eol}{eol}{}{eol}{eol}",
+        glyph.repeat(width.max(1) as usize)
+    );
+    let mut out = diagrams.join(&separator);
+    if config.trailing_newline && !out.is_empty() {
+        out.push_str(eol);
+    }
+    Ok(out)
+}
+
+/// Render only layers `start_layer..=end_layer` of `src`, for paging
+/// through a tall diagram. The whole graph is laid out first so every page
+/// keeps the same columns; nodes outside the range are dropped and edges
+/// are cut at the gaps next to the hidden layers, leaving stubs (without
+/// arrowheads) that show where they continue.
+pub fn render_layer_range(
     src: &str,
-    unicode: bool,
-    padding: usize,
-    direction: &str,
-) -> Result<String, JsError> {
-    let dir = if direction.is_empty() {
-        None
-    } else {
-        Some(direction)
+    start_layer: usize,
+    end_layer: usize,
+    config: &RenderConfig,
+) -> Result<String, RenderError> {
+    let Some((ir, direction, config)) = layout_dsl(src, config)? else {
+        return Ok(String::new());
     };
-    render_dsl(src, unicode, padding, dir).map_err(|e| JsError::new(&e))
+    let (start, end) = (start_layer as i32, end_layer as i32);
+    let along_x = direction == "LR" || direction == "RL";
+    let ir = clip_to_layers(&ir, start, end, along_x);
+    let mut out = Vec::new();
+    write_ir_text(&ir, direction, &config, &mut out)?;
+    Ok(String::from_utf8(out)?)
 }
 
-#[cfg(feature = "wasm")]
-#[wasm_bindgen(js_name = "renderSvg")]
-pub fn render_svg(src: &str, padding: usize, direction: &str) -> Result<String, JsError> {
-    let dir = if direction.is_empty() {
-        None
-    } else {
-        Some(direction)
+/// Keep the rects in layers `start..=end` and the parts of edges inside the
+/// band between the hidden layers before and after them (minus the cells
+/// touching hidden boxes, where their arrowheads and exits sit), shifted so
+/// the band starts at 0. `along_x` is true when layers advance along x (LR/RL).
+fn clip_to_layers(ir: &LayoutIR, start: i32, end: i32, along_x: bool) -> LayoutIR {
+    let axis = |(x, y): (i32, i32)| if along_x { x } else { y };
+    let span = |r: &LayoutRect| if along_x { (r.x, r.w) } else { (r.y, r.h) };
+    // Exit cell just past the last hidden box before the range, and entry
+    // cell just short of the first hidden box after it
+    let before = (ir.rects.iter())
+        .filter(|r| r.layer < start)
+        .map(|r| span(r).0 + span(r).1)
+        .max();
+    let after = (ir.rects.iter())
+        .filter(|r| r.layer > end)
+        .map(|r| span(r).0 - 1)
+        .min();
+    let (lo, hi) = (
+        before.map_or(i32::MIN, |b| b + 1),
+        after.map_or(i32::MAX, |a| a - 1),
+    );
+    let (stub_lo, stub_hi) = (before.unwrap_or(i32::MIN), after.unwrap_or(i32::MAX));
+    let origin = before.unwrap_or(0);
+    let shift = |(x, y): (i32, i32)| {
+        if along_x {
+            (x - origin, y)
+        } else {
+            (x, y - origin)
+        }
+    };
+    let clamp = |(x, y): (i32, i32)| {
+        if along_x {
+            (x.clamp(stub_lo, stub_hi), y)
+        } else {
+            (x, y.clamp(stub_lo, stub_hi))
+        }
+    };
+
+    let rects = ir
+        .rects
+        .iter()
+        .filter(|r| (start..=end).contains(&r.layer))
+        .map(|r| {
+            let (x, y) = shift((r.x, r.y));
+            LayoutRect { x, y, ..r.clone() }
+        })
+        .collect();
+
+    let mut edges = Vec::new();
+    for e in &ir.edges {
+        let wps = &e.waypoints;
+        let mut label = edge_label_origin(wps, &e.label, e.label_pos)
+            .filter(|&p| (lo..=hi).contains(&axis(p)))
+            .map(|p| (e.label.clone(), shift(p)));
+        // (points, starts at the original first waypoint, ends at the last);
+        // cut pieces run on to the exit/entry cell as a plain stub
+        let in_band = |p: (i32, i32)| (lo..=hi).contains(&axis(p));
+        let mut pieces = Vec::new();
+        let mut current: Option<(Vec<(i32, i32)>, bool)> = None;
+        for (i, seg) in wps.windows(2).enumerate() {
+            let (a0, a1) = (axis(seg[0]), axis(seg[1]));
+            if a0.min(a1) > stub_hi || a0.max(a1) < stub_lo {
+                if let Some((points, whole_start)) = current.take() {
+                    pieces.push((points, whole_start, false));
+                }
+                continue;
+            }
+            let (p, q) = (clamp(seg[0]), clamp(seg[1]));
+            let whole_start = i == 0 && p == seg[0] && in_band(p);
+            let (points, _) = current.get_or_insert_with(|| (vec![p], whole_start));
+            if points.last() != Some(&q) {
+                points.push(q);
+            }
+            if q != seg[1] {
+                let (points, whole_start) = current.take().unwrap();
+                pieces.push((points, whole_start, false));
+            }
+        }
+        if let Some((points, whole_start)) = current {
+            let whole_end = wps.last().is_some_and(|&p| in_band(p));
+            pieces.push((points, whole_start, whole_end));
+        }
+
+        for (points, whole_start, whole_end) in pieces {
+            if points.len() < 2 {
+                continue;
+            }
+            let mut waypoints: Vec<(i32, i32)> = points.into_iter().map(shift).collect();
+            let (edge_type, reversed) = clipped_edge_type(&e.edge_type, whole_start, whole_end);
+            if reversed {
+                waypoints.reverse();
+            }
+            let (label, label_pos) = match label.take() {
+                Some((text, pos)) => (text, Some(pos)),
+                None => (String::new(), None),
+            };
+            edges.push(LayoutEdge {
+                from: e.from.clone(),
+                to: e.to.clone(),
+                waypoints,
+                edge_type,
+                label,
+                label_pos,
+                classes: e.classes.clone(),
+            });
+        }
+    }
+
+    LayoutIR {
+        rects,
+        edges,
+        acc_title: ir.acc_title.clone(),
+        acc_descr: ir.acc_descr.clone(),
+    }
+}
+
+/// Edge type for a clipped piece of an `edge_type` edge: arrowheads survive
+/// only on ends that were not cut. Returns whether the waypoints must be
+/// reversed (a bidirectional edge that kept only its start arrowhead).
+fn clipped_edge_type(edge_type: &str, whole_start: bool, whole_end: bool) -> (String, bool) {
+    let (arrow, line, bidir) = match edge_type {
+        "DottedArrow" | "DottedLine" | "BidirDotted" => {
+            ("DottedArrow", "DottedLine", "BidirDotted")
+        }
+        "ThickArrow" | "ThickLine" | "BidirThick" => ("ThickArrow", "ThickLine", "BidirThick"),
+        "CircleArrow" | "BidirCircle" => ("CircleArrow", "Line", "BidirCircle"),
+        "CrossArrow" | "BidirCross" => ("CrossArrow", "Line", "BidirCross"),
+        _ => ("Arrow", "Line", "BidirArrow"),
+    };
+    let start_head = whole_start && edge_type == bidir;
+    let end_head = whole_end && (edge_type == arrow || edge_type == bidir);
+    match (start_head, end_head) {
+        (true, true) => (bidir.to_string(), false),
+        (true, false) => (arrow.to_string(), true),
+        (false, true) => (arrow.to_string(), false),
+        (false, false) => (line.to_string(), false),
+    }
+}
+
+/// Paint `ir` and write it line by line: trailing blanks trimmed, BT/RL
+/// flips applied per line, lines joined with `config.line_ending`.
+fn write_ir_text<W: std::io::Write>(
+    ir: &LayoutIR,
+    direction: &str,
+    config: &RenderConfig,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    // `InvalidData` marks a diagram that cannot be drawn, not a failed write.
+    let lines = ir_lines(ir, direction, config)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    write_lines(&lines, config, writer)
+}
+
+/// Write finished output `lines` one at a time, each followed by
+/// `config.line_ending`; the last one only if `config.trailing_newline` is set.
+fn write_lines<W: std::io::Write>(
+    lines: &[String],
+    config: &RenderConfig,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let eol = match config.line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::CrLf => "\r\n",
     };
-    render_svg_dsl(src, padding, dir).map_err(|e| JsError::new(&e))
+    for (i, line) in lines.iter().enumerate() {
+        writer.write_all(line.as_bytes())?;
+        if i + 1 < lines.len() || config.trailing_newline {
+            writer.write_all(eol.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Paint `ir` into its output lines, without line endings: trailing blanks
+/// trimmed, BT/RL flips and the frame applied. Empty for an empty canvas.
+fn ir_lines(
+    ir: &LayoutIR,
+    direction: &str,
+    config: &RenderConfig,
+) -> Result<Vec<String>, RenderError> {
+    let c = paint_ir(ir, config)?;
+
+    // Rows up to the last non-blank one, each trimmed on the right
+    // (implemented directly to avoid .hom codegen issues), unless the
+    // whole canvas is wanted
+    let line = |row: &Vec<String>| {
+        let text = row.join("");
+        if config.trim_trailing {
+            text.trim_end().to_string()
+        } else {
+            text
+        }
+    };
+    let last = c
+        .cells
+        .iter()
+        .rposition(|row| !line(row).trim_end().is_empty());
+    let rows = if config.trim_trailing {
+        &c.cells[..last.map_or(0, |i| i + 1)]
+    } else {
+        &c.cells[..]
+    };
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut lines: Vec<String> = if direction == "BT" {
+        rows.iter()
+            .rev()
+            .map(|row| flip_vertical_line(&line(row)))
+            .collect()
+    } else if direction == "RL" {
+        let width = rows
+            .iter()
+            .map(|r| line(r).chars().count())
+            .max()
+            .unwrap_or(0);
+        rows.iter()
+            .map(|row| flip_horizontal_line(&line(row), width, config.trim_trailing))
+            .collect()
+    } else {
+        rows.iter().map(line).collect()
+    };
+    if config.frame {
+        lines = frame_lines(&lines, config);
+    }
+    Ok(lines)
+}
+
+/// The rendered diagram as a grid of characters, one row per output line,
+/// for callers that draw into their own buffer (e.g. a TUI). Rows are padded
+/// with spaces to the longest line; a wide character takes one entry.
+pub fn render_to_cells(src: &str, config: &RenderConfig) -> Result<Vec<Vec<char>>, RenderError> {
+    let lines = match inline_trivial_dsl(src, config)? {
+        Some(lines) => lines,
+        None => {
+            let Some((ir, direction, config)) = layout_dsl(src, config)? else {
+                return Ok(Vec::new());
+            };
+            ir_lines(&ir, direction, &config)?
+        }
+    };
+    let mut grid: Vec<Vec<char>> = lines.iter().map(|l| l.chars().collect()).collect();
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    for row in &mut grid {
+        row.resize(width, ' ');
+    }
+    Ok(grid)
+}
+
+/// frame: surround the finished `lines` with a charset border, `frame_padding`
+/// blank cells inside it on every side.
+fn frame_lines(lines: &[String], config: &RenderConfig) -> Vec<String> {
+    let bc = canvas::box_chars_for_charset(config.charset.clone());
+    let pad = config.frame_padding.max(0) as usize;
+    let width = lines.iter().map(|l| l.width()).max().unwrap_or(0) + 2 * pad;
+    let side = |text: &str| {
+        let fill = width - pad - text.width();
+        format!(
+            "{}{}{text}{}{}",
+            bc.vertical,
+            " ".repeat(pad),
+            " ".repeat(fill),
+            bc.vertical
+        )
+    };
+    let rule = bc.horizontal.repeat(width);
+    let mut out = vec![format!("{}{rule}{}", bc.top_left, bc.top_right)];
+    out.extend((0..pad).map(|_| side("")));
+    out.extend(lines.iter().map(|l| side(l)));
+    out.extend((0..pad).map(|_| side("")));
+    out.push(format!("{}{rule}{}", bc.bottom_left, bc.bottom_right));
+    out
+}
+
+/// Final `(width, height)` of [`render_dsl_with_config`]'s output, in
+/// characters and lines, without assembling the string. `(0, 0)` when the
+/// diagram is empty.
+pub fn measure_dsl(src: &str, config: &RenderConfig) -> Result<(usize, usize), RenderError> {
+    if let Some(lines) = inline_trivial_dsl(src, config)? {
+        let width = lines.iter().map(|l| display_width(l)).max().unwrap_or(0);
+        return Ok((width as usize, lines.len()));
+    }
+    let Some((ir, direction, config)) = layout_dsl(src, config)? else {
+        return Ok((0, 0));
+    };
+    let c = paint_ir(&ir, &config)?;
+    // frame adds a border cell plus its padding on every side
+    let grow = if config.frame {
+        2 + 2 * config.frame_padding.max(0) as usize
+    } else {
+        0
+    };
+    if !config.trim_trailing {
+        return Ok((
+            c.cells.first().map_or(0, Vec::len) + grow,
+            c.cells.len() + grow,
+        ));
+    }
+
+    // Bounding box of the painted cells; rendering trims everything outside it
+    // on the right and bottom.
+    let (mut min_col, mut max_col, mut max_row) = (usize::MAX, 0, None);
+    for (row, cells) in c.cells.iter().enumerate() {
+        if let Some(last) = cells.iter().rposition(|ch| !ch.trim().is_empty()) {
+            let first = cells
+                .iter()
+                .position(|ch| !ch.trim().is_empty())
+                .unwrap_or(last);
+            min_col = min_col.min(first);
+            max_col = max_col.max(last + 1);
+            max_row = Some(row);
+        }
+    }
+    let Some(max_row) = max_row else {
+        return Ok((0, 0));
+    };
+    // RL mirrors the lines, so leading blank columns become trailing ones and are trimmed.
+    let width = if direction == "RL" {
+        max_col - min_col
+    } else {
+        max_col
+    };
+    Ok((width + grow, max_row + 1 + grow))
+}
+
+/// Parse `src`, resolve its direction and front matter, and lay it out.
+/// Returns `None` for an empty diagram.
+fn layout_dsl<'a>(
+    src: &str,
+    config: &'a RenderConfig,
+) -> Result<Option<(LayoutIR, &'a str, RenderConfig)>, RenderError> {
+    layout_dsl_routed(src, config, &AStarRouter)
+}
+
+/// [`layout_dsl`] with edges routed by `router`.
+fn layout_dsl_routed<'a>(
+    src: &str,
+    config: &'a RenderConfig,
+    router: &dyn EdgeRouter,
+) -> Result<Option<(LayoutIR, &'a str, RenderConfig)>, RenderError> {
+    let (parsed, _) = prepare_ast_limited(src, config.max_subgraph_depth)?;
+    Ok(layout_ast(
+        &parsed,
+        config,
+        front_matter(src),
+        &layout_pins(&parsed),
+        router,
+    ))
+}
+
+/// Resolve the direction of an already-parsed diagram, apply its front
+/// matter and lay it out with its `%% pos` pins. Returns `None` for an
+/// empty diagram.
+fn layout_ast<'a>(
+    parsed: &parser::Graph,
+    config: &'a RenderConfig,
+    front_matter: FrontMatter,
+    pins: &LayoutPins,
+    router: &dyn EdgeRouter,
+) -> Option<(LayoutIR, &'a str, RenderConfig)> {
+    if parsed.nodes.is_empty() && parsed.edges.is_empty() && parsed.subgraphs.is_empty() {
+        return None;
+    }
+
+    let parsed_direction = direction_str(&parsed.direction);
+    let direction = config
+        .direction_override
+        .as_deref()
+        .unwrap_or(parsed_direction);
+    let is_lr_or_rl = direction == "LR" || direction == "RL";
+    let config = front_matter.apply(config, is_lr_or_rl);
+
+    let ir = run_layout_pipeline(parsed, &config, direction, pins, router, None);
+    Some((ir, direction, config))
+}
+
+/// Canvas size needed to draw `ir`, with a margin for labels and arrowheads.
+fn canvas_dimensions(ir: &LayoutIR) -> (i32, i32) {
+    let mut max_col: i32 = 0;
+    let mut max_row: i32 = 0;
+    for r in &ir.rects {
+        max_col = max_col.max(r.x.saturating_add(r.w).saturating_add(2));
+        max_row = max_row.max(r.y.saturating_add(r.h).saturating_add(4));
+    }
+    for e in &ir.edges {
+        for &(wx, wy) in &e.waypoints {
+            max_col = max_col.max(wx.saturating_add(4));
+            max_row = max_row.max(wy.saturating_add(4));
+        }
+        if let Some((lx, _)) = edge_label_origin(&e.waypoints, &e.label, e.label_pos) {
+            max_col = max_col.max(lx.saturating_add(label_width(&e.label)));
+        }
+    }
+    (max_col, max_row)
+}
+
+/// 1:1 IR → canvas (no logic, just draw primitives), before direction flips.
+/// Fails without allocating when the canvas would exceed `config.max_cells`.
+fn paint_ir(ir: &LayoutIR, config: &RenderConfig) -> Result<canvas::Canvas, RenderError> {
+    let (max_col, max_row) = canvas_dimensions(ir);
+    let max_col = max_col.max(config.min_canvas_width);
+    let max_row = max_row.max(config.min_canvas_height);
+    if max_col as i64 * max_row as i64 > config.max_cells as i64 {
+        return Err(RenderError::LimitExceeded(format!(
+            "diagram too large: {max_col}x{max_row} canvas exceeds max_cells ({})",
+            config.max_cells
+        )));
+    }
+    let mut c = canvas::canvas_new(max_col, max_row, config.charset.clone());
+
+    // Draw containers first (behind), then nodes on top
+    for r in &ir.rects {
+        if r.shape == "Container" {
+            paint_compound_node(&mut c, r, &config.subgraph_title);
+        }
+    }
+    let paint_nodes = |c: &mut canvas::Canvas| {
+        for r in &ir.rects {
+            if r.shape != "Container" && config.skeleton {
+                cwrite_str(c, r.x, r.y, &r.label);
+            } else if r.shape != "Container" {
+                let heavy = r.classes.iter().any(|cl| cl == EMPHASIS_CLASS)
+                    || style_is_heavy(&r.styles)
+                    || config.focus.as_deref() == Some(r.id.as_str());
+                paint_node(
+                    c,
+                    r.x,
+                    r.y,
+                    r.w,
+                    r.h,
+                    &r.label,
+                    &r.shape,
+                    heavy,
+                    &config.box_chars,
+                    &config.label_align,
+                );
+            }
+        }
+    };
+    let layer_of: HashMap<&str, i32> = ir.rects.iter().map(|r| (r.id.as_str(), r.layer)).collect();
+    let paint_edges = |c: &mut canvas::Canvas| {
+        // A later edge crossing an earlier one's arrowhead must not erase it
+        let mut heads = Vec::new();
+        for e in &ir.edges {
+            let edge_type = styled_edge_type(&e.edge_type, &e.classes);
+            // pass_through_marks: nodes in the layers strictly between the
+            // ends are ones a long edge passes by without connecting
+            let beside: Vec<&LayoutRect> =
+                match (layer_of.get(e.from.as_str()), layer_of.get(e.to.as_str())) {
+                    (Some(&a), Some(&b)) if config.pass_through_marks && (a - b).abs() > 1 => ir
+                        .rects
+                        .iter()
+                        .filter(|r| {
+                            r.shape != "Container" && r.layer > a.min(b) && r.layer < a.max(b)
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+            // The label is placed from the full path: dropping waypoints
+            // moves its middle one
+            paint_edge(
+                c,
+                &simplify_waypoints(&e.waypoints),
+                &edge_type,
+                &e.label,
+                edge_label_origin(&e.waypoints, &e.label, e.label_pos),
+                &beside,
+                config.boxed_edge_labels,
+            );
+            if let Some(&(x, y)) = e
+                .waypoints
+                .last()
+                .filter(|_| edge_type.contains("Arrow") || edge_type.starts_with("Bidir"))
+            {
+                heads.push((x, y, cget(c, x, y)));
+            }
+        }
+        for (x, y, head) in heads {
+            cset(c, x, y, head);
+        }
+    };
+    match config.draw_order {
+        DrawOrder::EdgesOnTop => {
+            paint_nodes(&mut c);
+            paint_edges(&mut c);
+        }
+        DrawOrder::NodesOnTop => {
+            paint_edges(&mut c);
+            // Arrowheads that enter on a border (diamond vertices) are part of
+            // the entry, so they go back on top of the boxes.
+            let tips: Vec<(i32, i32, String)> = ir
+                .edges
+                .iter()
+                .filter_map(|e| e.waypoints.last())
+                .map(|&(x, y)| (x, y, cget(&c, x, y)))
+                .collect();
+            paint_nodes(&mut c);
+            for (x, y, tip) in tips {
+                cset(&mut c, x, y, tip);
+            }
+        }
+    }
+
+    if config.exit_stubs {
+        paint_exit_stubs_ir(&mut c, ir);
+    }
+    Ok(c)
+}
+
+/// Shared layout result used by both ASCII and SVG renderers.
+/// A positioned rectangle — node or container.
+#[derive(Clone, Debug)]
+pub struct LayoutRect {
+    /// Node id from the source; the subgraph id for a container.
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+    pub label: String,
+    /// "Rectangle", "Rounded", "Diamond", "Circle", "Container"
+    pub shape: String,
+    /// Classes from `A:::name` and `class A name`, in source order.
+    pub classes: Vec<String>,
+    /// `(property, value)` pairs from `style A ...` statements.
+    pub styles: Vec<(String, String)>,
+    /// Sugiyama layer (rank); subgraph members share their container's layer.
+    pub layer: i32,
+}
+
+/// A routed edge with waypoints.
+#[derive(Clone, Debug)]
+pub struct LayoutEdge {
+    /// Ids of the source and target nodes as written (a subgraph member
+    /// rather than its container, a subgraph id for an edge to the subgraph).
+    pub from: String,
+    pub to: String,
+    pub waypoints: Vec<(i32, i32)>,
+    pub edge_type: String,
+    pub label: String,
+    /// First cell of the label when it sits on a reserved row (`label_rows`);
+    /// `None` lets each renderer place it from the waypoints.
+    pub label_pos: Option<(i32, i32)>,
+    /// Classes from `A -->:::name B`, in source order.
+    pub classes: Vec<String>,
+}
+
+/// Flat, primitive layout IR — no compound node hacks.
+/// Both ASCII and SVG renderers consume this directly.
+pub struct LayoutIR {
+    pub rects: Vec<LayoutRect>,
+    pub edges: Vec<LayoutEdge>,
+    /// Accessibility title and description (`accTitle` / `accDescr`); only
+    /// the SVG renderer uses them. Empty when absent.
+    pub acc_title: String,
+    pub acc_descr: String,
+}
+
+impl LayoutIR {
+    /// `(columns, rows)` of the canvas the ASCII renderer draws this layout on.
+    /// The rendered text is this size (or the configured minimum, if larger)
+    /// before trailing blanks are trimmed; use [`measure_dsl`] for the final dimensions.
+    pub fn canvas_size(&self) -> (usize, usize) {
+        let (w, h) = canvas_dimensions(self);
+        (w as usize, h as usize)
+    }
+
+    /// One warning per pair of node boxes that share a cell. A correct
+    /// layout has none; containers are skipped since they hold their members.
+    pub fn overlap_warnings(&self) -> Vec<String> {
+        let nodes: Vec<&LayoutRect> = self
+            .rects
+            .iter()
+            .filter(|r| r.shape != "Container")
+            .collect();
+        let mut warnings = Vec::new();
+        for (i, a) in nodes.iter().enumerate() {
+            for b in &nodes[i + 1..] {
+                let overlap =
+                    a.x < b.x + b.w && b.x < a.x + a.w && a.y < b.y + b.h && b.y < a.y + a.h;
+                if overlap {
+                    warnings.push(format!(
+                        "node boxes '{}' at ({}, {}) and '{}' at ({}, {}) overlap",
+                        a.label, a.x, a.y, b.label, b.x, b.y
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+}
+
+/// How the router produced an edge's path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouteMethod {
+    /// The [`EdgeRouter`] (A* search through the occupancy grid by default).
+    AStar,
+    /// A* found no path; an orthogonal L-path was used instead.
+    Fallback,
+    /// Long edge drawn through its dummy chain (`straighten`).
+    DummyChain,
+    /// Source and target share a layer; detoured below it into the target's bottom.
+    SameLayer,
+    /// Drawn down the column a `%% route` directive pinned it to.
+    Pinned,
+}
+
+/// Routing diagnostics for one edge, in the same coordinates as [`LayoutIR`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct EdgeTrace {
+    /// Source/target ids as written; a back edge runs upward from its
+    /// source's top border to its target's bottom border.
+    pub from: String,
+    pub to: String,
+    /// Cell just outside the source box where the path starts.
+    pub exit: (i32, i32),
+    /// Cell just outside the target box where the arrowhead lands
+    /// (below it for [`RouteMethod::SameLayer`] and back edges).
+    pub entry: (i32, i32),
+    pub method: RouteMethod,
+    pub waypoints: Vec<(i32, i32)>,
+}
+
+impl EdgeTrace {
+    fn transpose(&mut self) {
+        std::mem::swap(&mut self.exit.0, &mut self.exit.1);
+        std::mem::swap(&mut self.entry.0, &mut self.entry.1);
+        for wp in self.waypoints.iter_mut() {
+            std::mem::swap(&mut wp.0, &mut wp.1);
+        }
+    }
+}
+
+/// Phases 1-5: cycle removal, layering, ordering and coordinates.
+/// With `config.straighten`, long edges become dummy chains aligned on one column.
+fn layout_nodes(
+    g: &graph::Graph,
+    config: &RenderConfig,
+    is_lr_or_rl: bool,
+    dim_overrides: &HashMap<String, (i32, i32)>,
+    pins: &NodePins,
+    ranks: &[Vec<String>],
+) -> (
+    graph::NodeLayoutList,
+    Vec<(String, String)>,
+    Vec<DummyChain>,
+) {
+    let (rebuilt, reversed, mut layers) = remove_cycles_and_layer(g, config.super_source);
+    let dag = rebuilt.as_ref().unwrap_or(g);
+    apply_layer_pins(dag, &mut layers, pins);
+    apply_same_ranks(dag, &mut layers, ranks, pins);
+
+    if !config.straighten {
+        let ordering = build_ordering(dag, &layers, pins, ordering_passes(config));
+        let nodes = assign_coordinates_rust(dag, &ordering, config, is_lr_or_rl, dim_overrides);
+        return (nodes, reversed, Vec::new());
+    }
+
+    let (aug, aug_layers, chains) = insert_dummy_nodes_rust(dag, &layers);
+    let mut overrides = dim_overrides.clone();
+    for chain in &chains {
+        for d in &chain.dummy_ids {
+            overrides.insert(d.clone(), (1, 1));
+        }
+    }
+    let ordering = build_ordering(&aug, &aug_layers, pins, ordering_passes(config));
+    let nodes = assign_coordinates_rust(&aug, &ordering, config, is_lr_or_rl, &overrides);
+    straighten_dummy_chains(&nodes, &chains);
+    (nodes, reversed, chains)
+}
+
+/// LR/RL edge label origin, in drawn coordinates: right-aligned just short of
+/// the arrowhead when the edge ends on a horizontal run it fits on (inside
+/// the layer gap), else centred on its longest horizontal run. A jogged edge
+/// ends on a one-cell run into its target, which the label would overlap.
+/// The label goes on the rows above that run, or below it when a node box is
+/// in the way above.
+/// `None` when the edge has no horizontal run.
+fn lr_label_pos(waypoints: &[(i32, i32)], label: &str, rects: &[LayoutRect]) -> Option<(i32, i32)> {
+    let w = label_width(label);
+    let (lx, y) = match waypoints[..] {
+        [.., (x0, y0), (x1, y1)] if y0 == y1 && x1 - x0 > w => {
+            (std::cmp::max(x0 + 1, x1 - 1 - w), y1)
+        }
+        _ => {
+            let (a, b) = waypoints
+                .windows(2)
+                .map(|p| (p[0], p[1]))
+                .filter(|(a, b)| a.1 == b.1 && a.0 != b.0)
+                .max_by_key(|(a, b)| (a.0 - b.0).abs())?;
+            let (lo, hi) = (a.0.min(b.0), a.0.max(b.0));
+            (std::cmp::max(lo + 1, (lo + hi - w) / 2), a.1)
+        }
+    };
+    let lines = label.lines().count() as i32;
+    // Whether rows top..top + lines are free of node boxes under the label
+    let clear = |top: i32| {
+        top >= 0
+            && !rects.iter().any(|r| {
+                r.shape != "Container"
+                    && top < r.y + r.h
+                    && top + lines > r.y
+                    && lx < r.x + r.w
+                    && lx + w > r.x
+            })
+    };
+    // The origin is the last line's row
+    if !clear(y - lines) && clear(y + 1) {
+        Some((lx, y + lines))
+    } else {
+        Some((lx, y - 1))
+    }
+}
+
+/// Run the full layout pipeline (parse → graph → layout → route).
+/// Returns clean primitives: rects + edges.
+///
+/// When `trace` is given, one [`EdgeTrace`] per routed edge is appended to it.
+fn run_layout_pipeline(
+    parsed: &parser::Graph,
+    config: &RenderConfig,
+    direction: &str,
+    pins: &LayoutPins,
+    router: &dyn EdgeRouter,
+    mut trace: Option<&mut Vec<EdgeTrace>>,
+) -> LayoutIR {
+    let relabelled = relabel_ast(parsed, config);
+    let parsed = relabelled.as_ref().unwrap_or(parsed);
+    let mut g = ast_to_graph(parsed);
+    if config.show_attrs && !config.skeleton {
+        add_attr_badges(&mut g, &node_classes(parsed), &config.charset);
+    }
+    if config.merge_mutual {
+        g = merge_mutual_edges(&g);
+    }
+    if config.summarize && g.digraph.node_count() > config.max_nodes.max(0) as usize {
+        g = summarize_leaves(&g, config.max_nodes.max(0) as usize);
+    }
+    let is_lr_or_rl = direction == "LR" || direction == "RL";
+    // LR/RL labels sit on the single row above their edge: one line each
+    if is_lr_or_rl {
+        for ed in g.digraph.edge_weights_mut() {
+            if let Some(label) = &mut ed.label {
+                *label = label.replace('\n', " ");
+            }
+        }
+    }
+
+    let subgraph_members = collect_subgraph_members(parsed);
+    let has_subgraphs = !subgraph_members.is_empty();
+
+    let (raw_nodes, raw_edges, compounds) = if has_subgraphs {
+        let subgraph_directions = collect_subgraph_directions(parsed);
+        let (collapsed, compounds, member_edges) = collapse_subgraphs(
+            &g,
+            &subgraph_members,
+            &subgraph_directions,
+            config,
+            direction,
+        );
+        let dim_overrides = compute_compound_dimensions(&compounds);
+
+        // Swimlanes take one layer each, in source order
+        let lanes = swimlane_ids(parsed, direction);
+        let mut lane_pins = pins.nodes.clone();
+        for (layer, id) in lanes.iter().enumerate() {
+            lane_pins.entry(id.clone()).or_insert((layer as i32, 0));
+        }
+        let (nodes, reversed, chains) = layout_nodes(
+            &collapsed,
+            config,
+            is_lr_or_rl,
+            &dim_overrides,
+            &lane_pins,
+            &parsed.same_ranks,
+        );
+        widen_swimlanes(&nodes, &lanes);
+
+        let expanded = expand_compound_nodes(&nodes, &compounds, is_lr_or_rl);
+        let routed = route_edges_rust(
+            &collapsed,
+            &expanded,
+            &reversed,
+            &chains,
+            &pins.columns,
+            config.max_cells,
+            router,
+            trace.as_deref_mut(),
+        );
+        let routed = attach_member_edges(&routed, &expanded, &compounds, &member_edges);
+        (expanded, routed, compounds)
+    } else {
+        let empty_overrides = HashMap::new();
+        let (nodes, reversed, chains) = layout_nodes(
+            &g,
+            config,
+            is_lr_or_rl,
+            &empty_overrides,
+            &pins.nodes,
+            &parsed.same_ranks,
+        );
+        let routed = route_edges_rust(
+            &g,
+            &nodes,
+            &reversed,
+            &chains,
+            &pins.columns,
+            config.max_cells,
+            router,
+            trace.as_deref_mut(),
+        );
+        (nodes, routed, Vec::new())
+    };
+
+    if is_lr_or_rl {
+        transpose_layout(&raw_nodes, &raw_edges);
+        if let Some(traces) = trace {
+            for t in traces.iter_mut() {
+                t.transpose();
+            }
+        }
+    }
+
+    // Convert to flat primitives
+    let classes = node_classes(parsed);
+    let styles = node_styles(parsed);
+    let compound_ids: HashSet<String> = compounds.iter().map(|c| c.compound_id.clone()).collect();
+    let mut rects = Vec::new();
+    let nn = graph::nll_len(raw_nodes.clone());
+    for i in 0..nn {
+        let id = graph::nll_get_id(raw_nodes.clone(), i);
+        if id.starts_with(DUMMY_PREFIX) {
+            continue;
+        }
+        let x = graph::nll_get_x(raw_nodes.clone(), i);
+        let y = graph::nll_get_y(raw_nodes.clone(), i);
+        let w = graph::nll_get_width(raw_nodes.clone(), i);
+        let h = graph::nll_get_height(raw_nodes.clone(), i);
+        let label = graph::nll_get_label(raw_nodes.clone(), i);
+        let shape = if compound_ids.contains(&id) {
+            "Container".to_string()
+        } else {
+            graph::nll_get_shape(raw_nodes.clone(), i)
+        };
+        let classes = classes.get(&id).cloned().unwrap_or_default();
+        let styles = styles.get(&id).cloned().unwrap_or_default();
+        rects.push(LayoutRect {
+            id: source_id(&id).to_string(),
+            x,
+            y,
+            w,
+            h,
+            label,
+            shape,
+            classes,
+            styles,
+            layer: graph::nll_get_layer(raw_nodes.clone(), i),
+        });
+    }
+    order_containers_outermost_first(&mut rects, &compounds);
+
+    let en = graph::erl_len(raw_edges.clone());
+    let mut edges = Vec::new();
+    for i in 0..en {
+        let wpc = graph::erl_get_waypoint_count(raw_edges.clone(), i);
+        let mut waypoints = Vec::new();
+        for j in 0..wpc {
+            waypoints.push((
+                graph::erl_get_waypoint_x(raw_edges.clone(), i, j),
+                graph::erl_get_waypoint_y(raw_edges.clone(), i, j),
+            ));
+        }
+        let label = graph::erl_get_label(raw_edges.clone(), i);
+        let mut label_pos = None;
+        if config.label_rows && !is_lr_or_rl {
+            reserve_label_row(&mut waypoints);
+            if let (false, Some(&(x, y))) = (label.is_empty(), waypoints.last()) {
+                let half = label_width(&label) / 2;
+                label_pos = Some((std::cmp::max(0, x - half), y - 1));
+            }
+        } else if is_lr_or_rl && !label.is_empty() {
+            label_pos = lr_label_pos(&waypoints, &label, &rects);
+        }
+        edges.push(LayoutEdge {
+            from: source_id(&graph::erl_get_from(raw_edges.clone(), i)).to_string(),
+            to: source_id(&graph::erl_get_to(raw_edges.clone(), i)).to_string(),
+            waypoints,
+            edge_type: graph::erl_get_etype(raw_edges.clone(), i),
+            label,
+            label_pos,
+            classes: graph::erl_get_classes(raw_edges.clone(), i),
+        });
+    }
+
+    LayoutIR {
+        rects,
+        edges,
+        acc_title: parsed.acc_title.clone(),
+        acc_descr: parsed.acc_descr.clone(),
+    }
+}
+
+/// Render Mermaid DSL source to geometry-based SVG.
+///
+/// Runs the full layout pipeline then calls `svg_renderer::render()`.
+/// Direction: parsed from the source header; `_direction` overrides it.
+pub fn render_svg_dsl(
+    src: &str,
+    padding: usize,
+    _direction: Option<&str>,
+) -> Result<String, RenderError> {
+    let config = config_new(true, padding as i32, _direction.map(str::to_string));
+    render_svg_with_config(src, &config)
+}
+
+/// [`render_svg_dsl`] driven by a full [`RenderConfig`]; the charset and
+/// other text-only options are ignored.
+pub fn render_svg_with_config(src: &str, config: &RenderConfig) -> Result<String, RenderError> {
+    let Some((ir, direction, config)) = layout_dsl(src, config)? else {
+        return Ok(String::new());
+    };
+    Ok(svg_renderer::render_ir_rounded(
+        &ir,
+        direction,
+        config.svg_corner_radius,
+    ))
+}
+
+/// Lay out `src` and report how every edge was routed, for debugging odd paths.
+///
+/// Routing is identical to [`render_dsl_with_config`]; nothing is drawn.
+/// Self-loops are skipped by the router and have no trace.
+pub fn trace_edges(src: &str, config: &RenderConfig) -> Vec<EdgeTrace> {
+    let (parsed, _) = prepare_ast(src);
+    let direction = config
+        .direction_override
+        .as_deref()
+        .unwrap_or(direction_str(&parsed.direction));
+    let is_lr_or_rl = direction == "LR" || direction == "RL";
+    let config = &front_matter(src).apply(config, is_lr_or_rl);
+    let mut traces = Vec::new();
+    run_layout_pipeline(
+        &parsed,
+        config,
+        direction,
+        &layout_pins(&parsed),
+        &AStarRouter,
+        Some(&mut traces),
+    );
+    traces
+}
+
+// ── WASM bindings ───────────────────────────────────────────────────────────
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn render(src: &str) -> Result<String, JsError> {
+    render_dsl(src, true, 1, None).map_err(JsError::from)
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = "renderWithOptions")]
+pub fn render_with_options(
+    src: &str,
+    unicode: bool,
+    padding: usize,
+    direction: &str,
+) -> Result<String, JsError> {
+    let dir = if direction.is_empty() {
+        None
+    } else {
+        Some(direction)
+    };
+    render_dsl(src, unicode, padding, dir).map_err(JsError::from)
+}
+
+/// Render with a JSON-encoded config; see [`config_from_json`] for the keys.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = "renderWithConfig")]
+pub fn render_with_config(src: &str, json_config: &str) -> Result<String, JsError> {
+    let config = config_from_json(json_config).map_err(JsError::from)?;
+    render_dsl_with_config(src, &config).map_err(JsError::from)
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = "renderSvg")]
+pub fn render_svg(src: &str, padding: usize, direction: &str) -> Result<String, JsError> {
+    let dir = if direction.is_empty() {
+        None
+    } else {
+        Some(direction)
+    };
+    render_svg_dsl(src, padding, dir).map_err(JsError::from)
+}
+
+// ── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(src: &str, config: &RenderConfig) -> LayoutIR {
+        let (parsed, _) = prepare_ast(src);
+        run_layout_pipeline(
+            &parsed,
+            config,
+            "TD",
+            &layout_pins(&parsed),
+            &AStarRouter,
+            None,
+        )
+    }
+
+    fn edge_with_label<'a>(ir: &'a LayoutIR, label: &str) -> &'a LayoutEdge {
+        ir.edges.iter().find(|e| e.label == label).unwrap()
+    }
+
+    // A→D spans three layers with a free column between the two side chains.
+    const LONG_EDGE: &str =
+        "graph TD\n    A --> B --> C --> D\n    A --> x --> y --> D\n    A -->|long| D\n";
+
+    #[test]
+    fn test_straighten_long_edge_single_column() {
+        let mut config = config_default();
+        config.straighten = true;
+        let ir = layout(LONG_EDGE, &config);
+        let long = edge_with_label(&ir, "long");
+        let x0 = long.waypoints[0].0;
+        assert!(
+            long.waypoints.iter().all(|&(x, _)| x == x0),
+            "{:?}",
+            long.waypoints
+        );
+        // Dummy nodes never surface as rects
+        assert_eq!(ir.rects.len(), 6);
+    }
+
+    #[test]
+    fn test_inline_redefinition_upgrades_shape() {
+        let ir = layout("graph TD\nA-->B\n B{X}\n", &config_default());
+        let b = ir.rects.iter().find(|r| r.label == "X").unwrap();
+        assert_eq!(b.shape, "Diamond");
+        assert!(!ir.rects.iter().any(|r| r.label == "B"));
+    }
+
+    #[test]
+    fn test_bare_reference_does_not_downgrade_shape() {
+        let ir = layout("graph TD\nB{X}\nA-->B\nB-->C\n", &config_default());
+        let b = ir.rects.iter().find(|r| r.label == "X").unwrap();
+        assert_eq!(b.shape, "Diamond");
+    }
+
+    #[test]
+    fn test_lr_subgraphs_render_as_swimlanes() {
+        let lanes = "graph TD\n    subgraph Intake\n        direction LR\n        A[Receive] --> B[Sort]\n    end\n    subgraph Delivery\n        direction LR\n        C[Pack] --> D[Ship] --> E[Confirm]\n    end\n    B --> C\n";
+        let ir = layout(lanes, &config_default());
+        let rect = |label: &str| ir.rects.iter().find(|r| r.label == label).unwrap();
+        let (intake, delivery) = (rect("Intake"), rect("Delivery"));
+        assert!(intake.y + intake.h <= delivery.y);
+        assert_eq!((intake.x, intake.w), (delivery.x, delivery.w));
+        for (lane, ids) in [
+            (intake, vec!["Receive", "Sort"]),
+            (delivery, vec!["Pack", "Ship", "Confirm"]),
+        ] {
+            for pair in ids.windows(2) {
+                let (a, b) = (rect(pair[0]), rect(pair[1]));
+                assert!(a.y == b.y && a.x + a.w <= b.x);
+            }
+            for id in ids {
+                let r = rect(id);
+                assert!(r.x > lane.x && r.x + r.w < lane.x + lane.w && r.y > lane.y);
+            }
+        }
+
+        // One lane without `direction LR`: ordinary subgraphs, side by side
+        let mixed = lanes
+            .replacen("        direction LR\n", "", 1)
+            .replace("    B --> C\n", "");
+        let ir = layout(&mixed, &config_default());
+        let rect = |label: &str| ir.rects.iter().find(|r| r.label == label).unwrap();
+        assert_eq!(rect("Intake").y, rect("Delivery").y);
+    }
+
+    #[test]
+    fn test_tb_subgraph_stacks_members() {
+        let src = "graph TD\n    subgraph G\n        direction TB\n        A\n        B\n    end\n    X --> G\n";
+        let ir = layout(src, &config_default());
+        let a = ir.rects.iter().find(|r| r.label == "A").unwrap();
+        let b = ir.rects.iter().find(|r| r.label == "B").unwrap();
+        assert!(a.y + a.h <= b.y);
+    }
+
+    const DUPLICATE_SUBGRAPHS: &str = "graph TD\n    subgraph G\n        A\n    end\n    subgraph G\n        B\n    end\n    A --> B\n";
+
+    #[test]
+    fn test_duplicate_subgraph_names_get_separate_containers() {
+        let ir = layout(DUPLICATE_SUBGRAPHS, &config_default());
+        let containers: Vec<&LayoutRect> =
+            ir.rects.iter().filter(|r| r.shape == "Container").collect();
+        assert_eq!(containers.len(), 2);
+        let mut titles: Vec<&str> = containers.iter().map(|r| r.label.as_str()).collect();
+        titles.sort();
+        assert_eq!(titles, ["G", "G (2)"]);
+        let (a, b) = (containers[0], containers[1]);
+        assert!(a.y + a.h <= b.y || b.y + b.h <= a.y);
+    }
+
+    #[test]
+    fn test_odd_widths_center_exit_stub() {
+        let src = "graph TD\n    AB --> C\n    AB --> Wide[Even width]\n";
+        let mut config = config_default();
+        config.odd_widths = true;
+        let ir = layout(src, &config);
+        assert!(ir.rects.iter().all(|r| r.w % 2 == 1), "{:?}", ir.rects);
+
+        // Same number of border cells either side of the stub
+        assert_eq!(
+            render_dsl_with_config(src, &config)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "        ┌─────┐",
+                "        │ AB  │",
+                "        └──┬──┘",
+                "           │",
+                "  ┌────────┴────┐",
+                "  ▼             ▼",
+                "┌───┐    ┌─────────────┐",
+                "│ C │    │ Even width  │",
+                "└───┘    └─────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_odd_widths_lr_rounds_heights() {
+        // Edges leave LR boxes through their sides, so heights are rounded
+        let src = "graph LR\n    A[\"two\\nlines\"] --> B\n    A --> Even\n";
+        let mut config = config_default();
+        config.odd_widths = true;
+        let ir = layout(src, &config);
+        let a = ir.rects.iter().find(|r| r.label == "two\nlines").unwrap();
+        assert_eq!(a.h, 5);
+        assert!(ir.rects.iter().all(|r| r.h % 2 == 1), "{:?}", ir.rects);
+        let even = ir.rects.iter().find(|r| r.label == "Even").unwrap();
+        assert_eq!(even.w, 8);
+
+        assert_eq!(
+            render_dsl_with_config(src, &config)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "               ┌───┐",
+                "┌───────┐    ┌►│ B │",
+                "│  two  │    │ └───┘",
+                "│ lines ├────┤",
+                "│       │    │ ┌──────┐",
+                "└───────┘    └►│ Even │",
+                "               └──────┘",
+            ]
+        );
+    }
+
+    /// Number of nodes on each layer, top to bottom.
+    fn layer_sizes(layers: &HashMap<String, i32>) -> Vec<usize> {
+        let max = layers.values().max().copied().unwrap_or(0);
+        (0..=max)
+            .map(|l| layers.values().filter(|&&v| v == l).count())
+            .collect()
+    }
+
+    #[test]
+    fn test_super_source_balances_multi_root_layers() {
+        let src = "graph TD\n    A --> B --> C --> D\n    X --> D\n    Y --> C\n";
+        let (parsed, _) = prepare_ast(src);
+        let g = ast_to_graph(&parsed);
+
+        let plain = assign_layers_rust(&g);
+        assert_eq!(layer_sizes(&plain), [3, 1, 1, 1]);
+
+        let balanced = assign_layers_super_source(&g);
+        assert!(!balanced.contains_key(SUPER_SOURCE_ID));
+        assert_eq!(layer_sizes(&balanced), [1, 2, 2, 1]);
+        assert_eq!(balanced["X"], 2);
+        assert_eq!(balanced["Y"], 1);
+
+        let mut config = config_default();
+        config.super_source = true;
+        let ir = layout(src, &config);
+        assert_eq!(ir.rects.len(), 6);
+    }
+
+    #[test]
+    fn test_label_rows_reserve_blank_row() {
+        let src = "graph TD\n    A -->|yes| B\n    A --> C\n    C -->|no| D\n";
+        let mut config = config_default();
+        config.label_rows = true;
+        let ir = layout(src, &config);
+        let plain = layout(src, &config_default());
+        let b = |ir: &LayoutIR| ir.rects.iter().find(|r| r.label == "B").unwrap().y;
+        assert_eq!(b(&ir), b(&plain) + 1);
+
+        // No horizontal line glyphs share a label's row
+        assert_eq!(
+            render_dsl_with_config(src, &config)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "    ┌───┐",
+                "    │ A │",
+                "    └─┬─┘",
+                "      │",
+                "  ┌───┴────┐",
+                "  │        │",
+                " yes       │",
+                "  ▼        ▼",
+                "┌───┐    ┌───┐",
+                "│ B │    │ C │",
+                "└───┘    └─┬─┘",
+                "           │",
+                "           │",
+                "      ┌────┘",
+                "     no",
+                "      ▼",
+                "    ┌───┐",
+                "    │ D │",
+                "    └───┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trace_simple_edge_endpoints() {
+        let traces = trace_edges("graph TD\n    A --> B\n", &config_default());
+        let ir = layout("graph TD\n    A --> B\n", &config_default());
+        assert_eq!(traces.len(), 1);
+        let t = &traces[0];
+        let (a, b) = (&ir.rects[0], &ir.rects[1]);
+        assert_eq!((t.from.as_str(), t.to.as_str()), ("A", "B"));
+        assert_eq!(t.exit, (a.x + a.w / 2, a.y + a.h));
+        assert_eq!(t.entry, (b.x + b.w / 2, b.y - 1));
+        assert_eq!(t.method, RouteMethod::AStar);
+        assert_eq!(t.waypoints, ir.edges[0].waypoints);
+    }
+
+    #[test]
+    fn test_trace_reports_fallback_l_path() {
+        // X sits on A's exit cell, so A* has nowhere to go.
+        let mut g = graph::graph_new();
+        graph::graph_add_edge(&mut g, "A", "B", "Arrow", None);
+        let nodes = graph::nll_new();
+        for (id, x, y) in [("A", 0, 0), ("X", 0, 3), ("B", 10, 10)] {
+            let (id, shape) = (id.to_string(), "Rectangle".to_string());
+            graph::nll_push(nodes.clone(), id.clone(), 0, 0, x, y, 3, 3, id, shape);
+        }
+        let mut traces = Vec::new();
+        route_edges_rust(
+            &g,
+            &nodes,
+            &[],
+            &[],
+            &EdgeColumns::new(),
+            i32::MAX,
+            &AStarRouter,
+            Some(&mut traces),
+        );
+        let t = &traces[0];
+        assert_eq!(t.method, RouteMethod::Fallback);
+        assert_eq!((t.exit, t.entry), ((1, 3), (11, 9)));
+        assert_eq!(t.waypoints, vec![(1, 3), (1, 6), (11, 6), (11, 9)]);
+    }
+
+    const FAN_OUT: &str = "graph TD\n    A --> B\n    A --> C\n";
+
+    #[test]
+    fn test_split_front_matter() {
+        let src = "---\ntitle: x\n---\ngraph TD\n    A\n";
+        assert_eq!(
+            split_front_matter(src),
+            (Some("title: x\n"), "graph TD\n    A\n")
+        );
+        assert_eq!(split_front_matter(FAN_OUT), (None, FAN_OUT));
+        let (parsed, _) = prepare_ast(src);
+        assert_eq!(parsed.nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_dag_fast_path_matches_full_cycle_removal() {
+        let layers_of = |pairs: &[(&str, i32)]| -> HashMap<String, i32> {
+            pairs
+                .iter()
+                .map(|&(id, layer)| (id.to_string(), layer))
+                .collect()
+        };
+
+        // A DAG takes the fast path: no rebuilt graph, nothing reversed, and
+        // the same layers the full back-edge search would give
+        let g = ast_to_graph(&prepare_ast(LONG_EDGE).0);
+        let (rebuilt, reversed, layers) = remove_cycles_and_layer(&g, false);
+        assert!(rebuilt.is_none());
+        assert!(reversed.is_empty());
+        let expected = layers_of(&[("A", 0), ("B", 1), ("C", 2), ("D", 3), ("x", 1), ("y", 2)]);
+        assert_eq!(layers, expected);
+        let (full, full_reversed) = reverse_back_edges(&g);
+        assert!(full_reversed.is_empty());
+        assert_eq!(assign_layers_rust(&full), expected);
+
+        // A cycle still goes through the back-edge search
+        let g = ast_to_graph(&prepare_ast("graph TD\n    A --> B --> A\n").0);
+        let (rebuilt, reversed, layers) = remove_cycles_and_layer(&g, false);
+        assert!(rebuilt.is_some());
+        assert_eq!(reversed, [("B".to_string(), "A".to_string())]);
+        assert_eq!(layers, layers_of(&[("A", 0), ("B", 1)]));
+    }
+
+    #[test]
+    fn test_edge_into_diamond_lands_on_top_vertex() {
+        let src = "graph TD\n    A --> B{Decide}\n";
+        let ir = layout(src, &config_default());
+        let d = ir.rects.iter().find(|r| r.shape == "Diamond").unwrap();
+        assert_eq!(ir.edges[0].waypoints.last(), Some(&(d.x + d.w / 2, d.y)));
+        assert_eq!(
+            render_dsl(src, true, 1, None)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "  ┌───┐",
+                "  │ A │",
+                "  └─┬─┘",
+                "    │",
+                "    └┐",
+                "     │",
+                "/────▼───\\",
+                "│ Decide │",
+                "\\────────/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_same_layer_edge_detours_below_middle_node() {
+        let mut g = graph::graph_new();
+        graph::graph_add_edge(&mut g, "A", "C", "Arrow", None);
+        let nodes = graph::nll_new();
+        for (id, x) in [("A", 0), ("B", 7), ("C", 14)] {
+            let (id, shape) = (id.to_string(), "Rectangle".to_string());
+            graph::nll_push(nodes.clone(), id.clone(), 0, 0, x, 0, 5, 3, id, shape);
+        }
+        let mut traces = Vec::new();
+        route_edges_rust(
+            &g,
+            &nodes,
+            &[],
+            &[],
+            &EdgeColumns::new(),
+            i32::MAX,
+            &AStarRouter,
+            Some(&mut traces),
+        );
+        let t = &traces[0];
+        assert_eq!(t.method, RouteMethod::SameLayer);
+        assert_eq!(t.waypoints, vec![(2, 3), (2, 4), (16, 4), (16, 3)]);
+        // No segment enters B's box (x 7..12, y 0..3)
+        for pair in t.waypoints.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            let crosses_b = x0.max(x1) >= 7 && x0.min(x1) < 12 && y0.min(y1) < 3;
+            assert!(!crosses_b, "{:?}", t.waypoints);
+        }
+    }
+
+    #[test]
+    fn test_top_level_definition_keeps_subgraph_membership() {
+        let defined_first = "graph TD\n    A[Label]\n    subgraph G\n        A\n    end\n";
+        let listed_first = "graph TD\n    subgraph G\n        A\n    end\n    A[Label] --> B\n";
+        for src in [defined_first, listed_first] {
+            let g = ast_to_graph(&prepare_ast(src).0);
+            let a = &g.digraph[g.node_index["A"]];
+            assert_eq!(
+                (a.label.as_str(), a.shape.as_str(), a.subgraph.as_deref()),
+                ("Label", "Rectangle", Some("G")),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_class_statement_assigns_emphasis() {
+        let src =
+            "graph TD\n    A --> B\n    classDef emphasis stroke-width:4px\n    class B emphasis\n";
+        assert_eq!(
+            render_dsl(src, true, 1, None)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌───┐",
+                "│ A │",
+                "└─┬─┘",
+                "  │",
+                "  │",
+                "  ▼",
+                "┏━━━┓",
+                "┃ B ┃",
+                "┗━━━┛",
+            ]
+        );
+        let ir = layout(src, &config_default());
+        assert_eq!(ir.rects.len(), 2, "classDef must not create nodes");
+        let b = ir.rects.iter().find(|r| r.label == "B").unwrap();
+        assert_eq!(b.classes, vec!["emphasis".to_string()]);
+    }
+
+    #[test]
+    fn test_style_statement_sets_border_and_fill() {
+        // Declared before the node exists
+        let src = "graph TD\n    style A fill:#f00,stroke-width:4px\n    A --> B\n    style B stroke-width:1px\n    style Z fill:#0f0\n";
+        assert_eq!(
+            render_dsl(src, true, 1, None)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┏━━━┓",
+                "┃ A ┃",
+                "┗━┯━┛",
+                "  │",
+                "  │",
+                "  ▼",
+                "┌───┐",
+                "│ B │",
+                "└───┘",
+            ]
+        );
+        let ir = layout(src, &config_default());
+        assert_eq!(ir.rects.len(), 2, "style must not create nodes");
+        let a = ir.rects.iter().find(|r| r.label == "A").unwrap();
+        let pair = |p: &str, v: &str| (p.to_string(), v.to_string());
+        assert_eq!(
+            a.styles,
+            vec![pair("fill", "#f00"), pair("stroke-width", "4px")]
+        );
+        let svg = render_svg_dsl(src, 1, None).unwrap();
+        assert!(
+            svg.contains(r#"style="fill:#f00;stroke-width:4px""#),
+            "{svg}"
+        );
+        assert!(graph_stats(src).warnings.is_empty());
+        assert_eq!(
+            format_dsl(src).unwrap(),
+            "graph TD\n    A --> B\n    style A fill:#f00,stroke-width:4px\n    style B stroke-width:1px\n    style Z fill:#0f0\n"
+        );
+    }
+
+    #[test]
+    fn test_single_node_uses_small_canvas() {
+        let ir = layout("graph TD\n    A\n", &config_default());
+        let (w, h) = ir.canvas_size();
+        assert!(
+            w < 40 && h < 10,
+            "canvas {w}x{h} inflated by an implicit minimum"
+        );
+        for direction in [None, Some("RL")] {
+            let mut config = config_default();
+            config.direction_override = direction.map(|d| d.to_string());
+            let out = render_dsl_with_config("graph TD\n    A\n", &config).unwrap();
+            assert_eq!(out, "┌───┐\n│ A │\n└───┘\n", "{direction:?}");
+        }
+        // A larger minimum only adds blank space, which is trimmed
+        let mut config = config_default();
+        config.min_canvas_width = 80;
+        config.min_canvas_height = 20;
+        assert_eq!(
+            render_dsl_with_config("graph TD\n    A\n", &config).unwrap(),
+            "┌───┐\n│ A │\n└───┘\n"
+        );
+    }
+
+    #[test]
+    fn test_nested_subgraph_borders_paint_outermost_first() {
+        let src = "graph TD\n    subgraph Outer\n        A --> B\n        subgraph Inner\n            C\n        end\n    end\n    B --> C\n";
+        let ir = layout(src, &config_default());
+        let containers: Vec<&str> = ir
+            .rects
+            .iter()
+            .filter(|r| r.shape == "Container")
+            .map(|r| r.label.as_str())
+            .collect();
+        assert_eq!(containers, vec!["Outer", "Inner"]);
+
+        // Inner's title row blocks the way down to C, so the B→C edge ends
+        // on Inner's border; A→B stays inside Outer and is not drawn.
+        assert_eq!(
+            render_dsl(src, true, 1, None)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌─────────────┐",
+                "│    Outer    │",
+                "│ ┌───┐ ┌───┐ │",
+                "│ │ A │ │ B │ │",
+                "│ └───┘ └─┬─┘ │",
+                "└─────────┼───┘",
+                "       ┌──┘",
+                "       │",
+                "       ▼",
+                " ┌───────────┐",
+                " │   Inner   │",
+                " │   ┌───┐   │",
+                " │   │ C │   │",
+                " │   └───┘   │",
+                " └───────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flowchart_variant_headers() {
+        for header in ["flowchart-elk LR", "flowchart-v2 LR"] {
+            let src = format!("{header}\n    A --> B\n");
+            let (parsed, _) = prepare_ast(&src);
+            assert_eq!(direction_str(&parsed.direction), "LR", "{header}");
+            assert_eq!(
+                render_dsl(&src, true, 1, None).unwrap(),
+                "┌───┐      ┌───┐\n│ A ├─────►│ B │\n└───┘      └───┘\n"
+            );
+        }
+        // Without the hyphen it is a node id, not a header
+        let (parsed, _) = prepare_ast("flowchartX --> B\n");
+        assert_eq!(parsed.nodes[0].id, "flowchartX");
+    }
+
+    #[test]
+    fn test_nodes_on_top_keeps_borders_under_edge_labels() {
+        // A label that runs over the next box's top border
+        let rect = |x: i32, label: &str| LayoutRect {
+            id: label.to_string(),
+            x,
+            y: 1,
+            w: 5,
+            h: 3,
+            label: label.to_string(),
+            shape: "Rectangle".to_string(),
+            classes: Vec::new(),
+            styles: Vec::new(),
+            layer: 0,
+        };
+        let ir = LayoutIR {
+            rects: vec![rect(0, "A"), rect(9, "B")],
+            edges: vec![LayoutEdge {
+                from: "A".to_string(),
+                to: "B".to_string(),
+                waypoints: vec![(4, 2), (8, 2)],
+                edge_type: "Arrow".to_string(),
+                label: "some long label".to_string(),
+                label_pos: Some((5, 1)),
+                classes: Vec::new(),
+            }],
+            acc_title: String::new(),
+            acc_descr: String::new(),
+        };
+        let border_cells = |order: DrawOrder| {
+            let mut config = config_default();
+            config.draw_order = order;
+            let c = paint_ir(&ir, &config).unwrap();
+            ir.rects
+                .iter()
+                .flat_map(|r| (r.x..r.x + r.w).map(move |x| (x, r.y)))
+                .map(|(x, y)| cget(&c, x, y))
+                .collect::<Vec<String>>()
+        };
+        // The labels overflow onto the box tops by default...
+        assert!(
+            border_cells(DrawOrder::EdgesOnTop)
+                .iter()
+                .any(|ch| !"┌─┐┬".contains(ch.as_str()))
+        );
+        // ...but never replace a border cell with NodesOnTop
+        for ch in border_cells(DrawOrder::NodesOnTop) {
+            assert!(
+                "┌─┐┬".contains(ch.as_str()),
+                "border cell replaced by {ch:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_percent_signs_in_labels_are_literal() {
+        let (parsed, _) = prepare_ast(
+            "graph TD\n    A[50%% off] --> B[\"50%% done\"]\n    C[ %% x] --> D{%%}\n    D --> E %% trailing comment\n",
+        );
+        let label = |id: &str| {
+            parsed
+                .nodes
+                .iter()
+                .find(|n| n.id == id)
+                .unwrap()
+                .label
+                .clone()
+        };
+        assert_eq!(label("A"), "50%% off");
+        assert_eq!(label("B"), "50%% done");
+        assert_eq!(label("C"), "%% x");
+        assert_eq!(label("D"), "%%");
+        assert_eq!(label("E"), "E");
+        assert_eq!(parsed.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_edges_attach_to_subgraph_members() {
+        let src = "graph TD\nsubgraph S\nA\nB\nC\nend\nX --> C\nA --> Y\n";
+        let ir = layout(src, &config_default());
+        let rect = |label: &str| ir.rects.iter().find(|r| r.label == label).unwrap();
+        let (a, c) = (rect("A"), rect("C"));
+        let into_c = ir.edges.iter().find(|e| e.waypoints[0].1 < c.y).unwrap();
+        assert_eq!(*into_c.waypoints.last().unwrap(), (c.x + c.w / 2, c.y - 1));
+        let out_of_a = ir.edges.iter().find(|e| e.waypoints[0].1 > c.y).unwrap();
+        assert_eq!(out_of_a.waypoints[0], (a.x + a.w / 2, a.y + a.h));
+    }
+
+    #[test]
+    fn test_end_as_node_id() {
+        let parsed = rust_parser::parse_flowchart("graph TD\n    end[Finish] --> B\n");
+        assert_eq!(parsed.nodes[0].id, "end");
+        assert_eq!(parsed.nodes[0].label, "Finish");
+        assert_eq!(parsed.edges.len(), 1);
+
+        let src = "graph TD\n    subgraph S\n        A --> end[Finish]\n    end\n    end --> C\n";
+        let parsed = rust_parser::parse_flowchart(src);
+        let sg = &parsed.subgraphs[0];
+        let ids: Vec<&str> = sg.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["A", "end"]);
+        assert_eq!(sg.nodes[1].label, "Finish");
+        // The bare `end` closed S; the next line is a top-level edge.
+        assert_eq!(parsed.edges.len(), 1);
+        assert_eq!(
+            (
+                parsed.edges[0].from_id.as_str(),
+                parsed.edges[0].to_id.as_str()
+            ),
+            ("end", "C")
+        );
+    }
+
+    #[test]
+    fn test_lr_labels_sit_beside_their_horizontal_run() {
+        let src = "graph LR\n    A -->|go| B\n    A -->|a longer label| C\n    B --> D\n    C -->|done| D\n";
+        let (ir, _, _) = layout_dsl(src, &config_default()).unwrap().unwrap();
+        for e in ir.edges.iter().filter(|e| !e.label.is_empty()) {
+            let (lx, ly) = e.label_pos.unwrap();
+            let w = label_width(&e.label);
+            assert!(
+                !ir.rects
+                    .iter()
+                    .any(|r| ly >= r.y && ly < r.y + r.h && lx < r.x + r.w && lx + w > r.x),
+                "label {:?} overlaps a node",
+                e.label
+            );
+            let beside_run = e.waypoints.windows(2).any(|p| {
+                let ((x0, y0), (x1, y1)) = (p[0], p[1]);
+                y0 == y1 && (y0 - ly).abs() == 1 && x0.min(x1) <= lx && lx <= x0.max(x1)
+            });
+            assert!(
+                beside_run,
+                "label {:?} at {:?} off its edge",
+                e.label,
+                (lx, ly)
+            );
+        }
+        assert_eq!(
+            render_dsl(src, true, 1, None)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "                  go  ┌───┐",
+                "      ┌──────────────►│ B ├─────┐",
+                "┌───┐ │               └───┘     │ ┌───┐",
+                "│ A ├─┤                         ├►│ D │",
+                "└───┘ │a longer label ┌───┐ done│ └───┘",
+                "      └──────────────►│ C ├─────┘",
+                "                      └───┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lr_uses_its_own_default_gaps() {
+        // Blank cells between A and B along the flow, plus between B and C
+        // across it (siblings of one rank).
+        let gaps = |header: &str, config: &RenderConfig| {
+            let src = format!("{header}\n    A --> B\n    A --> C\n");
+            let (ir, _, _) = layout_dsl(&src, config).unwrap().unwrap();
+            let rect = |label: &str| ir.rects.iter().find(|r| r.label == label).unwrap();
+            let (a, b, c) = (rect("A"), rect("B"), rect("C"));
+            if header.ends_with("LR") {
+                (b.x - (a.x + a.w), c.y - (b.y + b.h))
+            } else {
+                (b.y - (a.y + a.h), c.x - (b.x + b.w))
+            }
+        };
+        let defaults = config_default();
+        assert_eq!(
+            gaps("graph TD", &defaults),
+            (TD_DEFAULT_GAPS.1, TD_DEFAULT_GAPS.0)
+        );
+        assert_eq!(gaps("graph LR", &defaults), LR_DEFAULT_GAPS);
+
+        // Explicit gaps are kept in LR too, even when they equal TD's defaults.
+        let mut config = config_default();
+        config.h_gap = Some(8);
+        config.v_gap = Some(5);
+        assert_eq!(gaps("graph LR", &config), (8, 5));
+        config.h_gap = Some(TD_DEFAULT_GAPS.0);
+        config.v_gap = Some(TD_DEFAULT_GAPS.1);
+        assert_eq!(gaps("graph LR", &config), TD_DEFAULT_GAPS);
+    }
+
+    #[test]
+    fn test_dotted_node_ids() {
+        let parsed =
+            rust_parser::parse_flowchart("graph TD\n    a.b --> c.d\n    x.y[\"X\"] -.-> z\n");
+        let ids: Vec<&str> = parsed.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["a.b", "c.d", "x.y", "z"]);
+        assert_eq!(parsed.nodes[2].label, "X");
+        let edges: Vec<(&str, &str)> = parsed
+            .edges
+            .iter()
+            .map(|e| (e.from_id.as_str(), e.to_id.as_str()))
+            .collect();
+        assert_eq!(edges, vec![("a.b", "c.d"), ("x.y", "z")]);
+        assert_eq!(parsed.edges[1].edge_type, parser::EdgeType::DottedArrow);
+
+        // A dot before a connector still belongs to the connector.
+        let parsed = rust_parser::parse_flowchart("graph TD\n    A-.->B\n");
+        assert_eq!(parsed.edges[0].from_id, "A");
+        assert_eq!(parsed.edges[0].edge_type, parser::EdgeType::DottedArrow);
+    }
+
+    #[test]
+    fn test_oversized_canvas_is_an_error() {
+        let rect = |x: i32, y: i32| LayoutRect {
+            id: "A".to_string(),
+            x,
+            y,
+            w: 5,
+            h: 3,
+            label: "A".to_string(),
+            shape: "Rectangle".to_string(),
+            classes: Vec::new(),
+            styles: Vec::new(),
+            layer: 0,
+        };
+        let ir = LayoutIR {
+            rects: vec![rect(0, 0), rect(i32::MAX - 3, i32::MAX - 1)],
+            edges: vec![LayoutEdge {
+                from: "A".to_string(),
+                to: "A".to_string(),
+                waypoints: vec![(2, 3), (i32::MAX - 1, i32::MAX - 2)],
+                edge_type: "Arrow".to_string(),
+                label: String::new(),
+                label_pos: None,
+                classes: Vec::new(),
+            }],
+            acc_title: String::new(),
+            acc_descr: String::new(),
+        };
+        let err = paint_ir(&ir, &config_default()).err().unwrap();
+        assert!(matches!(err, RenderError::LimitExceeded(_)), "{err}");
+        assert!(err.to_string().starts_with("diagram too large"), "{err}");
+
+        let mut config = config_default();
+        config.max_cells = 50;
+        let err = render_dsl_with_config("graph TD\n    A --> B\n", &config).unwrap_err();
+        assert_eq!(
+            err,
+            RenderError::LimitExceeded(
+                "diagram too large: 7x13 canvas exceeds max_cells (50)".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_tab_indented_subgraph_body() {
+        let src = "graph TD\nsubgraph S\n\tA --> B\n\t\n\t%% note\n \t C[x]\t\r\n\t\tD\n\tend\n";
+        assert_eq!(check_dsl(src), Ok(()));
+        let parsed = rust_parser::parse_flowchart(src);
+        let sg = &parsed.subgraphs[0];
+        let ids: Vec<&str> = sg.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["A", "B", "C", "D"]);
+        assert_eq!(sg.nodes[2].label, "x");
+        assert_eq!(sg.edges.len(), 1);
+    }
+
+    #[test]
+    fn test_balanced_brackets_in_labels() {
+        let parsed = rust_parser::parse_flowchart(
+            "graph TD\n    A[arr[0]] --> B(f(x))\n    C{a{b}c} --> D((g(h)))\n",
+        );
+        let labels: Vec<(&str, &str)> = parsed
+            .nodes
+            .iter()
+            .map(|n| (n.id.as_str(), n.label.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("A", "arr[0]"),
+                ("B", "f(x)"),
+                ("C", "a{b}c"),
+                ("D", "g(h)")
+            ]
+        );
+        assert_eq!(parsed.edges.len(), 2);
+        assert_eq!(parsed.nodes[3].shape, parser::NodeShape::Circle);
+    }
+
+    #[test]
+    fn test_circle_grows_taller_with_width() {
+        let config = config_default();
+        let ir = layout("graph TD\n    A[Wide label] --> B((Wide label))\n", &config);
+        let (rect, circle) = (&ir.rects[0], &ir.rects[1]);
+        assert_eq!(rect.w, circle.w);
+        assert!(circle.h > rect.h, "{} vs {}", circle.h, rect.h);
+        assert_eq!(circle.h, (circle.w + 1) / 2);
+
+        // Label sits on the middle row
+        assert_eq!(
+            render_dsl_with_config("graph TD\n    A((Wide label))\n", &config)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                " ╭──────────╮",
+                "(            )",
+                "(            )",
+                "( Wide label )",
+                "(            )",
+                "(            )",
+                " ╰──────────╯",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reversed_edge_chain() {
+        let edge_set = |src: &str| {
+            let g = rust_parser::parse_flowchart(src);
+            let mut set: Vec<(String, String)> = g
+                .edges
+                .iter()
+                .map(|e| (e.from_id.clone(), e.to_id.clone()))
+                .collect();
+            set.sort();
+            set
+        };
+        let forward = "graph TD\n    A --> B --> C --> D\n";
+        let backward = "graph TD\n    D <-- C <-- B <-- A\n";
+        assert_eq!(edge_set(backward), edge_set(forward));
+        let mixed = edge_set("graph TD\n    B <-.- A ==> C <== D\n");
+        let expect = [("A", "B"), ("A", "C"), ("D", "C")];
+        assert_eq!(mixed, expect.map(|(a, b)| (a.to_string(), b.to_string())));
+        // `<-->` is still bidirectional, not a reversed `<--`
+        let bidir = rust_parser::parse_flowchart("graph TD\n    A <--> B\n");
+        assert_eq!(bidir.edges[0].edge_type, parser::EdgeType::BidirArrow);
+
+        let config = config_default();
+        let render = |src| render_dsl_with_config(src, &config).unwrap();
+        assert_eq!(render(backward), render(forward));
+    }
+
+    #[test]
+    fn test_subgraph_margins() {
+        let src = "graph TD\n    subgraph S\n        A\n    end\n";
+        let gap = |mx: i32, my: i32| {
+            let mut config = config_default();
+            config.subgraph_margin_x = mx;
+            config.subgraph_margin_y = my;
+            let ir = layout(src, &config);
+            let sg = ir.rects.iter().find(|r| r.shape == "Container").unwrap();
+            let a = ir.rects.iter().find(|r| r.label == "A").unwrap();
+            // Members stay inside the border whatever the margins
+            assert!(a.x > sg.x && a.x + a.w < sg.x + sg.w, "{mx},{my}");
+            assert!(a.y > sg.y + 1 && a.y + a.h < sg.y + sg.h, "{mx},{my}");
+            let out = render_dsl_with_config(src, &config).unwrap();
+            ((a.x - sg.x, sg.y + sg.h - (a.y + a.h)), out)
+        };
+        let ((x0, y0), out) = gap(0, 0);
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            [
+                "┌─────┐",
+                "│  S  │",
+                "│┌───┐│",
+                "││ A ││",
+                "│└───┘│",
+                "└─────┘"
+            ]
+        );
+        let ((x1, y1), out) = gap(1, 0);
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            [
+                "┌───────┐",
+                "│   S   │",
+                "│ ┌───┐ │",
+                "│ │ A │ │",
+                "│ └───┘ │",
+                "└───────┘",
+            ]
+        );
+        let ((x3, y2), out) = gap(3, 2);
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            [
+                "┌───────────┐",
+                "│     S     │",
+                "│           │",
+                "│           │",
+                "│   ┌───┐   │",
+                "│   │ A │   │",
+                "│   └───┘   │",
+                "│           │",
+                "│           │",
+                "└───────────┘",
+            ]
+        );
+        assert_eq!((x0, y0), (1, 1));
+        assert!(x0 < x1 && x1 < x3, "{x0} {x1} {x3}");
+        assert_eq!(y1, 1);
+        assert_eq!(y2, 3);
+    }
+
+    #[test]
+    fn test_back_edge_leaves_through_top_border() {
+        // B has a forward edge down to C and a back edge up to A
+        let src = "graph TD\n    A --> B\n    B --> C\n    B --> A\n";
+        let ir = layout(src, &config_default());
+        let b = ir.rects.iter().find(|r| r.label == "B").unwrap();
+        let traces = trace_edges(src, &config_default());
+        let edge = |from: &str, to: &str| {
+            traces
+                .iter()
+                .find(|t| t.from == from && t.to == to)
+                .unwrap()
+        };
+        assert_eq!(edge("B", "C").exit.1, b.y + b.h);
+        assert_eq!(edge("B", "A").exit.1, b.y - 1);
+        // Into A's bottom border, beside the column A --> B leaves from
+        let (forward, back) = (edge("A", "B").exit, edge("B", "A").entry);
+        assert_eq!(back.1, forward.1);
+        assert_ne!(back.0, forward.0);
+
+        assert_eq!(
+            render_dsl_with_config(src, &config_default())
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌───┐",
+                "│ A │",
+                "└─┬─┘",
+                "  │▲",
+                "  ││",
+                "  ▼│",
+                "┌──┴┐",
+                "│ B │",
+                "└─┬─┘",
+                "  │",
+                "  │",
+                "  ▼",
+                "┌───┐",
+                "│ C │",
+                "└───┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_mutual_edges() {
+        let src = "graph TD\n    A --> B\n    B --> A\n";
+        assert_eq!(layout(src, &config_default()).edges.len(), 2);
+
+        let mut config = config_default();
+        config.merge_mutual = true;
+        let ir = layout(src, &config);
+        assert_eq!(ir.edges.len(), 1);
+        assert_eq!(ir.edges[0].edge_type, "BidirArrow");
+        assert_eq!(
+            render_dsl_with_config(src, &config)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌───┐",
+                "│ A │",
+                "└─┬─┘",
+                "  ▲",
+                "  │",
+                "  ▼",
+                "┌───┐",
+                "│ B │",
+                "└───┘",
+            ]
+        );
+
+        // Different strokes, or two labels, stay separate edges
+        for src in [
+            "graph TD\n    A --> B\n    B -.-> A\n",
+            "graph TD\n    A -->|go| B\n    B -->|back| A\n",
+        ] {
+            assert_eq!(layout(src, &config).edges.len(), 2, "{src}");
+        }
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        for (text, want) in [
+            ("a &amp; b", "a & b"),
+            ("&lt;tag&gt;", "<tag>"),
+            ("say &quot;hi&quot;", "say \"hi\""),
+            ("it&apos;s", "it's"),
+            ("it&#39;s", "it's"),
+            ("it&#x27;s &#X41;", "it's A"),
+            // Unknown or malformed entities stay as written
+            (
+                "&nbsp; &foo; &#xZZ; &#1114112; & ;",
+                "&nbsp; &foo; &#xZZ; &#1114112; & ;",
+            ),
+            ("AT&T", "AT&T"),
+            ("&amp;amp;", "&amp;"),
+        ] {
+            assert_eq!(decode_entities(text), want, "{text}");
+        }
+
+        let src = "graph TD\n    A[\"a &amp; b\"] -->|&lt;go&gt;| B\n";
+        assert_eq!(
+            render_dsl_with_config(src, &config_default())
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌───────────┐",
+                "│ a &amp; b │",
+                "└─────┬─────┘",
+                "      │",
+                "      │",
+                "      &lt;go&gt;",
+                "      ▼",
+                "    ┌───┐",
+                "    │ B │",
+                "    └───┘",
+            ]
+        );
+        let mut config = config_default();
+        config.decode_entities = true;
+        assert_eq!(
+            render_dsl_with_config(src, &config)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌───────┐",
+                "│ a & b │",
+                "└───┬───┘",
+                "    │",
+                "    │",
+                "    <go>",
+                "    ▼",
+                "  ┌───┐",
+                "  │ B │",
+                "  └───┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlap_warnings() {
+        let src = "graph TD\n    A --> B\n    A --> C\n    subgraph S\n        C --> D\n    end\n";
+        let mut config = config_default();
+        config.assert_no_overlap = true;
+        assert!(layout(src, &config).overlap_warnings().is_empty());
+        assert_eq!(
+            overlap_warnings(src, &config).unwrap(),
+            Vec::<String>::new()
+        );
+
+        let rect = |label: &str, x: i32, shape: &str| LayoutRect {
+            id: label.to_string(),
+            x,
+            y: 0,
+            w: 5,
+            h: 3,
+            label: label.to_string(),
+            shape: shape.to_string(),
+            classes: Vec::new(),
+            styles: Vec::new(),
+            layer: 0,
+        };
+        let ir = LayoutIR {
+            rects: vec![
+                rect("A", 0, "Rectangle"),
+                rect("B", 4, "Rounded"),
+                rect("C", 9, "Rectangle"),
+                rect("S", 0, "Container"),
+            ],
+            edges: Vec::new(),
+            acc_title: String::new(),
+            acc_descr: String::new(),
+        };
+        assert_eq!(
+            ir.overlap_warnings(),
+            vec!["node boxes 'A' at (0, 0) and 'B' at (4, 0) overlap".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_pos_pins_layer_and_order() {
+        let src = "graph TD\n    A --> B\n    A --> C\n    C --> D\n";
+        let rect = |ir: &LayoutIR, label: &str| {
+            let r = ir.rects.iter().find(|r| r.label == label).unwrap();
+            (r.layer, r.x)
+        };
+        let ir = layout(src, &config_default());
+        assert_eq!(rect(&ir, "D").0, 2);
+        assert!(rect(&ir, "B").1 < rect(&ir, "C").1);
+
+        let pinned = format!("{src}    %% pos C 1 0\n    %% pos D 3 0\n    %% pos B x 1\n");
+        let ir = layout(&pinned, &config_default());
+        assert_eq!(rect(&ir, "D").0, 3);
+        assert_eq!(rect(&ir, "C").0, 1);
+        assert!(rect(&ir, "C").1 < rect(&ir, "B").1);
+        // Malformed pins are ignored, and the directives draw nothing
+        assert_eq!(node_pins(&prepare_ast(&pinned).0).len(), 2);
+        assert_eq!(
+            render_dsl_with_config("graph TD\n    A\n    %% pos A 0 0\n", &config_default())
+                .unwrap(),
+            "┌───┐\n│ A │\n└───┘\n"
+        );
+    }
+
+    #[test]
+    fn test_route_directive_pins_edge_column() {
+        let src = "graph TD\n    A --> B\n    %% route A B col=10\n    %% route A B col=x\n";
+        let mut config = config_default();
+        config.v_gap = Some(6);
+        let ir = layout(src, &config);
+        let (a, b) = (&ir.rects[0], &ir.rects[1]);
+        let (exit_y, entry_y) = (a.y + a.h, b.y - 1);
+        let x = a.x + a.w / 2;
+        assert_eq!(
+            ir.edges[0].waypoints,
+            vec![
+                (x, exit_y),
+                (x, exit_y + 1),
+                (10, exit_y + 1),
+                (10, entry_y - 1),
+                (x, entry_y - 1),
+                (x, entry_y),
+            ]
+        );
+        let traces = trace_edges(src, &config);
+        assert_eq!(traces[0].method, RouteMethod::Pinned);
+        assert_eq!(edge_columns(&prepare_ast(src).0).len(), 1);
+    }
+
+    #[test]
+    fn test_rank_same_shares_a_layer() {
+        let src = "graph TD\n    A --> B\n    A --> D\n    D --> C\n    C --> E\n";
+        let layer =
+            |ir: &LayoutIR, label: &str| ir.rects.iter().find(|r| r.label == label).unwrap().layer;
+        let ir = layout(src, &config_default());
+        assert_eq!((layer(&ir, "B"), layer(&ir, "C")), (1, 2));
+
+        let ranked = format!("{src}    rank same B, C\n");
+        let ir = layout(&ranked, &config_default());
+        assert_eq!((layer(&ir, "B"), layer(&ir, "C")), (2, 2));
+        // Successors stay below, and the statement draws nothing
+        assert_eq!(layer(&ir, "E"), 3);
+        assert_eq!(ir.rects.len(), 5);
+        assert!(graph_stats(&ranked).warnings.is_empty());
+        assert_eq!(
+            format_dsl(&ranked).unwrap(),
+            "graph TD\n    A --> B\n    A --> D\n    D --> C\n    C --> E\n    rank same B C\n"
+        );
+    }
+
+    #[test]
+    fn test_arrowhead_skips_duplicate_tip() {
+        let mut c = canvas::canvas_new(6, 6, CharSet::Unicode);
+        paint_edge(
+            &mut c,
+            &[(2, 0), (2, 4), (2, 4)],
+            "BidirArrow",
+            "",
+            None,
+            &[],
+            false,
+        );
+        assert_eq!(c.cells[4][2], "▼");
+        assert_eq!(c.cells[0][2], "▲");
+        let mut c = canvas::canvas_new(6, 6, CharSet::Unicode);
+        paint_edge(
+            &mut c,
+            &[(4, 1), (4, 1), (0, 1), (0, 1)],
+            "BidirArrow",
+            "",
+            None,
+            &[],
+            false,
+        );
+        assert_eq!(c.cells[1][0], "◄");
+        assert_eq!(c.cells[1][4], "►");
+    }
+
+    #[test]
+    fn test_boxed_edge_label_clears_line_beside_it() {
+        let row = |boxed: bool| {
+            let mut c = canvas::canvas_new(20, 3, CharSet::Unicode);
+            let wps = [(0, 1), (16, 1)];
+            paint_edge(&mut c, &wps, "Line", "yes", Some((6, 1)), &[], boxed);
+            c.cells[1].concat()
+        };
+        assert!(row(false).starts_with("──────yes───────"), "{}", row(false));
+        assert!(row(true).starts_with("───── yes ──────"), "{}", row(true));
+
+        let mut config = config_default();
+        config.boxed_edge_labels = true;
+        assert_eq!(
+            render_dsl_with_config("graph LR\n    A -->|yes| B\n", &config).unwrap(),
+            "┌───┐ yes  ┌───┐\n│ A ├─────►│ B │\n└───┘      └───┘\n"
+        );
+    }
+
+    #[test]
+    fn test_simplify_waypoints_drops_straight_midpoints() {
+        let wps = [(0, 0), (0, 2), (0, 5), (3, 5), (6, 5), (6, 8)];
+        let simple = simplify_waypoints(&wps);
+        assert_eq!(simple, vec![(0, 0), (0, 5), (6, 5), (6, 8)]);
+        assert_eq!(
+            simplify_waypoints(&[(0, 0), (0, 3), (0, 3)]),
+            vec![(0, 0), (0, 3)]
+        );
+        let paint = |wps: &[(i32, i32)]| {
+            let mut c = canvas::canvas_new(8, 10, CharSet::Unicode);
+            paint_edge(&mut c, wps, "Arrow", "", None, &[], false);
+            c.cells
+        };
+        assert_eq!(paint(&wps), paint(&simple));
+        // A spur the path doubles back along keeps its far end
+        assert_eq!(simplify_waypoints(&[(0, 0), (4, 0), (2, 0)]).len(), 3);
+    }
+
+    #[test]
+    fn test_pass_through_line_beside_node_is_marked() {
+        let b = LayoutRect {
+            id: "B".to_string(),
+            x: 3,
+            y: 4,
+            w: 5,
+            h: 3,
+            label: "B".to_string(),
+            shape: "Rectangle".to_string(),
+            classes: Vec::new(),
+            styles: Vec::new(),
+            layer: 1,
+        };
+        let mut c = canvas::canvas_new(10, 12, CharSet::Unicode);
+        let plain = box_chars_override_none();
+        paint_node(
+            &mut c,
+            b.x,
+            b.y,
+            b.w,
+            b.h,
+            &b.label,
+            &b.shape,
+            false,
+            &plain,
+            &TextAlign::Center,
+        );
+        paint_edge(&mut c, &[(2, 0), (2, 10)], "Arrow", "", None, &[&b], false);
+        let column: Vec<&str> = (0..11).map(|row| c.cells[row][2].as_str()).collect();
+        assert_eq!(
+            column,
+            vec!["│", "│", "│", "│", "┊", "┊", "┊", "│", "│", "│", "▼"]
+        );
+        // Nothing marked when the box is not one the edge skips
+        let mut c = canvas::canvas_new(10, 12, CharSet::Ascii);
+        paint_edge(&mut c, &[(2, 0), (2, 10)], "Line", "", None, &[], false);
+        assert!((0..11).all(|row| c.cells[row][2] == "|"));
+
+        // A long edge bypassing a middle-layer node, laid out tight: no
+        // plain line cell sits right against the node's sides.
+        let mut config = config_default();
+        config.h_gap = Some(0);
+        config.pass_through_marks = true;
+        let src = "graph TD\n    A --> B\n    B --> C\n    A --> C\n";
+        let (ir, _, config) = layout_dsl(src, &config).unwrap().unwrap();
+        let c = paint_ir(&ir, &config).unwrap();
+        let b = ir.rects.iter().find(|r| r.id == "B").unwrap();
+        for row in b.y..b.y + b.h {
+            for col in [b.x - 1, b.x + b.w] {
+                assert_ne!(cget(&c, col, row), "│", "{:?}", c.cells);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dummy_ids_ignore_edge_order() {
+        let dummies = |src: &str| {
+            let (parsed, _) = prepare_ast(src);
+            let (dag, _) = remove_cycles_rust(&ast_to_graph(&parsed));
+            let layers = assign_layers_rust(&dag);
+            let (_, _, chains) = insert_dummy_nodes_rust(&dag, &layers);
+            let mut ids: Vec<(String, String, Vec<String>)> = chains
+                .into_iter()
+                .map(|ch| (ch.from_id, ch.to_id, ch.dummy_ids))
+                .collect();
+            ids.sort();
+            ids
+        };
+        let forward =
+            dummies("graph TD\n    A --> B\n    B --> C\n    C --> D\n    A --> C\n    A --> D\n");
+        let shuffled =
+            dummies("graph TD\n    A --> D\n    C --> D\n    A --> C\n    B --> C\n    A --> B\n");
+        assert_eq!(forward, shuffled);
+        assert_eq!(
+            forward,
+            vec![
+                (
+                    "A".to_string(),
+                    "C".to_string(),
+                    vec!["__dummy_1_A_C_0".to_string()]
+                ),
+                (
+                    "A".to_string(),
+                    "D".to_string(),
+                    vec!["__dummy_1_A_D_0".to_string(), "__dummy_1_A_D_1".to_string()]
+                ),
+            ]
+        );
+
+        // Ids containing `_` that would join to the same `a_b_c`
+        let src = "graph TD\n    a_b --> x --> y --> c\n    a_b --> c\n    \
+                   a --> p --> q --> b_c\n    a --> b_c\n";
+        let (parsed, _) = prepare_ast(src);
+        let (dag, _) = remove_cycles_rust(&ast_to_graph(&parsed));
+        let layers = assign_layers_rust(&dag);
+        let (aug, aug_layers, chains) = insert_dummy_nodes_rust(&dag, &layers);
+        let ids: Vec<&String> = chains.iter().flat_map(|ch| &ch.dummy_ids).collect();
+        assert_eq!(
+            ids,
+            [
+                "__dummy_3_a_b_c_0",
+                "__dummy_3_a_b_c_1",
+                "__dummy_1_a_b_c_0",
+                "__dummy_1_a_b_c_1"
+            ]
+        );
+        assert_eq!(graph::graph_node_count(&aug), 8 + 4);
+        assert_eq!(aug_layers.len(), 8 + 4);
+    }
+
+    #[test]
+    fn test_parallel_dummy_chains_keep_apart() {
+        // A→E and B→E both cross C's layer, side by side
+        let src = "graph TD\n    A --> E\n    B --> E\n    C --> D\n    C --> E\n    A --> C\n";
+        let (parsed, _) = prepare_ast(src);
+        let g = ast_to_graph(&parsed);
+        let mut config = config_default();
+        config.straighten = true;
+        config.h_gap = Some(0);
+        let (nodes, _, chains) =
+            layout_nodes(&g, &config, false, &HashMap::new(), &NodePins::new(), &[]);
+        assert_eq!(chains.len(), 2);
+        let x_of = |id: &String| {
+            let i = graph::nll_id_to_index(nodes.clone(), id.clone());
+            graph::nll_get_x(nodes.clone(), i)
+        };
+        for (d0, d1) in chains[0].dummy_ids.iter().zip(&chains[1].dummy_ids) {
+            assert!((x_of(d0) - x_of(d1)).abs() >= 2, "{d0} and {d1} touch");
+        }
+    }
+
+    #[test]
+    fn test_label_align_left_and_right() {
+        let row = |align: TextAlign, row: usize| {
+            let mut c = canvas::canvas_new(10, 4, CharSet::Unicode);
+            let plain = box_chars_override_none();
+            paint_node(
+                &mut c,
+                0,
+                0,
+                10,
+                4,
+                "ab\nlonger",
+                "Rectangle",
+                false,
+                &plain,
+                &align,
+            );
+            c.cells[row].join("")
+        };
+        // Inner text columns are 2..=7 (one column of padding each side)
+        assert_eq!(row(TextAlign::Left, 1), "│ ab     │");
+        assert_eq!(row(TextAlign::Left, 2), "│ longer │");
+        assert_eq!(row(TextAlign::Right, 1), "│     ab │");
+        assert_eq!(row(TextAlign::Right, 2), "│ longer │");
+        assert_eq!(row(TextAlign::Center, 1), "│   ab   │");
+        assert_eq!(
+            config_from_json(r#"{"labelAlign":"right"}"#)
+                .unwrap()
+                .label_align,
+            TextAlign::Right
+        );
+    }
+
+    #[test]
+    fn test_label_centred_in_tall_box() {
+        // C stretched to the height of its three-line neighbour B
+        let src = "graph TD\n    A --> B[one<br>two<br>six]\n    A --> C\n";
+        let rect = |ir: &LayoutIR, id: &str| {
+            let r = ir.rects.iter().find(|r| r.id == id).unwrap();
+            (r.x, r.y, r.w, r.h)
+        };
+        assert_eq!(rect(&layout(src, &config_default()), "C").3, 3);
+
+        let mut config = config_default();
+        config.layer_max_height = true;
+        let ir = layout(src, &config);
+        let (_, b_y, _, b_h) = rect(&ir, "B");
+        let (x, y, w, h) = rect(&ir, "C");
+        assert_eq!((y, h), (b_y, b_h));
+        assert_eq!(h, 5);
+        let c = paint_ir(&ir, &config).unwrap();
+        let interior: Vec<String> = (y + 1..y + h - 1)
+            .map(|row| c.cells[row as usize][(x + 1) as usize..(x + w - 1) as usize].concat())
+            .collect();
+        assert_eq!(interior, ["   ", " C ", "   "]);
+    }
+
+    #[test]
+    fn test_ampersand_groups_in_subgraph() {
+        let (parsed, _) = prepare_ast(
+            "graph TD\n    subgraph G\n        A & B --> C\n    end\n    C --> D & E\n",
+        );
+        let sg = &parsed.subgraphs[0];
+        let members: Vec<&str> = sg.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(members, ["A", "B", "C"]);
+        let pairs = |edges: &[parser::Edge]| -> Vec<(String, String)> {
+            edges
+                .iter()
+                .map(|e| (e.from_id.clone(), e.to_id.clone()))
+                .collect()
+        };
+        let pair = |a: &str, b: &str| (a.to_string(), b.to_string());
+        assert_eq!(pairs(&sg.edges), [pair("A", "C"), pair("B", "C")]);
+        assert_eq!(pairs(&parsed.edges), [pair("C", "D"), pair("C", "E")]);
+        assert!(check_dsl("graph TD\n    A & B[Two] --> C\n").is_ok());
+    }
+
+    #[test]
+    fn test_label_gaps_fit_edge_labels() {
+        let src = "graph TD\n    A -->|yes| B --> C\n";
+        let gaps = |config: &RenderConfig| {
+            let ir = layout(src, config);
+            let rect = |id: &str| ir.rects.iter().find(|r| r.id == id).unwrap();
+            let gap = |a: &str, b: &str| rect(b).y - (rect(a).y + rect(a).h);
+            (gap("A", "B"), gap("B", "C"))
+        };
+        let mut config = config_default();
+        let (labelled, plain) = gaps(&config);
+        assert!(labelled > plain, "{labelled} vs {plain}");
+        assert_eq!(labelled, LABEL_GAP_ROWS);
+
+        config.label_gaps = false;
+        let (labelled, plain) = gaps(&config);
+        assert_eq!(labelled, plain);
+    }
+
+    #[test]
+    fn test_roots_keep_declaration_order() {
+        let src = "graph TD\n    Start --> A\n    Begin --> B\n";
+        let ir = layout(src, &config_default());
+        let x = |id: &str| ir.rects.iter().find(|r| r.id == id).unwrap().x;
+        assert!(x("Start") < x("Begin"));
+        assert!(x("A") < x("B"));
+    }
+
+    #[test]
+    fn test_multiline_edge_labels() {
+        let src = "graph TD\n    A -->|line1<br>line2| B\n";
+        let (parsed, _) = prepare_ast(src);
+        assert_eq!(parsed.edges[0].label, "line1\nline2");
+        let (parsed, _) = prepare_ast("graph TD\n    A -->|a <BR/> b\\nc| B\n");
+        assert_eq!(parsed.edges[0].label, "a\nb\nc");
+
+        assert_eq!(
+            render_dsl_with_config(src, &config_default())
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌───┐",
+                "│ A │",
+                "└─┬─┘",
+                "  │",
+                "  │",
+                "  │line1",
+                "  │line2",
+                "  ▼",
+                "┌───┐",
+                "│ B │",
+                "└───┘",
+            ]
+        );
+        assert_eq!(
+            format_dsl(src).unwrap(),
+            "graph TD\n    A -->|line1<br>line2| B\n"
+        );
+    }
+
+    #[test]
+    fn test_crossing_minimization_can_be_disabled() {
+        let src = "graph TD\n    A --> Z\n    B --> Y\n";
+        let order = |config: &RenderConfig| {
+            let ir = layout(src, config);
+            let mut bottom: Vec<&LayoutRect> = ir.rects.iter().filter(|r| r.layer == 1).collect();
+            bottom.sort_by_key(|r| r.x);
+            bottom.iter().map(|r| r.id.clone()).collect::<Vec<_>>()
+        };
+        let mut config = config_default();
+        assert_eq!(order(&config), ["Z", "Y"]);
+        config.minimize_crossings = false;
+        assert_eq!(order(&config), ["Y", "Z"]);
+        config.minimize_crossings = true;
+        config.max_passes = 0;
+        assert_eq!(order(&config), ["Y", "Z"]);
+        assert_eq!(ordering_passes(&config), 0);
+    }
+
+    #[test]
+    fn test_mixed_edge_chain_keeps_segment_types() {
+        let src = "graph TD\n    A -->|x| B -.-> C ==> D\n";
+        let (parsed, _) = prepare_ast(src);
+        let types: Vec<_> = parsed.edges.iter().map(|e| e.edge_type.clone()).collect();
+        assert_eq!(
+            types,
+            vec![
+                parser::EdgeType::Arrow,
+                parser::EdgeType::DottedArrow,
+                parser::EdgeType::ThickArrow
+            ]
+        );
+        assert_eq!(parsed.edges[0].label, "x");
+        assert!(parsed.edges[1].label.is_empty());
+
+        // Each segment is drawn wholly in its own style, stub to arrowhead.
+        assert_eq!(
+            render_dsl_with_config(src, &config_default())
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌───┐",
+                "│ A │",
+                "└─┬─┘",
+                "  │",
+                "  │",
+                "  x",
+                "  ▼",
+                "┌───┐",
+                "│ B │",
+                "└─┬─┘",
+                "  ╎",
+                "  ╎",
+                "  ▼",
+                "┌───┐",
+                "│ C │",
+                "└─┬─┘",
+                "  ║",
+                "  ║",
+                "  ▼",
+                "┌───┐",
+                "│ D │",
+                "└───┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inline_labels_on_circle_and_cross_connectors() {
+        let src = "graph LR\n    A x-- err --x B\n    C o-- count --o D\n    \
+                   E -- a -- b --> F\n    G --o H\n    I -. maybe .-> J\n";
+        let (parsed, _) = prepare_ast(src);
+        let edges: Vec<_> = parsed
+            .edges
+            .iter()
+            .map(|e| (e.from_id.as_str(), e.edge_type.clone(), e.label.as_str()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("A", parser::EdgeType::BidirCross, "err"),
+                ("C", parser::EdgeType::BidirCircle, "count"),
+                ("E", parser::EdgeType::Arrow, "a -- b"),
+                ("G", parser::EdgeType::CircleArrow, ""),
+                ("I", parser::EdgeType::DottedArrow, "maybe"),
+            ]
+        );
+
+        assert_eq!(
+            render_dsl_with_config(src, &config_default())
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "┌───┐    err  ┌───┐",
+                "│ A ├x───────x│ B │",
+                "└───┘         └───┘",
+                "",
+                "┌───┐  count ┌───┐",
+                "│ C ├o───────o│ D │",
+                "└───┘         └───┘",
+                "",
+                "┌───┐ a -- b ┌───┐",
+                "│ E ├────────►│ F │",
+                "└───┘         └───┘",
+                "",
+                "┌───┐         ┌───┐",
+                "│ G ├────────o│ H │",
+                "└───┘         └───┘",
+                "",
+                "┌───┐  maybe ┌───┐",
+                "│ I ├╌╌╌╌╌╌╌╌►│ J │",
+                "└───┘         └───┘",
+            ]
+        );
+    }
 }
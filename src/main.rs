@@ -6,7 +6,7 @@ use std::process;
 
 use clap::Parser;
 
-use mermaid_ascii::{render_dsl, render_svg_dsl};
+use mermaid_ascii::{LineEnding, check_dsl, config_new, render_dsl_to_writer, render_svg_dsl};
 
 /// Mermaid flowchart to ASCII/Unicode graph output.
 #[derive(Parser, Debug)]
@@ -16,7 +16,7 @@ use mermaid_ascii::{render_dsl, render_svg_dsl};
     about = "Mermaid flowchart to ASCII/Unicode graph output"
 )]
 struct Cli {
-    /// Input file (reads from stdin if not provided)
+    /// Input file, or `-` for stdin (reads from stdin if not provided)
     input: Option<String>,
 
     /// Use plain ASCII instead of Unicode box-drawing characters
@@ -38,61 +38,112 @@ struct Cli {
     /// Write output to this file instead of stdout
     #[arg(short = 'o', long = "output")]
     output: Option<String>,
+
+    /// Fail on text the parser cannot read instead of skipping it
+    #[arg(long = "strict")]
+    strict: bool,
+
+    /// End lines with CRLF instead of LF
+    #[arg(long = "crlf")]
+    crlf: bool,
+
+    /// Omit the line ending after the last line
+    #[arg(long = "no-trailing-newline")]
+    no_trailing_newline: bool,
+
+    /// Keep trailing spaces and blank rows: every line spans the full canvas
+    #[arg(long = "no-trim")]
+    no_trim: bool,
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    let text = if let Some(ref path) = cli.input {
-        match fs::read_to_string(path) {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("error: cannot read '{}': {}", path, e);
+    // `-` means stdin; put `--` before a file name that starts with `-`
+    let text = match cli.input.as_deref() {
+        Some(path) if path != "-" => {
+            if fs::metadata(path).is_ok_and(|m| m.is_dir()) {
+                eprintln!("error: '{}' is a directory, not an input file", path);
                 process::exit(1);
             }
+            match fs::read_to_string(path) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("error: cannot read '{}': {}", path, e);
+                    process::exit(1);
+                }
+            }
         }
-    } else {
-        let mut buf = String::new();
-        if let Err(e) = io::stdin().read_to_string(&mut buf) {
-            eprintln!("error: cannot read stdin: {}", e);
-            process::exit(1);
+        _ => {
+            let mut buf = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut buf) {
+                eprintln!("error: cannot read stdin: {}", e);
+                process::exit(1);
+            }
+            buf
         }
-        buf
     };
 
+    if cli.strict
+        && let Err(e) = check_dsl(&text)
+    {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+
     let direction = cli.direction.as_deref();
-    let rendered = if cli.use_svg {
+    let svg = if cli.use_svg {
         match render_svg_dsl(&text, cli.padding, direction) {
-            Ok(s) => s,
+            Ok(s) => Some(s),
             Err(e) => {
                 eprintln!("error: {}", e);
                 process::exit(1);
             }
         }
     } else {
-        let unicode = !cli.use_ascii;
-        match render_dsl(&text, unicode, cli.padding, direction) {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("error: {}", e);
-                process::exit(1);
-            }
-        }
+        None
     };
 
-    if let Some(ref path) = cli.output {
-        match fs::write(path, rendered) {
-            Ok(()) => {}
-            Err(e) => {
-                eprintln!("error: cannot write '{}': {}", path, e);
-                process::exit(1);
+    // ASCII output streams straight to stdout; `-o` output is rendered in
+    // memory first so a failed render leaves an existing file untouched
+    let mut rendered = Vec::new();
+    let written = {
+        let mut out: Box<dyn Write + '_> = if cli.output.is_some() {
+            Box::new(&mut rendered)
+        } else {
+            Box::new(io::BufWriter::new(io::stdout().lock()))
+        };
+        let written = match svg {
+            Some(s) => out.write_all(s.as_bytes()),
+            None => {
+                let mut config =
+                    config_new(!cli.use_ascii, cli.padding as i32, cli.direction.clone());
+                if cli.crlf {
+                    config.line_ending = LineEnding::CrLf;
+                }
+                config.trailing_newline = !cli.no_trailing_newline;
+                config.trim_trailing = !cli.no_trim;
+                render_dsl_to_writer(&text, &config, &mut out)
             }
+        };
+        written.and_then(|()| out.flush())
+    };
+    match written {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            eprintln!("error: {}", e);
+            process::exit(1);
         }
-    } else {
-        print!("{}", rendered);
-        if let Err(e) = io::stdout().flush() {
-            eprintln!("error: cannot flush stdout: {}", e);
+        Err(e) => {
+            eprintln!("error: cannot write stdout: {}", e);
             process::exit(1);
         }
     }
+
+    if let Some(ref path) = cli.output
+        && let Err(e) = fs::write(path, &rendered)
+    {
+        eprintln!("error: cannot write '{}': {}", path, e);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,88 @@
+//! Edge routing on the occupancy grid — the extension point for custom routers.
+//!
+//! The layout marks every node box on a [`Grid`], then asks an [`EdgeRouter`]
+//! for each edge's path from the cell just outside its source to the cell
+//! just outside its target. [`AStarRouter`] is the default; see
+//! [`crate::render_dsl_with_router`] to plug in another one.
+
+use crate::pathfinder;
+
+/// Cells of the layout canvas, each free or blocked by a node box.
+/// Coordinates are `(column, row)` in the layout's TD space (LR/RL diagrams
+/// are routed top-down and transposed afterwards).
+#[derive(Debug, Clone)]
+pub struct Grid {
+    inner: pathfinder::OccupancyGrid,
+}
+
+impl Grid {
+    /// An all-free grid of `width` × `height` cells.
+    pub fn new(width: i32, height: i32) -> Self {
+        Grid {
+            inner: pathfinder::grid_new(width, height),
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.inner.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.inner.height
+    }
+
+    /// Block the `w` × `h` rectangle at `(x, y)`, clipped to the grid.
+    pub fn mark_blocked(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        pathfinder::grid_mark_blocked(&mut self.inner, x, y, w, h);
+    }
+
+    /// Whether `(x, y)` is inside the grid and not blocked.
+    pub fn is_free(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && x < self.width()
+            && y >= 0
+            && y < self.height()
+            && !self.inner.data[(y * self.width() + x) as usize]
+    }
+}
+
+/// Shortest orthogonal path from `start` to `end` through free cells (`end`
+/// itself may be blocked), reduced to its corner points. Turns cost one
+/// extra step, so straighter paths win ties. `None` if no path exists.
+pub fn a_star(grid: &mut Grid, start: (i32, i32), end: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+    let mut path = pathfinder::a_star(&mut grid.inner, start.0, start.1, end.0, end.1);
+    if path.is_empty() {
+        None
+    } else {
+        Some(pathfinder::simplify_path(&mut path))
+    }
+}
+
+/// Chooses the path of each edge that is not drawn through a dummy chain
+/// (`straighten`) or as a same-layer detour.
+pub trait EdgeRouter {
+    /// Waypoints from `exit` to `entry` (both included), turning only at
+    /// right angles. `None` falls back to a three-segment L-path. The first
+    /// and last segments are made vertical afterwards if they are not.
+    fn route(
+        &self,
+        grid: &mut Grid,
+        exit: (i32, i32),
+        entry: (i32, i32),
+    ) -> Option<Vec<(i32, i32)>>;
+}
+
+/// The default router: [`a_star`] around the node boxes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AStarRouter;
+
+impl EdgeRouter for AStarRouter {
+    fn route(
+        &self,
+        grid: &mut Grid,
+        exit: (i32, i32),
+        entry: (i32, i32),
+    ) -> Option<Vec<(i32, i32)>> {
+        a_star(grid, exit, entry)
+    }
+}
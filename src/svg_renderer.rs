@@ -1,9 +1,10 @@
 //! SVG renderer — 1:1 conversion from LayoutIR primitives to SVG.
 //!
-//! Each LayoutRect becomes an SVG shape, each LayoutEdge becomes a polyline.
+//! Each LayoutRect becomes an SVG shape, each LayoutEdge becomes a polyline,
+//! or a path with rounded turns when a corner radius is set.
 //! No layout logic here — just drawing.
 
-use crate::{LayoutEdge, LayoutIR, LayoutRect};
+use crate::{LayoutEdge, LayoutIR, LayoutRect, config_default};
 
 // ── Constants ────────────────────────────────────────────────────────────────
 
@@ -24,6 +25,10 @@ fn escape(s: &str) -> String {
         .replace('>', "&gt;")
 }
 
+fn escape_attr(s: &str) -> String {
+    escape(s).replace('"', "&quot;").replace('\n', "&#10;")
+}
+
 fn font(size: i32) -> String {
     format!(r#"font-family="{FONT_FAMILY}" font-size="{size}""#)
 }
@@ -57,6 +62,43 @@ fn is_bidir(et: &str) -> bool {
     matches!(et, "BidirArrow" | "BidirDotted" | "BidirThick")
 }
 
+/// Marker id for `--o` / `--x` style ends; `o--o` / `x--x` use it at both ends.
+fn end_marker(et: &str) -> Option<&'static str> {
+    match et {
+        "CircleArrow" | "BidirCircle" => Some("circle-end"),
+        "CrossArrow" | "BidirCross" => Some("cross-end"),
+        _ => None,
+    }
+}
+
+/// Path data through `points` (in px) that rounds each turn with a quadratic
+/// curve of up to `radius` px, shortened to half the shorter adjacent segment.
+/// Collinear points and straight runs stay plain `L` segments.
+fn rounded_path(points: &[(i32, i32)], radius: i32) -> String {
+    let mut d = format!("M{},{}", points[0].0, points[0].1);
+    for i in 1..points.len() - 1 {
+        let (prev, (x, y), next) = (points[i - 1], points[i], points[i + 1]);
+        let (dx0, dy0) = ((x - prev.0).signum(), (y - prev.1).signum());
+        let (dx1, dy1) = ((next.0 - x).signum(), (next.1 - y).signum());
+        if (dx0, dy0) == (dx1, dy1) {
+            continue;
+        }
+        let len_in = (x - prev.0).abs() + (y - prev.1).abs();
+        let len_out = (next.0 - x).abs() + (next.1 - y).abs();
+        let r = radius.min(len_in / 2).min(len_out / 2);
+        d.push_str(&format!(
+            " L{},{} Q{x},{y} {},{}",
+            x - dx0 * r,
+            y - dy0 * r,
+            x + dx1 * r,
+            y + dy1 * r
+        ));
+    }
+    let (x, y) = points[points.len() - 1];
+    d.push_str(&format!(" L{x},{y}"));
+    d
+}
+
 // ── Rect → SVG ───────────────────────────────────────────────────────────────
 
 fn render_label_svg(cx: i32, cy: i32, label: &str) -> String {
@@ -84,6 +126,16 @@ fn render_label_svg(cx: i32, cy: i32, label: &str) -> String {
     }
 }
 
+/// Default fill and stroke, with any `style A ...` properties layered on
+/// top as an inline CSS `style`, which takes precedence over them.
+fn node_paint(r: &LayoutRect) -> String {
+    if r.styles.is_empty() {
+        return FILL_STROKE.to_string();
+    }
+    let css: Vec<String> = r.styles.iter().map(|(p, v)| format!("{p}:{v}")).collect();
+    format!(r#"{FILL_STROKE} style="{}""#, escape_attr(&css.join(";")))
+}
+
 fn render_rect(r: &LayoutRect) -> String {
     let sx = px(r.x);
     let sy = py(r.y);
@@ -91,6 +143,9 @@ fn render_rect(r: &LayoutRect) -> String {
     let sh = r.h * CELL_H;
     let cx = sx + sw / 2;
     let cy = sy + sh / 2;
+    // Source id for scripts that look up a node's shape
+    let id = format!(r#"data-id="{}""#, escape_attr(&r.id));
+    let paint = node_paint(r);
 
     match r.shape.as_str() {
         "Container" => {
@@ -98,7 +153,7 @@ fn render_rect(r: &LayoutRect) -> String {
             let f = font(FONT_SIZE - 2);
             let ty = sy + FONT_SIZE + 2;
             format!(
-                r##"<rect x="{sx}" y="{sy}" width="{sw}" height="{sh}" {SG_STROKE}/>
+                r##"<rect x="{sx}" y="{sy}" width="{sw}" height="{sh}" {SG_STROKE} {id}/>
 <text x="{}" y="{ty}" {f} fill="#666">{}</text>"##,
                 sx + 8,
                 escape(&r.label)
@@ -107,14 +162,14 @@ fn render_rect(r: &LayoutRect) -> String {
         "Rounded" => {
             let rv = sw.min(sh) / 4;
             let shape_svg = format!(
-                r#"<rect x="{sx}" y="{sy}" width="{sw}" height="{sh}" rx="{rv}" {FILL_STROKE}/>"#
+                r#"<rect x="{sx}" y="{sy}" width="{sw}" height="{sh}" rx="{rv}" {paint} {id}/>"#
             );
             let label_svg = render_label_svg(cx, cy, &r.label);
             format!("{shape_svg}\n{label_svg}")
         }
         "Diamond" => {
             let pts = format!("{cx},{sy} {},{cy} {cx},{} {sx},{cy}", sx + sw, sy + sh);
-            let shape_svg = format!(r#"<polygon points="{pts}" {FILL_STROKE}/>"#);
+            let shape_svg = format!(r#"<polygon points="{pts}" {paint} {id}/>"#);
             let label_svg = render_label_svg(cx, cy, &r.label);
             format!("{shape_svg}\n{label_svg}")
         }
@@ -122,14 +177,14 @@ fn render_rect(r: &LayoutRect) -> String {
             let rx = sw / 2;
             let ry = sh / 2;
             let shape_svg =
-                format!(r#"<ellipse cx="{cx}" cy="{cy}" rx="{rx}" ry="{ry}" {FILL_STROKE}/>"#);
+                format!(r#"<ellipse cx="{cx}" cy="{cy}" rx="{rx}" ry="{ry}" {paint} {id}/>"#);
             let label_svg = render_label_svg(cx, cy, &r.label);
             format!("{shape_svg}\n{label_svg}")
         }
         _ => {
             // Rectangle (default)
             let shape_svg = format!(
-                r#"<rect x="{sx}" y="{sy}" width="{sw}" height="{sh}" rx="0" {FILL_STROKE}/>"#
+                r#"<rect x="{sx}" y="{sy}" width="{sw}" height="{sh}" rx="0" {paint} {id}/>"#
             );
             let label_svg = render_label_svg(cx, cy, &r.label);
             format!("{shape_svg}\n{label_svg}")
@@ -139,7 +194,7 @@ fn render_rect(r: &LayoutRect) -> String {
 
 // ── Edge → SVG ───────────────────────────────────────────────────────────────
 
-fn render_edge(e: &LayoutEdge) -> String {
+fn render_edge(e: &LayoutEdge, corner_radius: i32) -> String {
     if e.waypoints.len() < 2 {
         return String::new();
     }
@@ -152,27 +207,72 @@ fn render_edge(e: &LayoutEdge) -> String {
     if is_bidir(&e.edge_type) {
         markers.push_str(r#" marker-start="url(#arrowhead-rev)""#);
     }
+    if let Some(id) = end_marker(&e.edge_type) {
+        markers.push_str(&format!(r#" marker-end="url(#{id})""#));
+        if e.edge_type.starts_with("Bidir") {
+            markers.push_str(&format!(r#" marker-start="url(#{id})""#));
+        }
+    }
 
-    let pts: String = e
-        .waypoints
-        .iter()
-        .map(|(x, y)| format!("{},{}", px(*x), py(*y)))
-        .collect::<Vec<_>>()
-        .join(" ");
+    // Edge classes (`A -->:::name B`) for stylesheet rules
+    let class = if e.classes.is_empty() {
+        String::new()
+    } else {
+        format!(r#" class="{}""#, escape_attr(&e.classes.join(" ")))
+    };
 
-    let mut parts = vec![format!(
-        r#"<polyline points="{pts}" fill="none" stroke="black" stroke-width="1.5" {style}{markers}/>"#
-    )];
+    // Endpoint ids so scripts can find an edge's connections
+    let ends = format!(
+        r#" data-from="{}" data-to="{}""#,
+        escape_attr(&e.from),
+        escape_attr(&e.to)
+    );
+
+    let points: Vec<(i32, i32)> = e.waypoints.iter().map(|&(x, y)| (px(x), py(y))).collect();
+    let line = if corner_radius > 0 {
+        let d = rounded_path(&points, corner_radius);
+        format!(
+            r#"<path d="{d}" fill="none" stroke="black" stroke-width="1.5" {style}{markers}{class}{ends}/>"#
+        )
+    } else {
+        let pts: String = points
+            .iter()
+            .map(|(x, y)| format!("{x},{y}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            r#"<polyline points="{pts}" fill="none" stroke="black" stroke-width="1.5" {style}{markers}{class}{ends}/>"#
+        )
+    };
+    let mut parts = vec![line];
 
     if !e.label.is_empty() {
-        let mid = e.waypoints.len() / 2;
-        let (lx, ly) = e.waypoints[mid];
-        let lsx = px(lx);
-        let lsy = py(ly) - 8;
+        let (lsx, lsy) = if let Some((lx, ly)) = e.label_pos {
+            // Reserved label row: centre the text on the cells it occupies
+            let len = e.label.chars().count() as i32;
+            (px(lx) + len * CELL_W / 2, py(ly) + CELL_H / 2)
+        } else {
+            let mid = e.waypoints.len() / 2;
+            let (lx, ly) = e.waypoints[mid];
+            (px(lx), py(ly) - 8)
+        };
         let f = font(FONT_SIZE - 2);
-        parts.push(format!(
-            r##"<text x="{lsx}" y="{lsy}" text-anchor="middle" {f} fill="#333">{}</text>"##,
+        let text = if e.label.contains('\n') {
+            // Lines stack upward so the last one keeps the single-line spot
+            let lines: Vec<&str> = e.label.lines().collect();
+            lines
+                .iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    let ty = lsy - (lines.len() - 1 - i) as i32 * FONT_SIZE;
+                    format!(r#"<tspan x="{lsx}" y="{ty}">{}</tspan>"#, escape(line))
+                })
+                .collect()
+        } else {
             escape(&e.label)
+        };
+        parts.push(format!(
+            r##"<text x="{lsx}" y="{lsy}" text-anchor="middle" {f} fill="#333">{text}</text>"##
         ));
     }
 
@@ -182,7 +282,13 @@ fn render_edge(e: &LayoutEdge) -> String {
 // ── Public API ───────────────────────────────────────────────────────────────
 
 /// 1:1 render LayoutIR → SVG string. No layout logic, just drawing.
+/// Edge turns use the default `RenderConfig::svg_corner_radius`.
 pub fn render_ir(ir: &LayoutIR, direction: &str) -> String {
+    render_ir_rounded(ir, direction, config_default().svg_corner_radius)
+}
+
+/// [`render_ir`] with edge turns rounded to `corner_radius` px (0: sharp).
+pub fn render_ir_rounded(ir: &LayoutIR, direction: &str, corner_radius: i32) -> String {
     if ir.rects.is_empty() {
         return String::new();
     }
@@ -210,10 +316,27 @@ pub fn render_ir(ir: &LayoutIR, direction: &str) -> String {
         _ => String::new(),
     };
 
-    let mut parts = vec![
-        format!(
-            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{svg_w}" height="{svg_h}" viewBox="0 0 {svg_w} {svg_h}">"#
-        ),
+    // Accessible name for screen readers: the title, else the description
+    let acc_name = if ir.acc_title.is_empty() {
+        &ir.acc_descr
+    } else {
+        &ir.acc_title
+    };
+    let aria = if acc_name.is_empty() {
+        String::new()
+    } else {
+        format!(r#" role="img" aria-label="{}""#, escape_attr(acc_name))
+    };
+    let mut parts = vec![format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{svg_w}" height="{svg_h}" viewBox="0 0 {svg_w} {svg_h}"{aria}>"#
+    )];
+    if !ir.acc_title.is_empty() {
+        parts.push(format!("<title>{}</title>", escape(&ir.acc_title)));
+    }
+    if !ir.acc_descr.is_empty() {
+        parts.push(format!("<desc>{}</desc>", escape(&ir.acc_descr)));
+    }
+    parts.extend([
         "<defs>".to_string(),
         r#"  <marker id="arrowhead" markerWidth="10" markerHeight="7" refX="10" refY="3.5" orient="auto">"#.to_string(),
         r#"    <polygon points="0 0, 10 3.5, 0 7" fill="black"/>"#.to_string(),
@@ -221,9 +344,15 @@ pub fn render_ir(ir: &LayoutIR, direction: &str) -> String {
         r#"  <marker id="arrowhead-rev" markerWidth="10" markerHeight="7" refX="0" refY="3.5" orient="auto">"#.to_string(),
         r#"    <polygon points="10 0, 0 3.5, 10 7" fill="black"/>"#.to_string(),
         "  </marker>".to_string(),
+        r#"  <marker id="circle-end" markerWidth="10" markerHeight="10" refX="5" refY="5">"#.to_string(),
+        r#"    <circle cx="5" cy="5" r="4" fill="white" stroke="black" stroke-width="1.5"/>"#.to_string(),
+        "  </marker>".to_string(),
+        r#"  <marker id="cross-end" markerWidth="10" markerHeight="10" refX="5" refY="5">"#.to_string(),
+        r#"    <path d="M1,1 L9,9 M9,1 L1,9" stroke="black" stroke-width="1.5"/>"#.to_string(),
+        "  </marker>".to_string(),
         "</defs>".to_string(),
         format!(r#"<rect width="{svg_w}" height="{svg_h}" fill="white"/>"#),
-    ];
+    ]);
 
     if !transform.is_empty() {
         parts.push(transform);
@@ -238,7 +367,7 @@ pub fn render_ir(ir: &LayoutIR, direction: &str) -> String {
 
     // Draw edges (behind nodes)
     for e in &ir.edges {
-        let svg = render_edge(e);
+        let svg = render_edge(e, corner_radius);
         if !svg.is_empty() {
             parts.push(svg);
         }
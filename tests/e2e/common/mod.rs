@@ -0,0 +1,41 @@
+//! Fixtures shared by the e2e test crates. Each crate uses only some
+//! of them.
+
+#![allow(dead_code)]
+
+/// `rows` as rendered text: one line each, every line newline-terminated.
+pub fn lines(rows: &[&str]) -> String {
+    rows.iter().map(|row| format!("{row}\n")).collect()
+}
+
+// A→D spans three layers with a free column between the two side chains.
+pub const LONG_EDGE: &str =
+    "graph TD\n    A --> B --> C --> D\n    A --> x --> y --> D\n    A -->|long| D\n";
+
+pub const DUPLICATE_SUBGRAPHS: &str = "graph TD\n    subgraph G\n        A\n    end\n    subgraph G\n        B\n    end\n    A --> B\n";
+
+pub const FAN_OUT: &str = "graph TD\n    A --> B\n    A --> C\n";
+
+pub const FAN_OUT_ROWS: [&str; 9] = [
+    "    ┌───┐",
+    "    │ A │",
+    "    └─┬─┘",
+    "      │",
+    "  ┌───┴────┐",
+    "  ▼        ▼",
+    "┌───┐    ┌───┐",
+    "│ B │    │ C │",
+    "└───┘    └───┘",
+];
+
+pub const A_TO_B_ROWS: [&str; 9] = [
+    "┌───┐",
+    "│ A │",
+    "└─┬─┘",
+    "  │",
+    "  │",
+    "  ▼",
+    "┌───┐",
+    "│ B │",
+    "└───┘",
+];
@@ -0,0 +1,5 @@
+graph BT
+    A -->|| B
+    B --> |x| C
+    C -->|unclosed D
+    D ---|y|---|z| E
@@ -0,0 +1,5 @@
+graph TD
+    A --> B
+    subgraph G
+      B --> C
+    end
@@ -0,0 +1,5 @@
+graph TD
+    end
+    end --> end
+    subgraph
+    end
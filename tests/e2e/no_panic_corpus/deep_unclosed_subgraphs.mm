@@ -0,0 +1,202 @@
+graph TD
+subgraph S0
+  subgraph S1
+    subgraph S2
+      subgraph S3
+        subgraph S4
+          subgraph S5
+            subgraph S6
+              subgraph S7
+                subgraph S8
+                  subgraph S9
+                    subgraph S10
+                      subgraph S11
+                        subgraph S12
+                          subgraph S13
+                            subgraph S14
+                              subgraph S15
+                                subgraph S16
+                                  subgraph S17
+                                    subgraph S18
+                                      subgraph S19
+                                        subgraph S20
+                                          subgraph S21
+                                            subgraph S22
+                                              subgraph S23
+                                                subgraph S24
+                                                  subgraph S25
+                                                    subgraph S26
+                                                      subgraph S27
+                                                        subgraph S28
+                                                          subgraph S29
+                                                            subgraph S30
+                                                              subgraph S31
+                                                                subgraph S32
+                                                                  subgraph S33
+                                                                    subgraph S34
+                                                                      subgraph S35
+                                                                        subgraph S36
+                                                                          subgraph S37
+                                                                            subgraph S38
+                                                                              subgraph S39
+                                                                                subgraph S40
+                                                                                  subgraph S41
+                                                                                    subgraph S42
+                                                                                      subgraph S43
+                                                                                        subgraph S44
+                                                                                          subgraph S45
+                                                                                            subgraph S46
+                                                                                              subgraph S47
+                                                                                                subgraph S48
+                                                                                                  subgraph S49
+                                                                                                    subgraph S50
+                                                                                                      subgraph S51
+                                                                                                        subgraph S52
+                                                                                                          subgraph S53
+                                                                                                            subgraph S54
+                                                                                                              subgraph S55
+                                                                                                                subgraph S56
+                                                                                                                  subgraph S57
+                                                                                                                    subgraph S58
+                                                                                                                      subgraph S59
+                                                                                                                        subgraph S60
+                                                                                                                          subgraph S61
+                                                                                                                            subgraph S62
+                                                                                                                              subgraph S63
+                                                                                                                                subgraph S64
+                                                                                                                                  subgraph S65
+                                                                                                                                    subgraph S66
+                                                                                                                                      subgraph S67
+                                                                                                                                        subgraph S68
+                                                                                                                                          subgraph S69
+                                                                                                                                            subgraph S70
+                                                                                                                                              subgraph S71
+                                                                                                                                                subgraph S72
+                                                                                                                                                  subgraph S73
+                                                                                                                                                    subgraph S74
+                                                                                                                                                      subgraph S75
+                                                                                                                                                        subgraph S76
+                                                                                                                                                          subgraph S77
+                                                                                                                                                            subgraph S78
+                                                                                                                                                              subgraph S79
+                                                                                                                                                                subgraph S80
+                                                                                                                                                                  subgraph S81
+                                                                                                                                                                    subgraph S82
+                                                                                                                                                                      subgraph S83
+                                                                                                                                                                        subgraph S84
+                                                                                                                                                                          subgraph S85
+                                                                                                                                                                            subgraph S86
+                                                                                                                                                                              subgraph S87
+                                                                                                                                                                                subgraph S88
+                                                                                                                                                                                  subgraph S89
+                                                                                                                                                                                    subgraph S90
+                                                                                                                                                                                      subgraph S91
+                                                                                                                                                                                        subgraph S92
+                                                                                                                                                                                          subgraph S93
+                                                                                                                                                                                            subgraph S94
+                                                                                                                                                                                              subgraph S95
+                                                                                                                                                                                                subgraph S96
+                                                                                                                                                                                                  subgraph S97
+                                                                                                                                                                                                    subgraph S98
+                                                                                                                                                                                                      subgraph S99
+                                                                                                                                                                                                        subgraph S100
+                                                                                                                                                                                                          subgraph S101
+                                                                                                                                                                                                            subgraph S102
+                                                                                                                                                                                                              subgraph S103
+                                                                                                                                                                                                                subgraph S104
+                                                                                                                                                                                                                  subgraph S105
+                                                                                                                                                                                                                    subgraph S106
+                                                                                                                                                                                                                      subgraph S107
+                                                                                                                                                                                                                        subgraph S108
+                                                                                                                                                                                                                          subgraph S109
+                                                                                                                                                                                                                            subgraph S110
+                                                                                                                                                                                                                              subgraph S111
+                                                                                                                                                                                                                                subgraph S112
+                                                                                                                                                                                                                                  subgraph S113
+                                                                                                                                                                                                                                    subgraph S114
+                                                                                                                                                                                                                                      subgraph S115
+                                                                                                                                                                                                                                        subgraph S116
+                                                                                                                                                                                                                                          subgraph S117
+                                                                                                                                                                                                                                            subgraph S118
+                                                                                                                                                                                                                                              subgraph S119
+                                                                                                                                                                                                                                                subgraph S120
+                                                                                                                                                                                                                                                  subgraph S121
+                                                                                                                                                                                                                                                    subgraph S122
+                                                                                                                                                                                                                                                      subgraph S123
+                                                                                                                                                                                                                                                        subgraph S124
+                                                                                                                                                                                                                                                          subgraph S125
+                                                                                                                                                                                                                                                            subgraph S126
+                                                                                                                                                                                                                                                              subgraph S127
+                                                                                                                                                                                                                                                                subgraph S128
+                                                                                                                                                                                                                                                                  subgraph S129
+                                                                                                                                                                                                                                                                    subgraph S130
+                                                                                                                                                                                                                                                                      subgraph S131
+                                                                                                                                                                                                                                                                        subgraph S132
+                                                                                                                                                                                                                                                                          subgraph S133
+                                                                                                                                                                                                                                                                            subgraph S134
+                                                                                                                                                                                                                                                                              subgraph S135
+                                                                                                                                                                                                                                                                                subgraph S136
+                                                                                                                                                                                                                                                                                  subgraph S137
+                                                                                                                                                                                                                                                                                    subgraph S138
+                                                                                                                                                                                                                                                                                      subgraph S139
+                                                                                                                                                                                                                                                                                        subgraph S140
+                                                                                                                                                                                                                                                                                          subgraph S141
+                                                                                                                                                                                                                                                                                            subgraph S142
+                                                                                                                                                                                                                                                                                              subgraph S143
+                                                                                                                                                                                                                                                                                                subgraph S144
+                                                                                                                                                                                                                                                                                                  subgraph S145
+                                                                                                                                                                                                                                                                                                    subgraph S146
+                                                                                                                                                                                                                                                                                                      subgraph S147
+                                                                                                                                                                                                                                                                                                        subgraph S148
+                                                                                                                                                                                                                                                                                                          subgraph S149
+                                                                                                                                                                                                                                                                                                            subgraph S150
+                                                                                                                                                                                                                                                                                                              subgraph S151
+                                                                                                                                                                                                                                                                                                                subgraph S152
+                                                                                                                                                                                                                                                                                                                  subgraph S153
+                                                                                                                                                                                                                                                                                                                    subgraph S154
+                                                                                                                                                                                                                                                                                                                      subgraph S155
+                                                                                                                                                                                                                                                                                                                        subgraph S156
+                                                                                                                                                                                                                                                                                                                          subgraph S157
+                                                                                                                                                                                                                                                                                                                            subgraph S158
+                                                                                                                                                                                                                                                                                                                              subgraph S159
+                                                                                                                                                                                                                                                                                                                                subgraph S160
+                                                                                                                                                                                                                                                                                                                                  subgraph S161
+                                                                                                                                                                                                                                                                                                                                    subgraph S162
+                                                                                                                                                                                                                                                                                                                                      subgraph S163
+                                                                                                                                                                                                                                                                                                                                        subgraph S164
+                                                                                                                                                                                                                                                                                                                                          subgraph S165
+                                                                                                                                                                                                                                                                                                                                            subgraph S166
+                                                                                                                                                                                                                                                                                                                                              subgraph S167
+                                                                                                                                                                                                                                                                                                                                                subgraph S168
+                                                                                                                                                                                                                                                                                                                                                  subgraph S169
+                                                                                                                                                                                                                                                                                                                                                    subgraph S170
+                                                                                                                                                                                                                                                                                                                                                      subgraph S171
+                                                                                                                                                                                                                                                                                                                                                        subgraph S172
+                                                                                                                                                                                                                                                                                                                                                          subgraph S173
+                                                                                                                                                                                                                                                                                                                                                            subgraph S174
+                                                                                                                                                                                                                                                                                                                                                              subgraph S175
+                                                                                                                                                                                                                                                                                                                                                                subgraph S176
+                                                                                                                                                                                                                                                                                                                                                                  subgraph S177
+                                                                                                                                                                                                                                                                                                                                                                    subgraph S178
+                                                                                                                                                                                                                                                                                                                                                                      subgraph S179
+                                                                                                                                                                                                                                                                                                                                                                        subgraph S180
+                                                                                                                                                                                                                                                                                                                                                                          subgraph S181
+                                                                                                                                                                                                                                                                                                                                                                            subgraph S182
+                                                                                                                                                                                                                                                                                                                                                                              subgraph S183
+                                                                                                                                                                                                                                                                                                                                                                                subgraph S184
+                                                                                                                                                                                                                                                                                                                                                                                  subgraph S185
+                                                                                                                                                                                                                                                                                                                                                                                    subgraph S186
+                                                                                                                                                                                                                                                                                                                                                                                      subgraph S187
+                                                                                                                                                                                                                                                                                                                                                                                        subgraph S188
+                                                                                                                                                                                                                                                                                                                                                                                          subgraph S189
+                                                                                                                                                                                                                                                                                                                                                                                            subgraph S190
+                                                                                                                                                                                                                                                                                                                                                                                              subgraph S191
+                                                                                                                                                                                                                                                                                                                                                                                                subgraph S192
+                                                                                                                                                                                                                                                                                                                                                                                                  subgraph S193
+                                                                                                                                                                                                                                                                                                                                                                                                    subgraph S194
+                                                                                                                                                                                                                                                                                                                                                                                                      subgraph S195
+                                                                                                                                                                                                                                                                                                                                                                                                        subgraph S196
+                                                                                                                                                                                                                                                                                                                                                                                                          subgraph S197
+                                                                                                                                                                                                                                                                                                                                                                                                            subgraph S198
+                                                                                                                                                                                                                                                                                                                                                                                                              subgraph S199
+A --> B
@@ -0,0 +1,5 @@
+graph LR
+    A[] --> B{}
+    B --> C()
+    C --> D(())
+    D --> E[""]
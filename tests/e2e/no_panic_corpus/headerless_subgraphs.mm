@@ -0,0 +1,7 @@
+subgraph A
+subgraph A
+A --> A
+end
+end
+end
+end
@@ -0,0 +1,4 @@
+graph TD
+    A --> A
+    A --> A
+    B --> B --> B
@@ -0,0 +1,4 @@
+graph TD
+    A --> B & C & --> D
+    & --> &
+    A:::x --> B:::
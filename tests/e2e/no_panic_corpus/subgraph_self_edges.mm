@@ -0,0 +1,7 @@
+graph RL
+    subgraph G
+        A
+    end
+    G --> G
+    A --> G
+    G --> A
@@ -0,0 +1,4 @@
+graph TD
+    中文[节点] --> é{ß}
+    é -->|→| 🎉((✓))
+    🎉 --> 中文
@@ -0,0 +1,2 @@
+graph TD
+    A["unterminated --> B
@@ -0,0 +1,271 @@
+//! Integration test: the analysis, checking and formatting entry points of
+//! the public API, compared against exact expected values.
+
+mod common;
+
+use common::DUPLICATE_SUBGRAPHS;
+use mermaid_ascii::{RenderError, check_dsl, config_default, graph_stats, render_dsl_with_config};
+
+#[test]
+fn test_stats_table_rows() {
+    use mermaid_ascii::{CharSet, render_stats_table};
+
+    let src = "graph TD\n    A --> B\n    A --> C\n    C --> A\n";
+    assert_eq!(
+        render_stats_table(src, &config_default()).unwrap(),
+        "┌───────────────────┐\n\
+         │ nodes           3 │\n\
+         │ edges           3 │\n\
+         │ subgraphs       0 │\n\
+         │ layers          2 │\n\
+         │ cyclic        yes │\n\
+         │ widest layer    2 │\n\
+         └───────────────────┘\n"
+    );
+    let mut ascii = config_default();
+    ascii.charset = CharSet::Ascii;
+    assert_eq!(
+        render_stats_table(src, &ascii).unwrap(),
+        "+-------------------+\n\
+         | nodes           3 |\n\
+         | edges           3 |\n\
+         | subgraphs       0 |\n\
+         | layers          2 |\n\
+         | cyclic        yes |\n\
+         | widest layer    2 |\n\
+         +-------------------+\n"
+    );
+    assert!(render_stats_table("sequenceDiagram\n", &config_default()).is_err());
+}
+
+#[test]
+fn test_duplicate_subgraph_names_warn() {
+    let stats = graph_stats(DUPLICATE_SUBGRAPHS);
+    assert_eq!(stats.subgraph_count, 2);
+    assert_eq!(
+        stats.warnings,
+        vec!["duplicate subgraph name 'G'; later block renamed to 'G (2)'".to_string()]
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_config_from_json_sets_padding_and_direction() {
+    use mermaid_ascii::{CharSet, config_from_json};
+
+    let config =
+        config_from_json(r#"{"padding": 3, "direction": "LR", "charset": "ascii"}"#).unwrap();
+    assert_eq!(config.padding, 3);
+    assert_eq!(config.direction_override.as_deref(), Some("LR"));
+    assert_eq!(config.charset, CharSet::Ascii);
+    assert_eq!(config.h_gap, config_default().h_gap);
+    assert_eq!(config_from_json("{}").unwrap(), config_default());
+    assert_eq!(
+        config_from_json(r#"{"charset": "fancy"}"#),
+        Err(RenderError::Config("unknown charset 'fancy'".to_string()))
+    );
+    assert!(matches!(
+        config_from_json("not json"),
+        Err(RenderError::Config(_))
+    ));
+}
+
+#[test]
+fn test_node_summary_lists_resolved_nodes() {
+    use mermaid_ascii::{NodeShape, node_summary};
+
+    assert_eq!(
+        node_summary("graph TD\n    A[Start] --> B((End))\n").unwrap(),
+        vec![
+            ("A".to_string(), "Start".to_string(), NodeShape::Rectangle),
+            ("B".to_string(), "End".to_string(), NodeShape::Circle),
+        ]
+    );
+    // A later definition beats the bare first reference
+    let summary =
+        node_summary("graph TD\n    A --> B\n    subgraph G\n        B{Check}\n    end\n");
+    assert_eq!(
+        summary.unwrap()[1],
+        ("B".to_string(), "Check".to_string(), NodeShape::Diamond)
+    );
+}
+
+#[test]
+fn test_graph_stats_warns_on_orphan_nodes() {
+    let stats = graph_stats("graph TD\n    A --> B\n    C\n    subgraph S\n        D\n    end\n");
+    assert_eq!(
+        stats.warnings,
+        vec!["orphan node 'C' has no edges; check for a typo in an edge".to_string()]
+    );
+    assert!(graph_stats("graph TD\n    A\n").warnings.is_empty());
+}
+
+#[test]
+fn test_graph_stats_notes_unsupported_features() {
+    let src = "graph TD\n    A --> B\n    click A callback \"Tip\"\n    B@{ shape: cyl } --> C\n    click B href\n    classDef hot fill:#f00\n";
+    let stats = graph_stats(src);
+    assert_eq!(
+        stats.warnings,
+        vec![
+            "unsupported: click".to_string(),
+            "unsupported: @{}".to_string(),
+            "unsupported: classDef".to_string()
+        ]
+    );
+    // The rest of each line still parses; nothing becomes a stray node
+    assert_eq!((stats.node_count, stats.edge_count), (3, 2));
+    assert_eq!(check_dsl(src), Ok(()));
+}
+
+#[test]
+fn test_check_dsl_reports_first_unparsed_position() {
+    assert_eq!(check_dsl("graph TD\n    A --> B;\n    B --> C\n"), Ok(()));
+    assert_eq!(
+        check_dsl("graph TD\n    A --> B\n    B --> ))\n"),
+        Err(RenderError::Parse {
+            line: 3,
+            col: 7,
+            msg: "cannot parse '--> ))'".to_string()
+        })
+    );
+    // Line numbers count the front matter too
+    assert_eq!(
+        check_dsl("---\ntitle: x\n---\ngraph TD\n    A -->\n")
+            .unwrap_err()
+            .to_string(),
+        "line 5, column 7: cannot parse '-->'"
+    );
+}
+
+#[test]
+fn test_other_diagram_types_are_unsupported() {
+    use mermaid_ascii::render_dsl;
+
+    let src = "%% greeting\nsequenceDiagram\n    Alice->>Bob: Hi\n";
+    let expected = RenderError::Unsupported("sequenceDiagram".to_string());
+    assert_eq!(render_dsl(src, true, 1, None), Err(expected.clone()));
+    assert_eq!(check_dsl(src), Err(expected.clone()));
+    assert_eq!(
+        expected.to_string(),
+        "unsupported diagram type 'sequenceDiagram': only flowcharts render"
+    );
+    assert_eq!(
+        render_dsl_with_config("pie title Pets\n", &config_default()),
+        Err(RenderError::Unsupported("pie".to_string()))
+    );
+    // Only the header counts: a node named like a keyword still renders
+    assert!(render_dsl("graph TD\n    gantt --> pie\n", true, 1, None).is_ok());
+}
+
+#[test]
+fn test_subgraph_nesting_limit() {
+    use mermaid_ascii::render_svg_dsl;
+
+    let nested = |levels: usize| {
+        let mut src = String::from("graph TD\n");
+        for i in 0..levels {
+            src.push_str(&format!("subgraph S{i}\n"));
+        }
+        src.push_str("A\n");
+        src.push_str(&"end\n".repeat(levels));
+        src
+    };
+    let config = config_default();
+    assert!(render_dsl_with_config(&nested(3), &config).is_ok());
+    assert!(check_dsl(&nested(64)).is_ok());
+
+    let expected = RenderError::LimitExceeded("subgraphs nested deeper than 64 levels".to_string());
+    assert_eq!(
+        render_dsl_with_config(&nested(65), &config).unwrap_err(),
+        expected
+    );
+    assert_eq!(render_svg_dsl(&nested(65), 1, None).unwrap_err(), expected);
+    assert_eq!(check_dsl(&nested(65)).unwrap_err(), expected);
+    // Far past the limit: still a clean error, not a stack overflow.
+    assert_eq!(
+        render_dsl_with_config(&nested(100_000), &config).unwrap_err(),
+        expected
+    );
+
+    let mut config = config_default();
+    config.max_subgraph_depth = 2;
+    let err = render_dsl_with_config(&nested(3), &config).unwrap_err();
+    assert_eq!(err.to_string(), "subgraphs nested deeper than 2 levels");
+}
+
+#[test]
+fn test_format_dsl_is_canonical_and_stable() {
+    use mermaid_ascii::format_dsl;
+
+    let src = "flowchart LR\n  %% comment\n  A[Start] --> B{Ok?} -->|yes| C((Done)); B -.->|no| A\n  D <-- E\n  subgraph Inner Group\n  direction TB\n  X[\" pad \"]:::hot --> end[Finish]\n  end\n  class A,B hot\n";
+    let once = format_dsl(src).unwrap();
+    assert_eq!(
+        once,
+        "graph LR\n    A[Start]\n    B{Ok?}\n    C((Done))\n    D\n    A --> B\n    B -->|yes| C\n    B -.->|no| A\n    E --> D\n    subgraph Inner Group\n        direction TD\n        X[\" pad \"]:::hot\n        end[Finish]\n        X --> end[Finish]\n    end\n    class A,B hot\n"
+    );
+    assert_eq!(format_dsl(&once).unwrap(), once);
+
+    let (before, after) = (graph_stats(src), graph_stats(&once));
+    assert_eq!(before.node_count, after.node_count);
+    assert_eq!(before.edge_count, after.edge_count);
+    assert_eq!(before.subgraph_count, after.subgraph_count);
+    let config = config_default();
+    assert_eq!(
+        render_dsl_with_config(&once, &config),
+        render_dsl_with_config(src, &config)
+    );
+    assert_eq!(format_dsl("  \n").unwrap(), "");
+}
+
+#[test]
+fn test_direction_rotation() {
+    use mermaid_ascii::{Direction, rerender_rotated};
+
+    for d in [Direction::TD, Direction::LR, Direction::BT, Direction::RL] {
+        assert_eq!(Direction::from_name(d.as_str()), Some(d.clone()));
+        assert_eq!(d.rotate_cw().rotate_ccw(), d);
+        assert_eq!(d.opposite().opposite(), d);
+    }
+    assert_eq!(Direction::TD.rotate_cw(), Direction::LR);
+    assert_eq!(Direction::TD.rotate_ccw(), Direction::RL);
+    assert_eq!(Direction::LR.opposite(), Direction::RL);
+    assert_eq!(Direction::from_name("TB"), Some(Direction::TD));
+
+    let src = "graph TD\n    A --> B\n    A --> C\n    B --> D\n";
+    let original = render_dsl_with_config(src, &config_default()).unwrap();
+    let mut lr = config_default();
+    lr.direction_override = Some("LR".to_string());
+    let quarter = rerender_rotated(src, 1, &config_default()).unwrap();
+    assert_eq!(quarter, render_dsl_with_config(src, &lr).unwrap());
+    assert_ne!(quarter, original);
+
+    // 180° there and back again
+    let mut turned = config_default();
+    turned.direction_override = Some(Direction::TD.opposite().as_str().to_string());
+    assert_ne!(
+        rerender_rotated(src, 2, &config_default()).unwrap(),
+        original
+    );
+    assert_eq!(rerender_rotated(src, 2, &turned).unwrap(), original);
+    assert_eq!(
+        rerender_rotated(src, -4, &config_default()).unwrap(),
+        original
+    );
+}
+
+#[test]
+fn test_has_cycle_and_longest_path_length() {
+    use mermaid_ascii::{has_cycle, longest_path_length};
+
+    let chain = "graph TD\n    A --> B --> C --> D\n";
+    assert!(!has_cycle(chain));
+    assert_eq!(longest_path_length(chain), 3);
+
+    let cyclic = "graph TD\n    A --> B --> C --> A\n";
+    assert!(has_cycle(cyclic));
+    assert_eq!(longest_path_length(cyclic), 2);
+    assert!(has_cycle("graph TD\n    A --> A\n"));
+
+    assert!(!has_cycle(""));
+    assert_eq!(longest_path_length("graph TD\n    A\n    B\n"), 0);
+}
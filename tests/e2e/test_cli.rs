@@ -0,0 +1,155 @@
+//! Integration test: run the `mermaid-ascii` binary and check exit codes and output.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const MALFORMED: &str = "graph TD\n    A --> B\n    B --> ))\n";
+
+fn run(args: &[&str], input_name: &str, input: &str) -> std::process::Output {
+    let path =
+        std::env::temp_dir().join(format!("mermaid-ascii-{}-{input_name}", std::process::id()));
+    std::fs::write(&path, input).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_mermaid-ascii"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    out
+}
+
+#[test]
+fn test_malformed_input_is_lenient_by_default() {
+    let out = run(&[], "lenient.mm", MALFORMED);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("│ B │"));
+}
+
+#[test]
+fn test_strict_fails_with_line_number() {
+    let out = run(&["--strict"], "strict.mm", MALFORMED);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(out.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("line 3, column 7"), "{stderr}");
+}
+
+#[test]
+fn test_strict_accepts_valid_input() {
+    let out = run(&["--strict"], "valid.mm", "graph TD\n    A --> B;\n");
+    assert_eq!(out.status.code(), Some(0));
+}
+
+#[test]
+fn test_line_ending_flags() {
+    let src = "graph TD\n    A\n";
+    let stdout = |args: &[&str]| String::from_utf8(run(args, "eol.mm", src).stdout).unwrap();
+    assert_eq!(stdout(&["-a"]), "+---+\n| A |\n+---+\n");
+    assert_eq!(stdout(&["-a", "--crlf"]), "+---+\r\n| A |\r\n+---+\r\n");
+    assert_eq!(
+        stdout(&["-a", "--no-trailing-newline"]),
+        "+---+\n| A |\n+---+"
+    );
+}
+
+#[test]
+fn test_no_trim_keeps_full_canvas() {
+    let src = "graph TD\n    A --> B\n    A --> LongerName\n";
+    let out = run(&["--no-trim"], "no_trim.mm", src);
+    assert_eq!(out.status.code(), Some(0));
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let widths: Vec<usize> = stdout.lines().map(|l| l.chars().count()).collect();
+    assert!(widths.iter().all(|&w| w == widths[0]), "{stdout}");
+    assert!(stdout.lines().last().unwrap().trim().is_empty(), "{stdout}");
+
+    let trimmed = run(&[], "trim.mm", src);
+    let trimmed = String::from_utf8(trimmed.stdout).unwrap();
+    assert!(trimmed.lines().all(|l| !l.ends_with(' ')), "{trimmed}");
+
+    // RL mirrors each row; the full canvas width survives the flip
+    let src = "graph RL\n    A --> B\n    A --> LongerName\n";
+    let out = run(&["--no-trim"], "no_trim_rl.mm", src);
+    assert_eq!(out.status.code(), Some(0));
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let widths: Vec<usize> = stdout.lines().map(|l| l.chars().count()).collect();
+    assert!(widths.iter().all(|&w| w == widths[0]), "{stdout}");
+    assert!(stdout.lines().any(|l| l.ends_with(' ')), "{stdout}");
+}
+
+#[test]
+fn test_dash_reads_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mermaid-ascii"))
+        .args(["-a", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"graph TD\n    A\n")
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "+---+\n| A |\n+---+\n"
+    );
+}
+
+#[test]
+fn test_double_dash_allows_leading_dash_file_name() {
+    let dir = std::env::temp_dir();
+    let name = format!("-mermaid-ascii-{}.mm", std::process::id());
+    std::fs::write(dir.join(&name), "graph TD\n    A\n").unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_mermaid-ascii"))
+        .current_dir(&dir)
+        .args(["-a", "--", &name])
+        .output()
+        .unwrap();
+    std::fs::remove_file(dir.join(&name)).unwrap();
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "+---+\n| A |\n+---+\n"
+    );
+}
+
+#[test]
+fn test_directory_input_is_a_clear_error() {
+    let out = Command::new(env!("CARGO_BIN_EXE_mermaid-ascii"))
+        .arg(std::env::temp_dir())
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert!(out.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("is a directory"), "{stderr}");
+}
+
+#[test]
+fn test_failed_render_leaves_output_file_untouched() {
+    let target =
+        std::env::temp_dir().join(format!("mermaid-ascii-{}-kept.txt", std::process::id()));
+    std::fs::write(&target, "previous output\n").unwrap();
+    let out = run(
+        &["-o", target.to_str().unwrap()],
+        "unsupported.mm",
+        "pie\n    \"a\" : 1\n",
+    );
+    let kept = std::fs::read_to_string(&target).unwrap();
+    std::fs::remove_file(&target).unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(kept, "previous output\n");
+
+    let out = run(
+        &["-a", "-o", target.to_str().unwrap()],
+        "written.mm",
+        "graph TD\n    A\n",
+    );
+    let written = std::fs::read_to_string(&target).unwrap();
+    std::fs::remove_file(&target).unwrap();
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(written, "+---+\n| A |\n+---+\n");
+}
@@ -14,7 +14,7 @@ fn test_all_examples_txt() {
     for entry in fs::read_dir(examples_dir).expect("_site/examples/ dir must exist") {
         let entry = entry.unwrap();
         let path = entry.path();
-        if path.extension().map_or(true, |e| e != "md") {
+        if path.extension().is_none_or(|e| e != "md") {
             continue;
         }
         let name = path.file_stem().unwrap().to_string_lossy();
@@ -55,7 +55,7 @@ fn test_all_examples_svg() {
     for entry in fs::read_dir(examples_dir).expect("_site/examples/ dir must exist") {
         let entry = entry.unwrap();
         let path = entry.path();
-        if path.extension().map_or(true, |e| e != "md") {
+        if path.extension().is_none_or(|e| e != "md") {
             continue;
         }
         let name = path.file_stem().unwrap().to_string_lossy();
@@ -86,3 +86,34 @@ fn test_all_examples_svg() {
         failures.join(", ")
     );
 }
+
+#[test]
+fn test_all_examples_have_no_overlaps() {
+    use mermaid_ascii::{config_new, overlap_warnings};
+
+    let examples_dir = Path::new("_site/examples");
+    let mut config = config_new(true, 1, None);
+    config.assert_no_overlap = true;
+    let mut tested = 0;
+    let mut failures = Vec::new();
+
+    for entry in fs::read_dir(examples_dir).expect("_site/examples/ dir must exist") {
+        let path = entry.unwrap().path();
+        if !path.to_string_lossy().ends_with(".mm.md") {
+            continue;
+        }
+        let input = fs::read_to_string(&path).unwrap();
+        let warnings = overlap_warnings(&input, &config).unwrap();
+        if !warnings.is_empty() {
+            failures.push(format!("{}: {}", path.display(), warnings.join("; ")));
+        }
+        tested += 1;
+    }
+
+    assert!(tested > 0, "no examples found to test");
+    assert!(
+        failures.is_empty(),
+        "overlapping nodes in:\n{}",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,132 @@
+//! Robustness test: random, Mermaid-like and mutated inputs must never panic
+//! and must always terminate, in every charset/direction/option combination.
+//!
+//! Inputs that once misbehaved (or are nasty by construction) live in
+//! `tests/e2e/no_panic_corpus/` and are replayed on every run.
+
+use mermaid_ascii::{
+    CharSet, config_default, format_dsl, graph_stats, render_dsl_with_config, render_svg_dsl,
+};
+use proptest::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Generous per-input budget; real inputs render in milliseconds.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Render `src` through every public entry point and option combination.
+fn render_everything(src: &str) {
+    for direction in [None, Some("LR"), Some("RL"), Some("BT")] {
+        for (charset, extras) in [
+            (CharSet::Unicode, false),
+            (CharSet::Ascii, true),
+            (CharSet::UnicodeArrows, false),
+        ] {
+            let mut config = config_default();
+            config.charset = charset;
+            config.direction_override = direction.map(|d| d.to_string());
+            config.straighten = extras;
+            config.odd_widths = extras;
+            config.super_source = extras;
+            config.label_rows = extras;
+            let _ = render_dsl_with_config(src, &config);
+        }
+        let _ = render_svg_dsl(src, 1, direction);
+    }
+    let _ = graph_stats(src);
+    let _ = format_dsl(src);
+}
+
+/// Run `render_everything` on a worker thread so a hang fails the test
+/// instead of stalling the suite. Panics on the worker are re-raised.
+fn assert_no_panic(src: &str) {
+    let owned = src.to_string();
+    let (tx, rx) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        render_everything(&owned);
+        let _ = tx.send(());
+    });
+    match rx.recv_timeout(TIMEOUT) {
+        Ok(()) => worker.join().unwrap(),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            if let Err(e) = worker.join() {
+                std::panic::resume_unwind(e);
+            }
+        }
+        Err(mpsc::RecvTimeoutError::Timeout) => panic!("render did not terminate for {src:?}"),
+    }
+}
+
+#[rustfmt::skip]
+const TOKENS: &[&str] = &[
+    "graph TD\n", "graph LR\n", "flowchart RL\n", "graph BT\n", "A", "B", "C", "x1", "end",
+    "subgraph ", "subgraph S\n", "direction LR\n", "-->", "---", "-.->", "==>", "<-->", "<-.->",
+    "<==>", "-.-", "===", "|", "|lbl|", "[", "]", "(", ")", "{", "}", "((", "))", "\"", "\n", " ",
+    "\t", "%%", "%% c\n", ":::", "&", ";", "é", "中", "\\n", "<br>", "A[x]", "B{y}", "C(z)",
+    "-- t -->", "--", "-", ">", "<", "=", ".", "@{", "click A", "\r\n", "0", "_",
+];
+
+fn example_sources() -> Vec<String> {
+    let mut sources = Vec::new();
+    for entry in fs::read_dir("_site/examples").expect("_site/examples/ dir must exist") {
+        let path = entry.unwrap().path();
+        if path.to_string_lossy().ends_with(".mm.md") {
+            sources.push(fs::read_to_string(&path).unwrap());
+        }
+    }
+    sources.sort();
+    sources
+}
+
+#[test]
+fn test_corpus_never_panics() {
+    let corpus_dir = Path::new("tests/e2e/no_panic_corpus");
+    let mut tested = 0;
+    for entry in fs::read_dir(corpus_dir).expect("no_panic_corpus/ dir must exist") {
+        let path = entry.unwrap().path();
+        let bytes = fs::read(&path).unwrap();
+        assert_no_panic(&String::from_utf8_lossy(&bytes));
+        tested += 1;
+    }
+    assert!(tested > 0, "no corpus inputs found");
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn prop_token_soup_never_panics(
+        tokens in prop::collection::vec(prop::sample::select(TOKENS), 0..40)
+    ) {
+        assert_no_panic(&tokens.concat());
+    }
+
+    #[test]
+    fn prop_arbitrary_text_never_panics(src in "\\PC{0,120}") {
+        assert_no_panic(&src);
+    }
+
+    #[test]
+    fn prop_mutated_examples_never_panic(
+        pick in any::<prop::sample::Index>(),
+        edits in prop::collection::vec((any::<prop::sample::Index>(), any::<char>(), 0u8..3), 1..8),
+    ) {
+        let sources = example_sources();
+        let mut chars: Vec<char> = pick.get(&sources).chars().collect();
+        for (at, ch, op) in edits {
+            let pos = at.index(chars.len() + 1);
+            match op {
+                0 => chars.insert(pos, ch),
+                1 if pos < chars.len() => {
+                    chars.remove(pos);
+                }
+                _ if pos < chars.len() => chars[pos] = ch,
+                _ => {}
+            }
+        }
+        assert_no_panic(&chars.into_iter().collect::<String>());
+    }
+}
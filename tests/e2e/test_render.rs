@@ -0,0 +1,989 @@
+//! Integration test: render small diagrams through the public API and compare
+//! the output against exact expected text.
+
+mod common;
+
+use common::{A_TO_B_ROWS, DUPLICATE_SUBGRAPHS, FAN_OUT, FAN_OUT_ROWS, LONG_EDGE, lines};
+use mermaid_ascii::{config_default, render_dsl, render_dsl_with_config};
+
+#[test]
+fn test_progress_bar_rejects_signed_percent() {
+    let mut config = config_default();
+    config.progress_bars = true;
+    // Not a percentage the bar can show: drawn as written, not a panic
+    assert_eq!(
+        render_dsl_with_config("graph TD\n    A[\"Task|-50%\"]\n", &config).unwrap(),
+        "┌───────────┐\n│ Task|-50% │\n└───────────┘\n"
+    );
+    assert_eq!(
+        render_dsl_with_config("graph TD\n    A[\"Task|+5%\"]\n", &config).unwrap(),
+        "┌──────────┐\n│ Task|+5% │\n└──────────┘\n"
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_svg_corner_radius_reaches_svg() {
+    use mermaid_ascii::{config_from_json, render_svg_with_config};
+
+    let src = "graph TD\n    A --> B\n    A --> C\n";
+    let mut sharp = config_default();
+    sharp.svg_corner_radius = 0;
+    let from_json = config_from_json(r#"{"svgCornerRadius": 0}"#).unwrap();
+    assert_eq!(from_json.svg_corner_radius, 0);
+    let svg = render_svg_with_config(src, &from_json).unwrap();
+    assert_eq!(svg, render_svg_with_config(src, &sharp).unwrap());
+    assert_ne!(svg, render_svg_with_config(src, &config_default()).unwrap());
+}
+
+#[test]
+fn test_inline_trivial_matches_full_render_options() {
+    use mermaid_ascii::{RenderConfig, measure_dsl, render_to_canvas, render_to_cells};
+
+    let mut config = config_default();
+    config.inline_trivial = true;
+    let render = |src: &str, config: &RenderConfig| render_dsl_with_config(src, config).unwrap();
+    // RL points the arrow back at the source, as the full layout does
+    assert_eq!(render("graph RL\n    A --> B\n", &config), "[B] ◄─── [A]\n");
+    assert_eq!(
+        render("graph RL\n    A -->|yes| B\n", &config),
+        "[B] ◄──yes─ [A]\n"
+    );
+    config.direction_override = Some("RL".to_string());
+    assert_eq!(render("graph LR\n    A --> B\n", &config), "[B] ◄─── [A]\n");
+    config.direction_override = None;
+
+    // Label rewrites run first
+    config.decode_entities = true;
+    assert_eq!(
+        render("graph LR\n    A[\"a &amp; b\"] --> B\n", &config),
+        "[a & b] ───► [B]\n"
+    );
+    config.decode_entities = false;
+
+    // Front matter is stripped, not read as part of the diagram
+    let src = "---\nconfig:\n  flowchart:\n    nodeSpacing: 100\n---\ngraph LR\n    A --> B\n";
+    assert_eq!(render(src, &config), "[A] ───► [B]\n");
+
+    // Every entry point draws the same line
+    let src = "graph LR\n    A --> B\n";
+    assert_eq!(measure_dsl(src, &config).unwrap(), (12, 1));
+    let cells = render_to_cells(src, &config).unwrap();
+    assert_eq!(cells, vec!["[A] ───► [B]".chars().collect::<Vec<_>>()]);
+    let canvas = render_to_canvas(src, &config).unwrap();
+    assert_eq!(canvas.render_to_string(), "[A] ───► [B]\n");
+}
+
+#[test]
+fn test_format_keeps_pos_pins() {
+    use mermaid_ascii::format_dsl;
+
+    let src =
+        "graph TD\n    A --> B\n    A --> C\n    C --> D\n    %% pos C 1 0\n    %% pos D 3 0\n";
+    let formatted = format_dsl(src).unwrap();
+    assert_eq!(
+        formatted,
+        "graph TD\n    A --> B\n    A --> C\n    C --> D\n    %% pos C 1 0\n    %% pos D 3 0\n"
+    );
+    let render = |src: &str| render_dsl_with_config(src, &config_default()).unwrap();
+    assert_eq!(render(&formatted), render(src));
+    // The pins do move nodes, so a formatter dropping them would show
+    assert_ne!(
+        render(src),
+        render("graph TD\n    A --> B\n    A --> C\n    C --> D\n")
+    );
+}
+
+#[test]
+fn test_format_keeps_route_hints() {
+    use mermaid_ascii::format_dsl;
+
+    let src = "graph TD\n    A --> B\n    %% route A B col=10\n";
+    let formatted = format_dsl(src).unwrap();
+    assert_eq!(formatted, src);
+    let mut config = config_default();
+    config.v_gap = Some(6);
+    let render = |src: &str| render_dsl_with_config(src, &config).unwrap();
+    assert_eq!(render(&formatted), render(src));
+    assert_ne!(render(src), render("graph TD\n    A --> B\n"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_label_gaps() {
+    use mermaid_ascii::config_from_json;
+
+    assert!(config_from_json("{}").unwrap().label_gaps);
+    let config = config_from_json(r#"{"labelGaps": false}"#).unwrap();
+    assert!(!config.label_gaps);
+    let mut expected = config_default();
+    expected.label_gaps = false;
+    let src = "graph TD\n    A -->|yes| B\n";
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        render_dsl_with_config(src, &expected).unwrap()
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_box_chars() {
+    use mermaid_ascii::{config_from_json, diamond_corners_unicode};
+
+    let config =
+        config_from_json(r#"{"boxChars": {"diamondCorners": ["◢", "◣", "◥", "◤"]}}"#).unwrap();
+    assert_eq!(config.box_chars.diamond_corners, diamond_corners_unicode());
+    let mut expected = config_default();
+    expected.box_chars.diamond_corners = diamond_corners_unicode();
+    let src = "graph TD\n    A{Ok?} --> B\n";
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        render_dsl_with_config(src, &expected).unwrap()
+    );
+    assert!(config_from_json(r#"{"boxChars": {"diamondCorners": ["<", ">"]}}"#).is_err());
+}
+
+#[test]
+fn test_explicit_ascii_charset_matches_bool_api() {
+    use mermaid_ascii::CharSet;
+
+    let src = "graph TD\n    A[Start] --> B{Check}\n    B -->|yes| C(Done)\n";
+    let mut config = config_default();
+    config.charset = CharSet::Ascii;
+    let explicit = render_dsl_with_config(src, &config).unwrap();
+    assert_eq!(
+        explicit,
+        lines(&[
+            "+-------+",
+            "| Start |",
+            "+---+---+",
+            "    |",
+            "    |",
+            "    |",
+            "/---v---\\",
+            "| Check |",
+            "\\---+---/",
+            "    |",
+            "    |",
+            "    yes",
+            "    v",
+            "+------+",
+            "| Done |",
+            "+------+",
+        ])
+    );
+    assert_eq!(explicit, render_dsl(src, false, 1, None).unwrap());
+}
+
+#[test]
+fn test_straighten_off_by_default() {
+    let config = config_default();
+    assert!(!config.straighten);
+    let plain = render_dsl(LONG_EDGE, true, 1, None).unwrap();
+    assert_eq!(render_dsl_with_config(LONG_EDGE, &config).unwrap(), plain);
+}
+
+#[test]
+fn test_quoted_whitespace_label_renders_blank_box() {
+    let out = render_dsl("graph TD\n    A[\" \"]\n", true, 1, None).unwrap();
+    assert_eq!(out, "┌───┐\n│   │\n└───┘\n");
+}
+
+#[test]
+fn test_unquoted_whitespace_label_falls_back_to_id() {
+    let out = render_dsl("graph TD\n    A[   ]\n", true, 1, None).unwrap();
+    assert_eq!(out, "┌───┐\n│ A │\n└───┘\n");
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn test_front_matter_node_spacing_widens_horizontal_gap() {
+    assert_eq!(
+        render_dsl(FAN_OUT, true, 1, None).unwrap(),
+        lines(&FAN_OUT_ROWS)
+    );
+    let src =
+        format!("---\ntheme: forest\nconfig:\n  flowchart:\n    nodeSpacing: 100\n---\n{FAN_OUT}");
+    assert_eq!(
+        render_dsl(&src, true, 1, None).unwrap(),
+        lines(&[
+            "      ┌───┐",
+            "      │ A │",
+            "      └─┬─┘",
+            "        │",
+            "  ┌─────┴──────┐",
+            "  ▼            ▼",
+            "┌───┐        ┌───┐",
+            "│ B │        │ C │",
+            "└───┘        └───┘",
+        ])
+    );
+    // An explicit gap beats the front matter.
+    let mut config = config_default();
+    config.h_gap = Some(2);
+    assert_eq!(
+        render_dsl_with_config(&src, &config).unwrap(),
+        lines(&[
+            "   ┌───┐",
+            "   │ A │",
+            "   └─┬─┘",
+            "     │",
+            "  ┌──┴───┐",
+            "  ▼      ▼",
+            "┌───┐  ┌───┐",
+            "│ B │  │ C │",
+            "└───┘  └───┘",
+        ])
+    );
+}
+
+#[test]
+fn test_unicode_diamond_corners_override() {
+    use mermaid_ascii::diamond_corners_unicode;
+
+    let src = "graph TD\n    A --> B{Decide}\n    B --> C\n";
+    let mut config = config_default();
+    config.box_chars.diamond_corners = diamond_corners_unicode();
+    // Edges still meet the slanted box on its flat sides; rectangles are untouched
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "  ┌───┐",
+            "  │ A │",
+            "  └─┬─┘",
+            "    │",
+            "    └┐",
+            "     │",
+            "◢────▼───◣",
+            "│ Decide │",
+            "◥────┬───◤",
+            "     │",
+            "    ┌┘",
+            "    ▼",
+            "  ┌───┐",
+            "  │ C │",
+            "  └───┘",
+        ])
+    );
+}
+
+#[test]
+fn test_measure_dsl_matches_rendered_output() {
+    use mermaid_ascii::measure_dsl;
+
+    let mut sources = vec![
+        LONG_EDGE.to_string(),
+        DUPLICATE_SUBGRAPHS.to_string(),
+        "graph TD\n    A -->|yes| B{Check}\n    B -->|no| C((End))\n".to_string(),
+        String::new(),
+    ];
+    for entry in std::fs::read_dir("_site/examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.to_string_lossy().ends_with(".mm.md") {
+            sources.push(std::fs::read_to_string(path).unwrap());
+        }
+    }
+    for src in &sources {
+        for direction in [None, Some("LR"), Some("RL"), Some("BT")] {
+            let mut config = config_default();
+            config.direction_override = direction.map(|d| d.to_string());
+            let out = render_dsl_with_config(src, &config).unwrap();
+            let width = out.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+            let size = measure_dsl(src, &config).unwrap();
+            assert_eq!(size, (width, out.lines().count()), "{direction:?}\n{out}");
+        }
+    }
+}
+
+#[test]
+fn test_unicode_arrows_charset_uses_plain_arrowheads() {
+    use mermaid_ascii::CharSet;
+
+    let mut config = config_default();
+    config.charset = CharSet::UnicodeArrows;
+    let out = render_dsl_with_config("graph TD\n    A --> B\n", &config).unwrap();
+    let mut rows = A_TO_B_ROWS;
+    rows[5] = "  ↓";
+    assert_eq!(out, lines(&rows));
+    config.direction_override = Some("RL".to_string());
+    let rl = render_dsl_with_config("graph TD\n    A --> B\n", &config).unwrap();
+    assert_eq!(rl, "┌───┐      ┌───┐\n│ B │←─────┤ A │\n└───┘      └───┘\n");
+}
+
+#[test]
+fn test_emphasis_class_draws_heavy_border() {
+    let out = render_dsl(
+        "graph TD\n    A:::emphasis --> B\n    A --> C\n",
+        true,
+        1,
+        None,
+    )
+    .unwrap();
+    // Siblings keep the normal light border
+    let mut rows = FAN_OUT_ROWS;
+    rows[..3].copy_from_slice(&["    ┏━━━┓", "    ┃ A ┃", "    ┗━┯━┛"]);
+    assert_eq!(out, lines(&rows));
+}
+
+#[test]
+fn test_focus_draws_heavy_border() {
+    let mut config = config_default();
+    config.focus = Some("B".to_string());
+    let out = render_dsl_with_config(FAN_OUT, &config).unwrap();
+    let mut rows = FAN_OUT_ROWS;
+    rows[6..].copy_from_slice(&["┏━━━┓    ┌───┐", "┃ B ┃    │ C │", "┗━━━┛    └───┘"]);
+    assert_eq!(out, lines(&rows));
+    config.focus = Some("Nope".to_string());
+    let out = render_dsl_with_config(FAN_OUT, &config).unwrap();
+    assert_eq!(out, lines(&FAN_OUT_ROWS));
+}
+
+#[test]
+fn test_graph_builder_renders_like_dsl() {
+    use mermaid_ascii::{Direction, EdgeType, GraphBuilder, NodeShape, render_graph};
+
+    let graph = GraphBuilder::new(Direction::TD)
+        .node("A", "Start", NodeShape::Rectangle)
+        .node("C", "Done", NodeShape::Rounded)
+        .edge("A", "B", EdgeType::Arrow, Some("go"))
+        .edge("B", "C", EdgeType::DottedArrow, None)
+        .build();
+    let config = config_default();
+    let dsl = "graph TD\n    A[Start]\n    C(Done)\n    A -->|go| B\n    B -.-> C\n";
+    assert_eq!(
+        render_graph(&graph, &config).unwrap(),
+        render_dsl_with_config(dsl, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_repeated_lr_renders_are_identical() {
+    use mermaid_ascii::{Direction, EdgeType, GraphBuilder, render_graph, render_svg_with_config};
+
+    let graph = GraphBuilder::new(Direction::LR)
+        .edge("A", "B", EdgeType::Arrow, Some("go"))
+        .edge("A", "C", EdgeType::Arrow, None)
+        .edge("B", "D", EdgeType::Arrow, None)
+        .build();
+    let config = config_default();
+    let first = render_graph(&graph, &config).unwrap();
+    let mut td = config_default();
+    td.direction_override = Some("TD".to_string());
+    render_graph(&graph, &td).unwrap();
+    assert_eq!(render_graph(&graph, &config).unwrap(), first);
+    assert_eq!(render_graph(&graph, &config).unwrap(), first);
+
+    // Same for the SVG path, which shares the transposed layout IR
+    let src = "graph LR\n    A -->|go| B\n    A --> C\n    B --> D\n";
+    assert_eq!(render_dsl_with_config(src, &config).unwrap(), first);
+    let svg = render_svg_with_config(src, &config).unwrap();
+    assert_eq!(render_svg_with_config(src, &config).unwrap(), svg);
+}
+
+#[test]
+fn test_cjk_subgraph_title_sizes_box_by_display_width() {
+    let src = "graph TD\n    subgraph 中文标题很长的名字\n        A\n    end\n";
+    // Every row spans the same number of terminal columns, the member
+    // centred under the wider title
+    assert_eq!(
+        render_dsl(src, true, 1, None).unwrap(),
+        lines(&[
+            "┌────────────────────────┐",
+            "│   中文标题很长的名字   │",
+            "│         ┌───┐          │",
+            "│         │ A │          │",
+            "│         └───┘          │",
+            "└────────────────────────┘",
+        ])
+    );
+}
+
+#[test]
+fn test_long_edge_label_widens_gap() {
+    // LR: the label fits in the gap, clear of both boxes
+    let out = render_dsl("graph LR\n    A -->|a very long label| B\n", true, 1, None).unwrap();
+    assert_eq!(
+        out,
+        lines(&[
+            "┌───┐ a very long label  ┌───┐",
+            "│ A ├───────────────────►│ B │",
+            "└───┘                    └───┘",
+        ])
+    );
+    let out = render_dsl("graph TD\n    A -->|a very long label| B\n", true, 1, None).unwrap();
+    assert_eq!(
+        out,
+        lines(&[
+            "┌───┐",
+            "│ A │",
+            "└─┬─┘",
+            "  │",
+            "  │",
+            "  a very long label",
+            "  ▼",
+            "┌───┐",
+            "│ B │",
+            "└───┘",
+        ])
+    );
+    // TD: sibling targets are spaced so their labels do not run together
+    let out = render_dsl(
+        "graph TD\n    A -->|a very long label| B\n    A -->|another long label| C\n",
+        true,
+        1,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        out,
+        lines(&[
+            "          ┌───┐",
+            "          │ A │",
+            "          └─┬─┘",
+            "            │",
+            "  a very lon│ label   another long label",
+            "  ┌─────────┴─────────┐",
+            "  ▼                   ▼",
+            "┌───┐               ┌───┐",
+            "│ B │               │ C │",
+            "└───┘               └───┘",
+        ])
+    );
+}
+
+#[test]
+fn test_render_to_writer_matches_string_api() {
+    use mermaid_ascii::render_dsl_to_writer;
+
+    for direction in [None, Some("LR"), Some("RL"), Some("BT")] {
+        let mut config = config_default();
+        config.direction_override = direction.map(|d| d.to_string());
+        let mut out: Vec<u8> = Vec::new();
+        render_dsl_to_writer(FAN_OUT, &config, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            render_dsl_with_config(FAN_OUT, &config).unwrap(),
+            "{direction:?}"
+        );
+    }
+}
+
+#[test]
+fn test_line_ending_and_trailing_newline() {
+    use mermaid_ascii::{LineEnding, config_new};
+
+    let cases = [
+        (LineEnding::Lf, true, "+---+\n| A |\n+---+\n"),
+        (LineEnding::Lf, false, "+---+\n| A |\n+---+"),
+        (LineEnding::CrLf, true, "+---+\r\n| A |\r\n+---+\r\n"),
+        (LineEnding::CrLf, false, "+---+\r\n| A |\r\n+---+"),
+    ];
+    for (line_ending, trailing_newline, expected) in cases {
+        let mut config = config_new(false, 1, None);
+        config.line_ending = line_ending;
+        config.trailing_newline = trailing_newline;
+        let out = render_dsl_with_config("graph BT\n    A\n", &config).unwrap();
+        assert_eq!(out, expected);
+    }
+}
+
+#[test]
+fn test_progress_bar_fills_node() {
+    let src = "graph TD\n    A[\"Task|60%\"]\n";
+    let mut config = config_default();
+    config.progress_bars = true;
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "┌────────────┐",
+            "│    Task    │",
+            "│ ██████░░░░ │",
+            "└────────────┘",
+        ])
+    );
+
+    // Off by default: the label is drawn as written.
+    assert_eq!(
+        render_dsl_with_config(src, &config_default()).unwrap(),
+        "┌──────────┐\n│ Task|60% │\n└──────────┘\n"
+    );
+}
+
+#[test]
+fn test_render_many_separates_diagrams_once() {
+    use mermaid_ascii::{config_new, render_many};
+
+    let config = config_new(false, 1, None);
+    let out = render_many(&["graph TD\n    A\n", "graph TD\n    LongName\n"], &config).unwrap();
+    let separator = "-".repeat(12);
+    assert_eq!(
+        out,
+        format!("+---+\n| A |\n+---+\n\n{separator}\n\n+----------+\n| LongName |\n+----------+\n")
+    );
+
+    let mut config = config_default();
+    config.stack_separator = Some("=".to_string());
+    let out = render_many(&["graph TD\n    A\n", "", "graph TD\n    B\n"], &config).unwrap();
+    assert_eq!(out, "┌───┐\n│ A │\n└───┘\n\n=====\n\n┌───┐\n│ B │\n└───┘\n");
+}
+
+#[test]
+fn test_skeleton_is_smaller_and_keeps_edges() {
+    let src = "graph TD\n    A --> B\n    A --> C\n    B --> D\n    B --> E\n    C --> F\n    C --> G\n    D --> H\n    E --> I\n    F --> J\n";
+    let full = render_dsl_with_config(src, &config_default()).unwrap();
+    let mut config = config_default();
+    config.skeleton = true;
+    let skeleton = render_dsl_with_config(src, &config).unwrap();
+
+    let area = |out: &str| {
+        let width = out.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        width * out.lines().count()
+    };
+    assert!(area(&skeleton) * 2 < area(&full), "{skeleton}\n{full}");
+
+    assert_eq!(
+        render_dsl_with_config(FAN_OUT, &config).unwrap(),
+        lines(&["   ●A", "    │", " ┌──┴──┐", " ▼     ▼", "●B    ●C"])
+    );
+}
+
+#[test]
+fn test_subgraph_without_nodes_renders_empty_group() {
+    for direction in [None, Some("LR")] {
+        let out = render_dsl("graph TD\n subgraph G\n end", true, 1, direction).unwrap();
+        assert_eq!(
+            out,
+            lines(&[
+                "┌───────┐",
+                "│   G   │",
+                "│       │",
+                "│       │",
+                "│       │",
+                "└───────┘",
+            ]),
+            "{direction:?}"
+        );
+    }
+}
+
+#[test]
+fn test_render_layer_range() {
+    use mermaid_ascii::render_layer_range;
+
+    let src = "graph TD\n    A --> B\n    B --> C\n    C --> D\n";
+    let config = config_default();
+    // B's outgoing edge is cut into a stub without an arrowhead
+    assert_eq!(
+        render_layer_range(src, 0, 1, &config).unwrap(),
+        lines(&[
+            "┌───┐",
+            "│ A │",
+            "└─┬─┘",
+            "  │",
+            "  │",
+            "  ▼",
+            "┌───┐",
+            "│ B │",
+            "└─┬─┘",
+            "  │",
+            "  │",
+            "  │",
+        ])
+    );
+    assert_eq!(
+        render_layer_range(src, 1, 2, &config).unwrap(),
+        lines(&[
+            "  │",
+            "  │",
+            "  ▼",
+            "┌───┐",
+            "│ B │",
+            "└─┬─┘",
+            "  │",
+            "  │",
+            "  ▼",
+            "┌───┐",
+            "│ C │",
+            "└─┬─┘",
+            "  │",
+            "  │",
+            "  │",
+        ])
+    );
+    let full = render_dsl_with_config(src, &config).unwrap();
+    assert_eq!(render_layer_range(src, 0, 3, &config).unwrap(), full);
+}
+
+#[test]
+fn test_edge_classes() {
+    use mermaid_ascii::render_svg_with_config;
+
+    let src = "graph TD\n    classDef hot stroke:red\n    A -->:::hot B\n    A -->|x|:::hot C\n    A --> D\n";
+    let svg = render_svg_with_config(src, &config_default()).unwrap();
+    let edges: Vec<&str> = svg
+        .lines()
+        .filter(|l| l.starts_with("<path") || l.starts_with("<polyline"))
+        .collect();
+    assert_eq!(edges.len(), 3);
+    assert_eq!(
+        edges
+            .iter()
+            .filter(|l| l.contains(r#"class="hot""#))
+            .count(),
+        2
+    );
+
+    // `thick` / `dotted` restyle the ASCII edge
+    let thick =
+        render_dsl_with_config("graph TD\n    A -->:::thick B\n", &config_default()).unwrap();
+    let mut rows = A_TO_B_ROWS;
+    rows[3..5].copy_from_slice(&["  ║", "  ║"]);
+    assert_eq!(thick, lines(&rows));
+    let written = render_dsl_with_config("graph TD\n    A ==> B\n", &config_default()).unwrap();
+    assert_eq!(thick, written);
+}
+
+#[test]
+fn test_custom_edge_router() {
+    use mermaid_ascii::routing::{self, Grid};
+    use mermaid_ascii::{AStarRouter, EdgeRouter, render_dsl_with_router};
+
+    let mut grid = Grid::new(10, 10);
+    grid.mark_blocked(2, 4, 6, 2);
+    assert!(grid.is_free(0, 0));
+    assert!(!grid.is_free(3, 5));
+    assert!(!grid.is_free(-1, 0));
+    let path = routing::a_star(&mut grid, (4, 1), (4, 8)).unwrap();
+    assert_eq!(path.first(), Some(&(4, 1)));
+    assert_eq!(path.last(), Some(&(4, 8)));
+    assert!(path.len() > 2, "path must detour around the box: {path:?}");
+
+    // A router that ignores obstacles and always turns halfway down.
+    struct StraightRouter;
+    impl EdgeRouter for StraightRouter {
+        fn route(
+            &self,
+            _grid: &mut Grid,
+            exit: (i32, i32),
+            entry: (i32, i32),
+        ) -> Option<Vec<(i32, i32)>> {
+            let mid = (exit.1 + entry.1) / 2;
+            Some(vec![exit, (exit.0, mid), (entry.0, mid), entry])
+        }
+    }
+    let config = config_default();
+    let custom = render_dsl_with_router(FAN_OUT, &config, &StraightRouter).unwrap();
+    let default = render_dsl_with_router(FAN_OUT, &config, &AStarRouter).unwrap();
+    assert_eq!(default, render_dsl_with_config(FAN_OUT, &config).unwrap());
+    assert_eq!(custom, lines(&FAN_OUT_ROWS));
+}
+
+#[test]
+fn test_frame_surrounds_diagram() {
+    use mermaid_ascii::{CharSet, measure_dsl};
+
+    let src = "graph BT\n    A --> Longer\n";
+    let mut config = config_default();
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "┌────────┐",
+            "│ Longer │",
+            "└────────┘",
+            "     ▲",
+            "    ┌┘",
+            "    │",
+            "  ┌─┴─┐",
+            "  │ A │",
+            "  └───┘",
+        ])
+    );
+    config.frame = true;
+    config.frame_padding = 1;
+    // The flipped diagram sits one blank cell inside the border
+    let framed = render_dsl_with_config(src, &config).unwrap();
+    assert_eq!(
+        framed,
+        lines(&[
+            "┌────────────┐",
+            "│            │",
+            "│ ┌────────┐ │",
+            "│ │ Longer │ │",
+            "│ └────────┘ │",
+            "│      ▲     │",
+            "│     ┌┘     │",
+            "│     │      │",
+            "│   ┌─┴─┐    │",
+            "│   │ A │    │",
+            "│   └───┘    │",
+            "│            │",
+            "└────────────┘",
+        ])
+    );
+    assert_eq!(measure_dsl(src, &config).unwrap(), (14, 13));
+
+    config.charset = CharSet::Ascii;
+    config.frame_padding = 0;
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "+----------+",
+            "|+--------+|",
+            "|| Longer ||",
+            "|+--------+|",
+            "|     ^    |",
+            "|    ++    |",
+            "|    |     |",
+            "|  +-+-+   |",
+            "|  | A |   |",
+            "|  +---+   |",
+            "+----------+",
+        ])
+    );
+}
+
+#[test]
+fn test_render_to_canvas_takes_annotations() {
+    use mermaid_ascii::render_to_canvas;
+
+    let src = "graph TD\n    A --> B\n";
+    let config = config_default();
+    let mut canvas = render_to_canvas(src, &config).unwrap();
+    assert_eq!(canvas.render_to_string(), lines(&A_TO_B_ROWS));
+
+    let (w, h) = (canvas.width(), canvas.height());
+    canvas.write_str(w + 2, 0, "v1.2");
+    canvas.draw_box(0, h + 1, 6, 3);
+    canvas.write_str(1, h + 2, "note");
+    // The diagram itself is untouched
+    assert_eq!(
+        canvas.render_to_string(),
+        "┌───┐  v1.2\n│ A │\n└─┬─┘\n  │\n  │\n  ▼\n┌───┐\n│ B │\n└───┘\n\n┌────┐\n│note│\n└────┘\n"
+    );
+    assert_eq!(canvas.cell(w + 2, 0), Some("v"));
+}
+
+#[test]
+fn test_subgraph_title_placement() {
+    use mermaid_ascii::TitlePos;
+
+    let src = "graph TD\n    subgraph Group\n        A\n    end\n";
+    let mut config = config_default();
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "┌───────────┐",
+            "│   Group   │",
+            "│   ┌───┐   │",
+            "│   │ A │   │",
+            "│   └───┘   │",
+            "└───────────┘",
+        ])
+    );
+
+    config.subgraph_title = TitlePos::OnBorder;
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "┌── Group ──┐",
+            "│   ┌───┐   │",
+            "│   │ A │   │",
+            "│   └───┘   │",
+            "└───────────┘",
+        ])
+    );
+    #[cfg(feature = "json")]
+    assert_eq!(
+        mermaid_ascii::config_from_json(r#"{"subgraphTitle":"onBorder"}"#)
+            .unwrap()
+            .subgraph_title,
+        TitlePos::OnBorder
+    );
+}
+
+#[test]
+fn test_show_attrs_adds_badge_row() {
+    let src = "graph TD\n    A[Api]:::service --> B\n";
+    let mut config = config_default();
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "┌─────┐",
+            "│ Api │",
+            "└──┬──┘",
+            "   │",
+            "   │",
+            "   ▼",
+            " ┌───┐",
+            " │ B │",
+            " └───┘",
+        ])
+    );
+
+    // B has no classes and keeps a one-line box
+    config.show_attrs = true;
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "┌───────────┐",
+            "│    Api    │",
+            "│ «service» │",
+            "└─────┬─────┘",
+            "      │",
+            "      │",
+            "      ▼",
+            "    ┌───┐",
+            "    │ B │",
+            "    └───┘",
+        ])
+    );
+}
+
+#[test]
+fn test_compact_preset_is_smaller_and_intact() {
+    use mermaid_ascii::{RenderConfig, overlap_warnings};
+
+    let src = "graph TD\n    A[Load] --> B[Parse input] & C[Check]\n    B -->|ok| D\n    C --> D\n    subgraph Out\n        D[Write]\n    end\n";
+    let full = render_dsl_with_config(src, &config_default()).unwrap();
+    let mut config = RenderConfig::compact_preset();
+    config.assert_no_overlap = true;
+    assert_eq!(
+        overlap_warnings(src, &config).unwrap(),
+        Vec::<String>::new()
+    );
+    let compact = render_dsl_with_config(src, &config).unwrap();
+    let chars = |out: &str| out.chars().filter(|c| *c != '\n').count();
+    assert!(chars(&compact) < chars(&full), "{compact}\n{full}");
+
+    // Boxes stay closed, just without the padding column
+    let src = "graph TD\n    A[Load] --> B[Parse]\n    A --> C[Check]\n";
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&[
+            "     ┌────┐",
+            "     │Load│",
+            "     └──┬─┘",
+            "        │",
+            "   ┌────┴───┐",
+            "   ▼        ▼",
+            "┌─────┐  ┌─────┐",
+            "│Parse│  │Check│",
+            "└─────┘  └─────┘",
+        ])
+    );
+}
+
+#[test]
+fn test_inline_trivial_draws_one_line() {
+    use mermaid_ascii::CharSet;
+
+    let mut config = config_default();
+    config.inline_trivial = true;
+    let render = |src: &str| render_dsl_with_config(src, &config).unwrap();
+    assert_eq!(render("graph LR\n A-->B"), "[A] ───► [B]\n");
+    assert_eq!(
+        render("graph TD\n    A(Start) -->|yes| B{Done}\n"),
+        "(Start) ─yes──► {Done}\n"
+    );
+    // Anything bigger gets the full layout
+    assert_eq!(
+        render("graph LR\n A-->B-->C"),
+        lines(&[
+            "┌───┐      ┌───┐      ┌───┐",
+            "│ A ├─────►│ B ├─────►│ C │",
+            "└───┘      └───┘      └───┘",
+        ])
+    );
+    config.charset = CharSet::Ascii;
+    assert_eq!(
+        render_dsl_with_config("graph LR\n A-->B", &config).unwrap(),
+        "[A] ---> [B]\n"
+    );
+}
+
+#[test]
+fn test_exit_stubs_can_be_disabled() {
+    let src = "graph TD\n    A --> B\n";
+    let mut config = config_default();
+    assert_eq!(
+        render_dsl_with_config(src, &config).unwrap(),
+        lines(&A_TO_B_ROWS)
+    );
+    config.exit_stubs = false;
+    let mut rows = A_TO_B_ROWS;
+    rows[2] = "└───┘";
+    assert_eq!(render_dsl_with_config(src, &config).unwrap(), lines(&rows));
+}
+
+#[test]
+fn test_render_to_cells_matches_text() {
+    use mermaid_ascii::render_to_cells;
+
+    let src = "graph LR\n    A -->|go| Longer\n    A --> C\n";
+    for direction in [None, Some("BT"), Some("RL")] {
+        let mut config = config_default();
+        config.direction_override = direction.map(str::to_string);
+        let text = render_dsl_with_config(src, &config).unwrap();
+        let grid = render_to_cells(src, &config).unwrap();
+        let width = text.lines().map(|l| l.chars().count()).max().unwrap();
+        assert_eq!(grid.len(), text.lines().count());
+        assert!(grid.iter().all(|row| row.len() == width));
+        for (row, line) in grid.iter().zip(text.lines()) {
+            let row: String = row.iter().collect();
+            assert_eq!(row.trim_end(), line);
+        }
+    }
+    assert!(render_to_cells("", &config_default()).unwrap().is_empty());
+}
+
+#[test]
+fn test_ascii_charset_emits_only_ascii() {
+    use mermaid_ascii::CharSet;
+
+    // Tees, crosses and corners from fan-in/fan-out, every connector
+    // style, shapes, a back edge and a subgraph.
+    let src = "graph TD\n    A --> B & C & D\n    B --> E\n    C -.-> E\n    \
+               D ==> E\n    A <--> F\n    F -.- G\n    F === H\n    E --> A\n    \
+               B --- F\n    I{{Hex}} --> J((Circle))\n    K{Diamond} -->|yes| L(Round)\n    \
+               subgraph S[Group]\n        M <-.-> N\n        N <==> M\n    end\n    \
+               H --> M\n";
+    for direction in ["TD", "LR", "BT", "RL"] {
+        let mut config = config_default();
+        config.charset = CharSet::Ascii;
+        config.direction_override = Some(direction.to_string());
+        let out = render_dsl_with_config(src, &config).unwrap();
+        assert!(out.is_ascii(), "{direction}:\n{out}");
+    }
+}
+
+#[test]
+fn test_summarize_folds_leaves_past_max_nodes() {
+    let mut src = String::from("graph TD\n    Root --> Hub\n    Root --> Other\n");
+    for i in 0..50 {
+        src.push_str(&format!("    Hub --> L{i}\n"));
+    }
+    let mut config = config_default();
+    let full = render_dsl_with_config(&src, &config).unwrap();
+
+    // Folding the hub's leaves is enough, so `Other` keeps its own box.
+    config.summarize = true;
+    config.max_nodes = 10;
+    assert_eq!(
+        render_dsl_with_config(&src, &config).unwrap(),
+        lines(&[
+            "         ┌──────┐",
+            "         │ Root │",
+            "         └───┬──┘",
+            "             │",
+            "       ┌─────┴────────┐",
+            "       ▼              ▼",
+            "┌────────────┐    ┌───────┐",
+            "│    Hub     │    │ Other │",
+            "│ (+50 more) │    └───────┘",
+            "└────────────┘",
+        ])
+    );
+
+    config.max_nodes = 60;
+    assert_eq!(render_dsl_with_config(&src, &config).unwrap(), full);
+}
@@ -0,0 +1,106 @@
+//! Integration test: render small diagrams to SVG through the public API and
+//! check the elements and attributes the output carries.
+
+mod common;
+
+use common::{FAN_OUT, lines};
+use mermaid_ascii::{
+    check_dsl, config_default, render_dsl, render_svg_dsl, render_svg_with_config,
+};
+
+/// Centre of the `<text>` element carrying `label` in an SVG document.
+fn svg_text_pos(svg: &str, label: &str) -> (i32, i32) {
+    let line = svg
+        .lines()
+        .find(|l| l.starts_with("<text") && l.ends_with(&format!(">{label}</text>")))
+        .unwrap();
+    let attr = |name: &str| -> i32 {
+        let start = line.find(&format!(" {name}=\"")).unwrap() + name.len() + 3;
+        let end = start + line[start..].find('"').unwrap();
+        line[start..end].parse().unwrap()
+    };
+    (attr("x"), attr("y"))
+}
+
+#[test]
+fn test_svg_lr_subgraph_inside_td_graph() {
+    let src = "graph TD\n    Top --> G\n    subgraph G\n        direction LR\n        A --> B --> C\n    end\n    G --> Bottom\n";
+    let svg = render_svg_dsl(src, 1, None).unwrap();
+    let (ax, ay) = svg_text_pos(&svg, "A");
+    let (bx, by) = svg_text_pos(&svg, "B");
+    let (cx, cy) = svg_text_pos(&svg, "C");
+    assert!(ax < bx && bx < cx);
+    assert!(ay == by && by == cy);
+    let (_, top_y) = svg_text_pos(&svg, "Top");
+    let (_, bottom_y) = svg_text_pos(&svg, "Bottom");
+    assert!(top_y < ay && ay < bottom_y);
+}
+
+#[test]
+fn test_svg_rounds_edge_turns() {
+    let edge_paths = |src: &str, radius: i32| -> Vec<String> {
+        let mut config = config_default();
+        config.svg_corner_radius = radius;
+        let svg = render_svg_with_config(src, &config).unwrap();
+        svg.lines()
+            .filter(|l| l.starts_with("<path") || l.starts_with("<polyline"))
+            .map(str::to_string)
+            .collect()
+    };
+    let turning = edge_paths(FAN_OUT, 4);
+    assert_eq!(turning.len(), 2);
+    assert!(turning.iter().all(|p| p.contains(" Q")), "{turning:?}");
+
+    let straight = edge_paths("graph TD\n    A --> B\n", 4);
+    assert_eq!(straight.len(), 1);
+    assert!(
+        !straight[0].contains('Q') && !straight[0].contains(" A"),
+        "{straight:?}"
+    );
+
+    // Radius 0 keeps the sharp-cornered polyline.
+    assert!(
+        edge_paths(FAN_OUT, 0)
+            .iter()
+            .all(|p| p.starts_with("<polyline"))
+    );
+}
+
+#[test]
+fn test_accessibility_title_and_description() {
+    let src = "graph TD\n    accTitle: My Flow\n    accDescr {\n        Two steps,\n        one arrow\n    }\n    A --> B\n";
+    assert_eq!(check_dsl(src), Ok(()));
+    let svg = render_svg_dsl(src, 1, None).unwrap();
+    assert!(svg.contains(r#"role="img" aria-label="My Flow">"#), "{svg}");
+    assert!(
+        svg.contains("<title>My Flow</title>\n<desc>Two steps,\none arrow</desc>"),
+        "{svg}"
+    );
+
+    let plain = "graph TD\n    A --> B\n";
+    assert_eq!(
+        render_dsl(src, true, 1, None).unwrap(),
+        render_dsl(plain, true, 1, None).unwrap()
+    );
+    assert!(!render_svg_dsl(plain, 1, None).unwrap().contains("<title>"));
+}
+
+#[test]
+fn test_svg_data_attributes_name_endpoints() {
+    let svg = render_svg_dsl("graph TD\n    A --> B\n", 1, None).unwrap();
+    let edge = svg.lines().find(|l| l.starts_with("<path")).unwrap();
+    assert!(edge.contains(r#"data-from="A" data-to="B""#), "{edge}");
+    assert!(svg.contains(r#"stroke-width="1.5" data-id="A"/>"#), "{svg}");
+    assert!(svg.contains(r#"stroke-width="1.5" data-id="B"/>"#), "{svg}");
+
+    // Member edges keep the member's id, not the container's
+    let svg = render_svg_dsl(
+        "graph TD\n    subgraph G\n        A\n    end\n    A --> B\n",
+        1,
+        None,
+    )
+    .unwrap();
+    assert!(svg.contains(r#"data-from="A" data-to="B""#), "{svg}");
+    assert!(svg.contains(r#"data-id="G"/>"#), "{svg}");
+    assert!(!svg.contains("__"), "{svg}");
+}